@@ -0,0 +1,117 @@
+//! Helper for build scripts, making the items of generated source files public the
+//! way the [`fully_pub`](https://docs.rs/fully_pub) attribute macro would.
+//!
+//! The macro can't be applied to files produced at build time, like the output of
+//! `bindgen` or `prost`, since they are included with `include!` and never annotated.
+//! This crate processes such files before they are compiled instead:
+//!
+//! ```no_run
+//! // build.rs
+//! use std::env;
+//! use std::path::PathBuf;
+//!
+//! fn main() {
+//!     let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("generated.rs");
+//!
+//!     // ... generate `out` ...
+//!
+//!     fully_pub_build::process_file(&out, &out, fully_pub_build::Options::default()).unwrap();
+//! }
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs, io};
+
+use fully_pub_core::Config;
+
+/// Options of [`process_file`].
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Whether the items of nested modules are made public too, as with
+    /// `#[fully_pub(recursive)]`. Defaults to `true`.
+    pub recursive: bool,
+    /// Whether every item of the file is made public, as if the whole file was
+    /// annotated with the macro. When `false`, only the items annotated in the file
+    /// are expanded. Defaults to `true`.
+    pub all: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            recursive: true,
+            all: true,
+        }
+    }
+}
+
+/// An error met while processing a file.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read or written.
+    Io(PathBuf, io::Error),
+    /// The input file could not be parsed, or contains an invalid use of the macro.
+    Syn(PathBuf, syn::Error),
+    /// The configuration of the macro in the manifest of the crate is invalid.
+    Config(fully_pub_core::ConfigError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            Error::Syn(path, e) => {
+                let start = e.span().start();
+                write!(
+                    f,
+                    "{}:{}:{}: {e}",
+                    path.display(),
+                    start.line,
+                    start.column + 1
+                )
+            }
+            Error::Config(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Reads the Rust source file at `in_path`, makes its items public according to
+/// the options, and writes the result to `out_path`, which may be the same file.
+///
+/// Everything but the visibilities is left as written in the input file. The defaults
+/// of `[package.metadata.fully_pub]` and `fully_pub::config!` apply, except for
+/// `recursive`. The input file, the manifest and the crate root are registered with
+/// `cargo:rerun-if-changed`, so this must only be called from build scripts.
+pub fn process_file(
+    in_path: impl AsRef<Path>,
+    out_path: impl AsRef<Path>,
+    options: Options,
+) -> Result<(), Error> {
+    let (in_path, out_path) = (in_path.as_ref(), out_path.as_ref());
+    let source = fs::read_to_string(in_path).map_err(|e| Error::Io(in_path.to_path_buf(), e))?;
+
+    let mut core_options = fully_pub_core::Options::default();
+    core_options.all = options.all;
+    core_options.recursive = options.recursive;
+
+    if let Some(manifest_dir) = env::var_os("CARGO_MANIFEST_DIR") {
+        let manifest_dir = Path::new(&manifest_dir);
+        core_options.config = Config::load(manifest_dir).map_err(Error::Config)?;
+        println!(
+            "cargo:rerun-if-changed={}",
+            manifest_dir.join("Cargo.toml").display()
+        );
+        if let Ok(root) = fully_pub_core::source::crate_root(manifest_dir) {
+            println!("cargo:rerun-if-changed={}", root.display());
+        }
+        println!("cargo:rerun-if-env-changed={}", fully_pub_core::VIS_VAR);
+    }
+
+    let processed = fully_pub_core::publish(&source, &core_options)
+        .map_err(|e| Error::Syn(in_path.to_path_buf(), e))?;
+
+    println!("cargo:rerun-if-changed={}", in_path.display());
+    fs::write(out_path, processed).map_err(|e| Error::Io(out_path.to_path_buf(), e))
+}
@@ -0,0 +1,142 @@
+//! Unified diffs of the rewritten sources, for `cargo fully-pub diff` and the
+//! `--diff` option of the rewriting commands.
+
+/// The number of unchanged lines shown around the changes.
+const CONTEXT: usize = 3;
+
+/// What happens to a line of the original text.
+#[derive(Clone, Copy, PartialEq)]
+enum Edit {
+    Keep,
+    Remove,
+    Insert,
+}
+
+/// Returns the shortest list of edits turning the lines `a` into the lines `b`, with
+/// the algorithm of Myers, which is fast for the few changes of a rewrite.
+fn edits(a: &[&str], b: &[&str]) -> Vec<Edit> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    // The furthest `x` reached on each diagonal `k = x - y` with each number of edits
+    // `d`, indexed by `k + d + 1`.
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    let mut v = vec![0; 3];
+
+    'search: for d in 0..=n + m {
+        let mut next = vec![0; 2 * d as usize + 3];
+        for k in (-d..=d).step_by(2) {
+            // The diagonals reached with one edit less.
+            let at = |k: isize| v[(k + d) as usize];
+            let mut x = match k == -d || k != d && at(k - 1) < at(k + 1) {
+                true => at(k + 1),
+                false => at(k - 1) + 1,
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                (x, y) = (x + 1, y + 1);
+            }
+
+            next[(k + d + 1) as usize] = x;
+            if x >= n && y >= m {
+                trace.push(next);
+                break 'search;
+            }
+        }
+        trace.push(next);
+        v = trace[d as usize].clone();
+    }
+
+    let mut edits = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let v = &trace[d as usize - 1];
+        let at = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let previous = match k == -d || k != d && at(k - 1) < at(k + 1) {
+            true => k + 1,
+            false => k - 1,
+        };
+        let (px, py) = (at(previous), at(previous) - previous);
+
+        while x > px && y > py {
+            edits.push(Edit::Keep);
+            (x, y) = (x - 1, y - 1);
+        }
+        edits.push(if x == px { Edit::Insert } else { Edit::Remove });
+        (x, y) = (px, py);
+    }
+    edits.extend((0..x).map(|_| Edit::Keep));
+
+    edits.reverse();
+    edits
+}
+
+/// Returns the changes from `old` to `new` as a unified diff of the file at `path`,
+/// empty if they are the same, to be applied with `patch -p1` or `git apply`.
+///
+/// ```text
+/// --- a/src/lib.rs
+/// +++ b/src/lib.rs
+/// @@ -1,2 +1,2 @@
+/// -#[fully_pub]
+/// -struct Meters(f64);
+/// +pub struct Meters(pub f64);
+/// ```
+pub fn unified(path: &str, old: &str, new: &str) -> String {
+    let (a, b): (Vec<_>, Vec<_>) = (old.lines().collect(), new.lines().collect());
+    let edits = edits(&a, &b);
+
+    // The lines of the original and the rewritten text at each edit.
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in &edits {
+        positions.push((i, j));
+        match edit {
+            Edit::Keep => (i, j) = (i + 1, j + 1),
+            Edit::Remove => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    let changes: Vec<_> = (0..edits.len())
+        .filter(|&index| edits[index] != Edit::Keep)
+        .collect();
+    let Some(&first) = changes.first() else {
+        return String::new();
+    };
+
+    // The ranges of edits shown by each hunk, merged when their contexts meet.
+    let mut hunks = vec![(first.saturating_sub(CONTEXT), first + 1)];
+    for &change in &changes[1..] {
+        let last = hunks.last_mut().unwrap();
+        match change - last.1 <= 2 * CONTEXT {
+            true => last.1 = change + 1,
+            false => hunks.push((change - CONTEXT, change + 1)),
+        }
+    }
+
+    let mut diff = format!("--- a/{path}\n+++ b/{path}\n");
+    for (start, end) in hunks {
+        let end = (end + CONTEXT).min(edits.len());
+        let ((i, j), (i_end, j_end)) = (positions[start], positions[end]);
+        // An empty range is written as starting at the line before it.
+        let range = |start: usize, len: usize| match len {
+            0 => format!("{start},0"),
+            _ => format!("{},{len}", start + 1),
+        };
+        diff += &format!("@@ -{} +{} @@\n", range(i, i_end - i), range(j, j_end - j));
+
+        for index in start..end {
+            let (i, j) = positions[index];
+            let line = match edits[index] {
+                Edit::Keep => format!(" {}", a[i]),
+                Edit::Remove => format!("-{}", a[i]),
+                Edit::Insert => format!("+{}", b[j]),
+            };
+            diff += &line;
+            diff.push('\n');
+        }
+    }
+
+    diff
+}
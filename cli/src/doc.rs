@@ -0,0 +1,158 @@
+//! `cargo fully-pub doc`: builds the documentation of the crate with and without
+//! the macro, and reports which documented items the macro contributes.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use fully_pub_core::DISABLE_VAR;
+
+use crate::{unexpected, Cli, Common, Result};
+
+/// Directories of the generated documentation that do not describe items.
+const IGNORED_DIRS: &[&str] = &[
+    "implementors",
+    "search.desc",
+    "src",
+    "static.files",
+    "trait.impl",
+    "type.impl",
+];
+
+/// Prefixes of the anchors that identify members documented inside a page.
+const MEMBER_ANCHORS: &[&str] = &[
+    "associatedconstant.",
+    "associatedtype.",
+    "method.",
+    "structfield.",
+    "tymethod.",
+    "variant.",
+];
+
+/// Anchors of the sections listing trait implementations, whose members are the
+/// same whether the macro is enabled or not.
+const TRAIT_SECTIONS: &[&str] = &[
+    "id=\"trait-implementations\"",
+    "id=\"synthetic-implementations\"",
+    "id=\"blanket-implementations\"",
+];
+
+/// Builds the documentation in `target_dir`, with the macro disabled or not.
+fn build(common: &Common, target_dir: &Path, disabled: bool, extra: &[String]) -> Result<()> {
+    let mut command = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+    command
+        .args(["doc", "--no-deps", "--lib"])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .args(extra);
+
+    if let Some(path) = &common.manifest_path {
+        command.arg("--manifest-path").arg(path);
+    }
+
+    if disabled {
+        command.env(DISABLE_VAR, "1");
+    } else {
+        command.env_remove(DISABLE_VAR);
+    }
+
+    let status = command.status()?;
+    if !status.success() {
+        return Err(format!("`cargo doc` failed with {status}").into());
+    }
+
+    Ok(())
+}
+
+/// Collects the documented items and members found under `dir`, identified by
+/// their page path, followed by an anchor for members.
+fn collect(dir: &Path, prefix: &str, items: &mut BTreeSet<String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = format!("{prefix}{name}");
+
+        if entry.file_type()?.is_dir() {
+            if !IGNORED_DIRS.contains(&name.as_str()) {
+                collect(&entry.path(), &format!("{path}/"), items)?;
+            }
+            continue;
+        }
+
+        let is_item_page = match name.strip_suffix(".html") {
+            Some("index") => !prefix.is_empty(),
+            Some(stem) => stem.contains('.'),
+            None => false,
+        };
+
+        if !is_item_page {
+            continue;
+        }
+
+        let html = fs::read_to_string(entry.path())?;
+        let end = TRAIT_SECTIONS
+            .iter()
+            .filter_map(|section| html.find(section))
+            .min()
+            .unwrap_or(html.len());
+
+        for anchor in html[..end].split("id=\"").skip(1) {
+            let Some(anchor) = anchor.split('"').next() else {
+                continue;
+            };
+
+            if MEMBER_ANCHORS.iter().any(|p| anchor.starts_with(p)) {
+                items.insert(format!("{path}#{anchor}"));
+            }
+        }
+
+        items.insert(path);
+    }
+
+    Ok(())
+}
+
+/// Prints the items of the list, under the given title.
+fn print_section(title: &str, items: &[&String]) {
+    println!("{title} ({}):", items.len());
+    for item in items {
+        println!("    {item}");
+    }
+}
+
+pub fn run(mut cli: Cli) -> Result<()> {
+    let mut common = Common::default();
+    let mut extra = Vec::new();
+
+    while let Some(arg) = cli.next() {
+        if common.parse(&arg, &mut cli)? {
+            continue;
+        }
+
+        match arg.as_str() {
+            // Everything after `--` is passed to `cargo doc`.
+            "--" => extra.extend(cli.args.drain(..)),
+            _ => return Err(unexpected(&arg)),
+        }
+    }
+
+    let target_dir = common.manifest_dir()?.join("target").join("fully-pub-doc");
+    let mut enabled = BTreeSet::new();
+    let mut disabled = BTreeSet::new();
+
+    build(&common, &target_dir.join("enabled"), false, &extra)?;
+    build(&common, &target_dir.join("disabled"), true, &extra)?;
+    collect(&target_dir.join("enabled").join("doc"), "", &mut enabled)?;
+    collect(&target_dir.join("disabled").join("doc"), "", &mut disabled)?;
+
+    let added: Vec<_> = enabled.difference(&disabled).collect();
+    let removed: Vec<_> = disabled.difference(&enabled).collect();
+
+    print_section("Documented only with the macro", &added);
+    if !removed.is_empty() {
+        print_section("Documented only without the macro", &removed);
+    }
+
+    Ok(())
+}
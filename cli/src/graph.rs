@@ -0,0 +1,171 @@
+//! `cargo fully-pub graph`: prints the module tree of the crate, with its items
+//! colored by their visibility after the expansion of the macro.
+
+use fully_pub_core::source::{self, Module};
+use syn::*;
+
+use crate::{unexpected, Cli, Common, Result};
+
+/// The output format of the graph.
+enum Format {
+    Dot,
+    Mermaid,
+}
+
+/// How visible a node of the graph is.
+#[derive(Clone, Copy)]
+enum Class {
+    Public,
+    Restricted,
+    Private,
+}
+
+impl Class {
+    fn of(vis: &Visibility) -> Self {
+        match vis {
+            Visibility::Public(_) => Class::Public,
+            Visibility::Restricted(_) => Class::Restricted,
+            Visibility::Inherited => Class::Private,
+        }
+    }
+
+    /// The name of the class in the Mermaid output.
+    fn name(self) -> &'static str {
+        match self {
+            Class::Public => "public",
+            Class::Restricted => "restricted",
+            Class::Private => "private",
+        }
+    }
+
+    /// The fill color of the nodes of that class.
+    fn color(self) -> &'static str {
+        match self {
+            Class::Public => "#9be29b",
+            Class::Restricted => "#f3d58a",
+            Class::Private => "#e8a0a0",
+        }
+    }
+}
+
+struct Node {
+    label: String,
+    class: Class,
+}
+
+/// A tree of modules and items.
+#[derive(Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    /// Adds a node, linked to its parent if any, and returns its index.
+    fn add(&mut self, parent: Option<usize>, label: String, vis: &Visibility) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            label,
+            class: Class::of(vis),
+        });
+        self.edges.extend(parent.map(|parent| (parent, index)));
+        index
+    }
+
+    /// Adds a module, its items and its nested modules.
+    fn add_module(&mut self, module: &Module, parent: Option<usize>) {
+        let label = match parent {
+            Some(_) => format!("mod {}", module.name),
+            None => module.name.clone(),
+        };
+        let index = self.add(parent, label, &module.vis);
+
+        // Fields are left out, to keep the graph readable.
+        for member in module.items.iter().flat_map(source::members) {
+            if member.kind != "field" {
+                let label = format!("{} {}", member.kind, member.path);
+                self.add(Some(index), label, &member.vis);
+            }
+        }
+
+        for child in &module.children {
+            self.add_module(child, Some(index));
+        }
+    }
+
+    /// Renders the graph in the Graphviz DOT language.
+    fn dot(&self) -> String {
+        let mut out = String::from("digraph crate {\n    node [shape=box, style=filled];\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            out += &format!(
+                "    n{i} [label=\"{}\", fillcolor=\"{}\"];\n",
+                node.label.replace('"', "\\\""),
+                node.class.color(),
+            );
+        }
+
+        for (from, to) in &self.edges {
+            out += &format!("    n{from} -> n{to};\n");
+        }
+
+        out + "}"
+    }
+
+    /// Renders the graph as a Mermaid flowchart.
+    fn mermaid(&self) -> String {
+        let mut out = String::from("graph LR\n");
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            out += &format!(
+                "    n{i}[\"{}\"]:::{}\n",
+                node.label.replace('"', "#quot;"),
+                node.class.name(),
+            );
+        }
+
+        for (from, to) in &self.edges {
+            out += &format!("    n{from} --> n{to}\n");
+        }
+
+        for class in [Class::Public, Class::Restricted, Class::Private] {
+            out += &format!("    classDef {} fill:{}\n", class.name(), class.color());
+        }
+
+        out
+    }
+}
+
+pub fn run(mut cli: Cli) -> Result<()> {
+    let mut common = Common::default();
+    let mut format = Format::Dot;
+
+    while let Some(arg) = cli.next() {
+        if common.parse(&arg, &mut cli)? {
+            continue;
+        }
+
+        match arg.as_str() {
+            "--format" => {
+                format = match cli.value(&arg)?.as_str() {
+                    "dot" => Format::Dot,
+                    "mermaid" => Format::Mermaid,
+                    other => return Err(format!("unknown format `{other}`").into()),
+                }
+            }
+            _ => return Err(unexpected(&arg)),
+        }
+    }
+
+    let config = common.config()?;
+    let module = source::load_crate(&source::crate_root(&common.manifest_dir()?)?, &config)?;
+    let mut graph = Graph::default();
+    graph.add_module(&module, None);
+
+    match format {
+        Format::Dot => println!("{}", graph.dot()),
+        Format::Mermaid => print!("{}", graph.mermaid()),
+    }
+
+    Ok(())
+}
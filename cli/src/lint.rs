@@ -0,0 +1,121 @@
+//! `cargo fully-pub lint`: warns about the visibilities written on members the
+//! attributes of the macro give the same visibility anyway.
+
+use std::fs;
+use std::path::Path;
+
+use fully_pub_core::source::{self, Module};
+use fully_pub_core::Config;
+use proc_macro2::Span;
+use syn::*;
+
+use crate::{relative, unexpected, Cli, Common, Result};
+
+/// Collects the redundant visibilities of the annotated items, looking inside
+/// inline modules too.
+fn collect(items: &mut [Item], config: &Config, spans: &mut Vec<Span>) -> syn::Result<()> {
+    for item in items {
+        let args = source::attrs_mut(item)
+            .map(source::take_attribute)
+            .transpose()?
+            .flatten();
+
+        if let Some(mut args) = args {
+            args.apply(config)?;
+            spans.extend(fully_pub_core::lint(&args, item)?);
+        } else if let Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            collect(items, config, spans)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reports the redundant visibilities found in the files of the module and of its
+/// nested modules, returning how many there are.
+///
+/// `parent_file` is the file the module is declared in, which is skipped if the
+/// module is declared inline.
+fn lint_module(
+    module: &Module,
+    parent_file: Option<&Path>,
+    dir: &Path,
+    config: &Config,
+) -> Result<usize> {
+    let mut count = 0;
+
+    if parent_file != Some(module.file.as_path()) {
+        let source = fs::read_to_string(&module.file)?;
+        let file_config = config.in_file(&module.file);
+        let file = relative(&module.file, dir);
+        let mut spans = Vec::new();
+
+        syn::parse_file(&source)
+            .and_then(|mut parsed| collect(&mut parsed.items, &file_config, &mut spans))
+            .map_err(|e| format!("{file}: {e}"))?;
+        spans.sort_by_key(|span| (span.start().line, span.start().column));
+
+        for span in &spans {
+            let start = span.start();
+            println!(
+                "{file}:{}:{}: warning: redundant visibility, the macro already gives it to \
+                this member",
+                start.line,
+                start.column + 1,
+            );
+        }
+
+        count += spans.len();
+    }
+
+    for child in &module.children {
+        count += lint_module(child, Some(&module.file), dir, config)?;
+    }
+
+    Ok(count)
+}
+
+pub fn run(mut cli: Cli) -> Result<()> {
+    let mut common = Common::default();
+    let mut deny = false;
+
+    while let Some(arg) = cli.next() {
+        if common.parse(&arg, &mut cli)? {
+            continue;
+        }
+
+        match arg.as_str() {
+            "--deny" => deny = true,
+            _ => return Err(unexpected(&arg)),
+        }
+    }
+
+    let dir = common.invoked_dir()?;
+    let mut count = 0;
+
+    common.for_each_package(|package, config| {
+        let module = source::load_crate(&source::crate_root(&package.manifest_dir)?, config)?;
+        let found = lint_module(&module, None, &dir, config)?;
+        count += found;
+
+        Ok(match found {
+            1 => "1 redundant visibility".to_string(),
+            _ => format!("{found} redundant visibilities"),
+        })
+    })?;
+
+    if deny && count > 0 {
+        let visibilities = if count == 1 {
+            "visibility"
+        } else {
+            "visibilities"
+        };
+        return Err(format!("found {count} redundant {visibilities}").into());
+    }
+
+    Ok(())
+}
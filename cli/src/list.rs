@@ -0,0 +1,243 @@
+//! `cargo fully-pub list`: lists every member the attributes of the macro make
+//! public (or give the configured visibility), with its location.
+//!
+//! With `--format json`, each member is printed as a JSON object on its own line,
+//! named the way rustdoc JSON names items, for the list to be joined with the output
+//! of tools reading it, like `cargo public-api` and `cargo semver-checks`:
+//!
+//! ```text
+//! {"crate":"app","path":["app","Account","login"],"kind":"struct_field","visibility":"public","was":"default","file":"src/lib.rs","line":3,"column":5}
+//! ```
+//!
+//! `path` is the path of the item as in the `paths` of rustdoc JSON, extended with the
+//! name of the member for fields and associated items, `kind` is the variant of its
+//! `ItemEnum`, and the visibilities are written as its `Visibility`, with the path of
+//! restricted visibilities as written in the source.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use fully_pub_core::source::{self, Module};
+use fully_pub_core::Config;
+use syn::*;
+
+use crate::{relative, unexpected, Cli, Common, Result};
+
+/// The output format of the list.
+#[derive(Clone, Copy)]
+enum Format {
+    Text,
+    Json,
+}
+
+/// A member made public by the macro.
+struct Entry {
+    line: usize,
+    column: usize,
+    kind: &'static str,
+    rustdoc_kind: &'static str,
+    path: String,
+    was: Visibility,
+    now: Visibility,
+}
+
+/// Returns the string as a JSON string.
+fn json_string(string: &str) -> String {
+    let mut out = String::from('"');
+
+    for c in string.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+
+    out + "\""
+}
+
+/// Returns the visibility as rustdoc JSON writes it.
+fn json_visibility(vis: &Visibility) -> String {
+    match vis {
+        Visibility::Public(_) => "\"public\"".to_string(),
+        Visibility::Restricted(VisRestricted { path, in_token, .. })
+            if in_token.is_none() && path.is_ident("crate") =>
+        {
+            "\"crate\"".to_string()
+        }
+        Visibility::Restricted(VisRestricted { path, .. }) => {
+            let path = quote::quote!(#path).to_string().replace(' ', "");
+            format!("{{\"restricted\":{{\"path\":{}}}}}", json_string(&path))
+        }
+        Visibility::Inherited => "\"default\"".to_string(),
+    }
+}
+
+/// Collects the members the annotated items made public, looking inside inline
+/// modules too. Their paths are prefixed by `prefix`.
+fn collect(
+    items: &[Item],
+    prefix: &str,
+    config: &Config,
+    entries: &mut Vec<Entry>,
+) -> syn::Result<()> {
+    for item in items {
+        let mut expanded = item.clone();
+
+        if source::expand_annotated(&mut expanded, config)? {
+            let before = source::members(item);
+            let after = source::members(&expanded);
+
+            for (before, after) in before.into_iter().zip(after) {
+                // With a configured visibility, the members may not become `pub`.
+                let (was, now) = (&before.vis, &after.vis);
+                let is_published =
+                    quote::quote!(#was).to_string() != quote::quote!(#now).to_string();
+
+                if is_published {
+                    let start = before.span.start();
+                    entries.push(Entry {
+                        line: start.line,
+                        column: start.column + 1,
+                        kind: before.kind,
+                        rustdoc_kind: before.rustdoc_kind,
+                        path: format!("{prefix}{}", before.path),
+                        was: before.vis,
+                        now: after.vis,
+                    });
+                }
+            }
+        } else if let Item::Mod(ItemMod {
+            ident,
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            collect(items, &format!("{prefix}{ident}::"), config, entries)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the members published in the files of the module and of its nested
+/// modules, except for `parent_file` where the module is declared inline, returning
+/// how many there are.
+///
+/// `crate_name` replaces `crate` in the paths of the JSON format.
+fn list_module(
+    module: &Module,
+    path: &str,
+    parent_file: Option<&Path>,
+    dir: &Path,
+    config: &Config,
+    (format, crate_name): (Format, &str),
+) -> Result<usize> {
+    let mut count = 0;
+
+    if parent_file != Some(module.file.as_path()) {
+        let source = fs::read_to_string(&module.file)?;
+        let file_config = config.in_file(&module.file);
+        let file = relative(&module.file, dir);
+        let mut entries = Vec::new();
+
+        syn::parse_file(&source)
+            .and_then(|parsed| {
+                collect(
+                    &parsed.items,
+                    &format!("{path}::"),
+                    &file_config,
+                    &mut entries,
+                )
+            })
+            .map_err(|e| format!("{file}: {e}"))?;
+        entries.sort_by_key(|entry| (entry.line, entry.column));
+        count += entries.len();
+
+        for Entry {
+            line,
+            column,
+            kind,
+            rustdoc_kind,
+            path,
+            was,
+            now,
+        } in entries
+        {
+            if let Format::Json = format {
+                let path: Vec<_> = [crate_name]
+                    .into_iter()
+                    .chain(path.split("::").skip(1))
+                    .map(json_string)
+                    .collect();
+                println!(
+                    "{{\"crate\":{},\"path\":[{}],\"kind\":\"{rustdoc_kind}\",\"visibility\":{},\
+                    \"was\":{},\"file\":{},\"line\":{line},\"column\":{column}}}",
+                    json_string(crate_name),
+                    path.join(","),
+                    json_visibility(&now),
+                    json_visibility(&was),
+                    json_string(&file),
+                );
+                continue;
+            }
+
+            let was = match was {
+                Visibility::Inherited => "private".to_string(),
+                vis => format!("`{}`", quote::quote!(#vis).to_string().replace(' ', "")),
+            };
+            println!("{file}:{line}:{column}: {kind} {path} (was {was})");
+        }
+    }
+
+    for child in &module.children {
+        let child_path = format!("{path}::{}", child.name);
+        let parent_file = Some(module.file.as_path());
+        count += list_module(
+            child,
+            &child_path,
+            parent_file,
+            dir,
+            config,
+            (format, crate_name),
+        )?;
+    }
+
+    Ok(count)
+}
+
+pub fn run(mut cli: Cli) -> Result<()> {
+    let mut common = Common::default();
+    let mut format = Format::Text;
+
+    while let Some(arg) = cli.next() {
+        if common.parse(&arg, &mut cli)? {
+            continue;
+        }
+
+        match arg.as_str() {
+            "--format" => {
+                format = match cli.value(&arg)?.as_str() {
+                    "text" => Format::Text,
+                    "json" => Format::Json,
+                    other => return Err(format!("unknown format `{other}`").into()),
+                }
+            }
+            _ => return Err(unexpected(&arg)),
+        }
+    }
+
+    let dir = common.invoked_dir()?;
+
+    common.for_each_package(|package, config| {
+        let module = source::load_crate(&source::crate_root(&package.manifest_dir)?, config)?;
+        // Rustdoc names crates with underscores.
+        let crate_name = package.name.replace('-', "_");
+        let count = list_module(&module, "crate", None, &dir, config, (format, &crate_name))?;
+        Ok(format!("{count} members made public"))
+    })
+}
@@ -0,0 +1,247 @@
+//! The `cargo fully-pub` subcommand, a set of tools for crates using the
+//! [`fully_pub`](https://docs.rs/fully_pub) attribute macro.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::{env, fs};
+
+use fully_pub_core::{Config, Package};
+
+mod diff;
+mod doc;
+mod graph;
+mod lint;
+mod list;
+mod rewrite;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+const USAGE: &str = "\
+Usage: cargo fully-pub <COMMAND> [OPTIONS]
+
+Commands:
+    annotate-excludes
+             Rewrite the sources, excluding the members the macro would
+             publish whose names look sensitive, with a TODO to review them
+             (--pattern <PATTERN> to match other names, --dry-run and --diff
+             as well)
+    diff     Print the changes `expand` would make to the sources, as a
+             unified diff
+    doc      Build the documentation with and without the macro, and report
+             the items it adds (arguments after `--` go to `cargo doc`)
+    expand   Rewrite the sources, replacing the attributes of the macro with
+             explicit visibilities (--dry-run to only list the files, --diff
+             to only print the changes, --item <PATH> to print the expansion
+             of an item instead)
+    fix      Rewrite the sources, removing the `pub` keywords the macro makes
+             redundant and the exclusions that change nothing, and making
+             modules whose items are all annotated recursive (--dry-run and
+             --diff as well)
+    graph    Print the module tree of the crate as a graph, after expansion
+             (--format dot|mermaid)
+    lint     Warn about the `pub` keywords the macro makes redundant
+             (--deny to fail if there are any)
+    list     List every member the macro makes public, with its location
+             (--format json for a JSON object per member, named as rustdoc
+             JSON names items)
+    migrate  Rewrite the items written with explicit visibilities to use the
+             macro, where it is shorter (--dry-run and --diff as well)
+    strip    Rewrite the sources, removing the attributes of the macro and
+             leaving items at their written visibility (--dry-run and --diff
+             as well)
+
+Options:
+    --manifest-path <PATH>    Path to the Cargo.toml of the crate
+    --workspace               Work on every package of the workspace, each with
+                              its own configuration on top of the one of the
+                              workspace (the default for a virtual manifest;
+                              annotate-excludes, diff, expand, fix, lint, list,
+                              migrate and strip only)
+    -h, --help                Print this message";
+
+/// The remaining command line arguments.
+struct Cli {
+    args: VecDeque<String>,
+}
+
+impl Cli {
+    /// Returns the next argument.
+    fn next(&mut self) -> Option<String> {
+        self.args.pop_front()
+    }
+
+    /// Returns the value of the flag that was just read.
+    fn value(&mut self, flag: &str) -> Result<String> {
+        self.next()
+            .ok_or_else(|| format!("missing value for `{flag}`").into())
+    }
+
+    /// Removes every occurrence of the flag from the arguments, returning their values.
+    fn take_all(&mut self, flag: &str) -> Result<Vec<String>> {
+        let mut values = Vec::new();
+        let mut rest = VecDeque::new();
+
+        while let Some(arg) = self.next() {
+            match arg == flag {
+                true => values.push(self.value(flag)?),
+                false => rest.push_back(arg),
+            }
+        }
+
+        self.args = rest;
+        Ok(values)
+    }
+}
+
+/// Options shared by all commands.
+#[derive(Default)]
+struct Common {
+    manifest_path: Option<PathBuf>,
+    workspace: bool,
+}
+
+impl Common {
+    /// Tries to parse a shared option from the argument, returning `false` if
+    /// it is not one.
+    fn parse(&mut self, arg: &str, cli: &mut Cli) -> Result<bool> {
+        match arg {
+            "--manifest-path" => self.manifest_path = Some(cli.value(arg)?.into()),
+            "--workspace" => self.workspace = true,
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Returns the directory containing the manifest of the crate to work on,
+    /// either given on the command line or found in a parent of the current directory.
+    fn manifest_dir(&self) -> Result<PathBuf> {
+        if self.workspace {
+            return Err("this command works on a single crate, `--workspace` can't be used".into());
+        }
+        self.invoked_dir()
+    }
+
+    /// Returns the directory containing the manifest given on the command line, or
+    /// found in a parent of the current directory.
+    fn invoked_dir(&self) -> Result<PathBuf> {
+        if let Some(path) = &self.manifest_path {
+            return Ok(path.parent().unwrap_or(Path::new(".")).to_path_buf());
+        }
+
+        let cwd = env::current_dir()?;
+        cwd.ancestors()
+            .find(|dir| dir.join("Cargo.toml").is_file())
+            .map(Path::to_path_buf)
+            .ok_or_else(|| {
+                "could not find `Cargo.toml` in the current directory or its parents".into()
+            })
+    }
+
+    /// Returns the configuration of the macro in the manifest of the crate.
+    fn config(&self) -> Result<Config> {
+        Ok(Config::load(&self.manifest_dir()?)?)
+    }
+
+    /// Runs `f` on each package to work on, with its configuration: the package of
+    /// the manifest, or the members of the workspace with `--workspace` or for a
+    /// virtual manifest.
+    ///
+    /// With several packages, `f` returns a summary printed to the standard error after
+    /// the output of each package, which is left for the output of the command, and the
+    /// packages that fail are reported without stopping the others.
+    fn for_each_package(
+        &self,
+        mut f: impl FnMut(&Package, &Config) -> Result<String>,
+    ) -> Result<()> {
+        let packages = fully_pub_core::packages(&self.invoked_dir()?, self.workspace)?;
+        let mut run = |package: &Package| f(package, &Config::load(&package.manifest_dir)?);
+
+        if let [package] = packages.as_slice() {
+            return run(package).map(drop);
+        }
+
+        let mut failed = 0;
+        for package in &packages {
+            match run(package) {
+                Ok(summary) => eprintln!("{}: {summary}", package.name),
+                Err(e) => {
+                    eprintln!("error: {}: {e}", package.name);
+                    failed += 1;
+                }
+            }
+        }
+
+        match failed {
+            0 => Ok(()),
+            _ => Err(format!("{failed} of {} packages failed", packages.len()).into()),
+        }
+    }
+}
+
+/// Returns the path relative to `base`, with `/` separators whatever the platform, for
+/// the output of the commands to be the same on every machine. The paths outside of
+/// `base` start with `..`.
+fn relative(path: &Path, base: &Path) -> String {
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let (path, base) = (canonical(path), canonical(base));
+
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return path.display().to_string();
+    }
+
+    let parents = base.components().skip(common).map(|_| "..".to_string());
+    let rest = path.components().skip(common);
+    let rest = rest.map(|component| component.as_os_str().to_string_lossy().into_owned());
+
+    parents.chain(rest).collect::<Vec<_>>().join("/")
+}
+
+/// Returns an error for an argument no command expected.
+fn unexpected(arg: &str) -> Box<dyn Error> {
+    format!("unexpected argument `{arg}`\n\n{USAGE}").into()
+}
+
+fn run(mut cli: Cli) -> Result<()> {
+    match cli.next().as_deref() {
+        Some("annotate-excludes") => rewrite::annotate_excludes(cli),
+        Some("diff") => rewrite::diff(cli),
+        Some("doc") => doc::run(cli),
+        Some("expand") => rewrite::expand(cli),
+        Some("fix") => rewrite::fix(cli),
+        Some("graph") => graph::run(cli),
+        Some("lint") => lint::run(cli),
+        Some("list") => list::run(cli),
+        Some("migrate") => rewrite::migrate(cli),
+        Some("strip") => rewrite::strip(cli),
+        Some("-h" | "--help") | None => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Some(command) => Err(format!("unknown command `{command}`\n\n{USAGE}").into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let mut args: VecDeque<_> = env::args().skip(1).collect();
+
+    // When invoked through cargo, the first argument is the name of the subcommand.
+    if args.front().map(String::as_str) == Some("fully-pub") {
+        args.pop_front();
+    }
+
+    match run(Cli { args }) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
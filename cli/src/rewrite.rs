@@ -0,0 +1,294 @@
+//! `cargo fully-pub expand`, `strip`, `migrate`, `fix` and `annotate-excludes`:
+//! rewrite the sources of the crate, replacing the attributes of the macro with
+//! explicit visibilities, removing them altogether, replacing explicit visibilities
+//! with the macro, shortening its uses, or excluding the members with sensitive names.
+//!
+//! `expand --item <PATH>` prints the expansion of a single item instead, without
+//! rewriting anything, and `diff` prints the changes `expand` would make.
+
+use std::fs;
+use std::path::Path;
+
+use fully_pub_core::source::{self, Module};
+use fully_pub_core::{rewrite, Config};
+use quote::ToTokens;
+use syn::*;
+
+use crate::{diff, relative, unexpected, Cli, Common, Result};
+
+/// Hint printed after removing the macro from a crate.
+const REMOVED: &str = "the `fully_pub` dependency can now be removed from `Cargo.toml`, if unused";
+
+/// What is done with the rewritten files.
+#[derive(Clone, Copy, PartialEq)]
+enum Output {
+    Write,
+    /// Only list the files that would change.
+    DryRun,
+    /// Only print the changes, as a unified diff.
+    Diff,
+}
+
+/// Rewrites every file of the crate, or of every package of the workspace, with `f`.
+///
+/// `verb` describes the rewrite in the messages, and `hint` is printed if any file
+/// changed.
+fn rewrite_crate(
+    mut cli: Cli,
+    verb: &str,
+    hint: &str,
+    f: &dyn Fn(&str, &Config) -> syn::Result<String>,
+) -> Result<()> {
+    let mut common = Common::default();
+    let mut output = Output::Write;
+
+    while let Some(arg) = cli.next() {
+        if common.parse(&arg, &mut cli)? {
+            continue;
+        }
+
+        match arg.as_str() {
+            "--dry-run" => output = Output::DryRun,
+            "--diff" => output = Output::Diff,
+            _ => return Err(unexpected(&arg)),
+        }
+    }
+
+    let dir = common.invoked_dir()?;
+    let mut changed = 0;
+
+    common.for_each_package(|package, config| {
+        let root = source::crate_root(&package.manifest_dir)?;
+        let module = source::load_crate(&root, config)?;
+        let (mut files, before) = (0, changed);
+
+        for file in module.files() {
+            let (source, path) = (fs::read_to_string(file)?, relative(file, &dir));
+            let rewritten =
+                f(&source, &config.in_file(file)).map_err(|e| format!("{path}: {e}"))?;
+            files += 1;
+
+            if rewritten != source {
+                changed += 1;
+
+                match output {
+                    Output::Write => {
+                        println!("{verb}: {path}");
+                        fs::write(file, rewritten)?;
+                    }
+                    Output::DryRun => println!("would {verb} {path}"),
+                    Output::Diff => print!("{}", diff::unified(&path, &source, &rewritten)),
+                }
+            }
+        }
+
+        let would = match output {
+            Output::Write => "changed",
+            _ => "would change",
+        };
+        Ok(format!("{} of {files} files {would}", changed - before))
+    })?;
+
+    if changed > 0 && output == Output::Write {
+        println!("{hint}");
+    }
+
+    Ok(())
+}
+
+/// Returns the name of the item, `None` for the items without one, like `impl` blocks.
+fn item_name(item: &Item) -> Option<&Ident> {
+    match item {
+        Item::Const(ItemConst { ident, .. })
+        | Item::Enum(ItemEnum { ident, .. })
+        | Item::ExternCrate(ItemExternCrate { ident, .. })
+        | Item::Fn(ItemFn {
+            sig: Signature { ident, .. },
+            ..
+        })
+        | Item::Mod(ItemMod { ident, .. })
+        | Item::Static(ItemStatic { ident, .. })
+        | Item::Struct(ItemStruct { ident, .. })
+        | Item::Trait(ItemTrait { ident, .. })
+        | Item::TraitAlias(ItemTraitAlias { ident, .. })
+        | Item::Type(ItemType { ident, .. })
+        | Item::Union(ItemUnion { ident, .. }) => Some(ident),
+        Item::Macro(ItemMacro { ident, .. }) => ident.as_ref(),
+        _ => None,
+    }
+}
+
+/// Returns the text of the item in the source, attributes included, without the
+/// indentation of the module containing it.
+fn item_text(source: &str, item: &Item) -> String {
+    let tokens: Vec<_> = item.to_token_stream().into_iter().collect();
+    let (Some(first), Some(last)) = (tokens.first(), tokens.last()) else {
+        return String::new();
+    };
+    let (start, end) = (
+        first.span().byte_range().start,
+        last.span().byte_range().end,
+    );
+
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    let indent = &source[line_start..start];
+    let indent = match indent.trim().is_empty() {
+        true => indent,
+        false => "",
+    };
+
+    let text = &source[start..end];
+    let lines: Vec<_> = text
+        .lines()
+        .map(|line| line.strip_prefix(indent).unwrap_or(line))
+        .collect();
+    lines.join("\n")
+}
+
+/// Prints the expansion of the items at the path, like `crate::models::User`, found
+/// in the module tree of `root`: all the items of this name in the module, since
+/// types and values have their own namespaces. The paths in the errors are relative
+/// to `dir`.
+fn print_item(root: &Module, path: &str, dir: &Path, config: &Config) -> Result<()> {
+    let mut segments: Vec<_> = path.split("::").skip(1).collect();
+    let Some(name) = segments.pop().filter(|name| !name.is_empty()) else {
+        return Err(format!("expected the path of an item, found `{path}`").into());
+    };
+
+    // The modules declared inline in the file of the module found so far.
+    let (mut module, mut inline) = (root, Vec::new());
+    for segment in &segments {
+        let Some(child) = module.children.iter().find(|child| child.name == *segment) else {
+            return Err(format!("no module `{segment}` in `{path}`").into());
+        };
+        match child.file == module.file {
+            true => inline.push(*segment),
+            false => inline.clear(),
+        }
+        module = child;
+    }
+
+    let source = fs::read_to_string(&module.file)?;
+    let file = relative(&module.file, dir);
+    let mut items = Vec::new();
+    let expanded = rewrite::expand_source(&source, &config.in_file(&module.file))
+        .and_then(|expanded| {
+            items = syn::parse_file(&expanded)?.items;
+            Ok(expanded)
+        })
+        .map_err(|e| format!("{file}: {e}"))?;
+
+    for segment in inline {
+        let content = items.into_iter().find_map(|item| match item {
+            Item::Mod(ItemMod {
+                ident,
+                content: Some((_, items)),
+                ..
+            }) if ident == segment => Some(items),
+            _ => None,
+        });
+        items = content.unwrap_or_default();
+    }
+
+    let texts: Vec<_> = items
+        .iter()
+        .filter(|item| item_name(item).is_some_and(|ident| ident == name))
+        .map(|item| item_text(&expanded, item))
+        .collect();
+    if texts.is_empty() {
+        return Err(format!("no item `{path}` in the crate").into());
+    }
+
+    println!("{}", texts.join("\n\n"));
+    Ok(())
+}
+
+/// Prints the expansion of the items at the paths, which start with `crate`, with the
+/// name of a package of the workspace, or with the name of a module of the crate root.
+fn print_items(common: &Common, paths: &[String]) -> Result<()> {
+    let dir = common.invoked_dir()?;
+    let packages = fully_pub_core::packages(&dir, common.workspace)?;
+
+    for (index, path) in paths.iter().enumerate() {
+        let (first, rest) = path.split_once("::").unwrap_or((path, ""));
+        // Paths name crates with underscores.
+        let named = packages
+            .iter()
+            .find(|package| package.name.replace('-', "_") == first);
+
+        let (package, path) = match (named, packages.as_slice()) {
+            (Some(package), _) => (package, format!("crate::{rest}")),
+            (None, [package]) if first == "crate" => (package, path.clone()),
+            (None, [package]) => (package, format!("crate::{path}")),
+            (None, _) => {
+                let names: Vec<_> = packages.iter().map(|p| p.name.replace('-', "_")).collect();
+                return Err(format!(
+                    "`{path}` must start with the name of one of the packages: {}",
+                    names.join(", "),
+                )
+                .into());
+            }
+        };
+
+        let config = Config::load(&package.manifest_dir)?;
+        let root = source::load_crate(&source::crate_root(&package.manifest_dir)?, &config)?;
+
+        if index > 0 {
+            println!();
+        }
+        print_item(&root, &path, &dir, &config)?;
+    }
+
+    Ok(())
+}
+
+pub fn expand(mut cli: Cli) -> Result<()> {
+    let items = cli.take_all("--item")?;
+    if items.is_empty() {
+        return rewrite_crate(cli, "expand", REMOVED, &rewrite::expand_source);
+    }
+
+    let mut common = Common::default();
+    while let Some(arg) = cli.next() {
+        if !common.parse(&arg, &mut cli)? {
+            return Err(unexpected(&arg));
+        }
+    }
+    print_items(&common, &items)
+}
+
+pub fn diff(mut cli: Cli) -> Result<()> {
+    cli.args.push_back("--diff".to_string());
+    rewrite_crate(cli, "expand", REMOVED, &rewrite::expand_source)
+}
+
+pub fn strip(cli: Cli) -> Result<()> {
+    rewrite_crate(cli, "strip", REMOVED, &|source, _| {
+        rewrite::strip_source(source)
+    })
+}
+
+pub fn migrate(cli: Cli) -> Result<()> {
+    let hint = "make sure `fully_pub` is listed in the `[dependencies]` of `Cargo.toml`";
+    rewrite_crate(cli, "migrate", hint, &rewrite::migrate_source)
+}
+
+pub fn fix(cli: Cli) -> Result<()> {
+    let hint = "the fixed items expand to the same visibilities as before";
+    rewrite_crate(cli, "fix", hint, &rewrite::fix_source)
+}
+
+pub fn annotate_excludes(mut cli: Cli) -> Result<()> {
+    let mut patterns = cli.take_all("--pattern")?;
+    if patterns.is_empty() {
+        patterns = rewrite::SENSITIVE_NAMES
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect();
+    }
+
+    let hint = "review the members marked `TODO`, and remove the comments once done";
+    rewrite_crate(cli, "annotate", hint, &|source, config| {
+        rewrite::annotate_excludes_source(source, &patterns, config)
+    })
+}
@@ -0,0 +1,33 @@
+//! A crate using the macro, rewritten by the tests of the commands.
+
+use fully_pub::fully_pub;
+
+pub mod shapes;
+
+#[fully_pub]
+struct Session {
+    user: String,
+    pub expires: u64,
+    token: String,
+}
+
+#[fully_pub]
+impl Session {
+    fn new(user: String) -> Self {
+        Session {
+            user,
+            expires: 0,
+            token: String::new(),
+        }
+    }
+}
+
+/// Written without the macro, shorter with it.
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub angle: f64,
+    pub scale: f64,
+}
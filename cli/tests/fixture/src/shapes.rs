@@ -0,0 +1,36 @@
+use fully_pub::fully_pub;
+
+#[fully_pub]
+mod circles {
+    use super::fully_pub;
+
+    #[fully_pub]
+    struct Circle {
+        radius: f64,
+    }
+
+    #[fully_pub]
+    fn unit() -> Circle {
+        Circle { radius: 1.0 }
+    }
+}
+
+#[fully_pub]
+enum Shape {
+    Square { side: f64 },
+    Circle { radius: f64 },
+}
+
+#[fully_pub]
+struct Scale {
+    factor: f64,
+    #[fully_pub(exclude)]
+    precision: u8,
+}
+
+pub(crate) fn area(shape: &Shape) -> f64 {
+    match shape {
+        Shape::Square { side } => side * side,
+        Shape::Circle { radius } => std::f64::consts::PI * radius * radius,
+    }
+}
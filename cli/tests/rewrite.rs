@@ -0,0 +1,273 @@
+//! Runs the rewriting commands on copies of the fixture crate, checking that the
+//! rewritten sources build and keep the visibilities the macro gives.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use fully_pub_core::source::{self, Module};
+use fully_pub_core::Config;
+use syn::{Fields, ImplItem, Item, Visibility};
+
+/// The manifest of the fixture, depending on the macro of this repository.
+const MANIFEST: &str = "\
+[package]
+name = \"fixture\"
+version = \"0.0.0\"
+edition = \"2021\"
+
+[dependencies]
+fully_pub = { path = '{root}' }
+
+[workspace]
+";
+
+/// A member and its visibility, as `path: vis`.
+type Visibilities = Vec<String>;
+
+/// Copies the fixture crate to a directory of its own, named after the test.
+fn fixture(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("src")).unwrap();
+
+    let sources = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixture/src");
+    for entry in fs::read_dir(sources).unwrap() {
+        let path = entry.unwrap().path();
+        fs::copy(&path, dir.join("src").join(path.file_name().unwrap())).unwrap();
+    }
+
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let manifest = MANIFEST.replace("{root}", &root.display().to_string());
+    fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+
+    dir
+}
+
+/// Runs the command of `cargo fully-pub` on the crate, returning its output.
+fn run(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-fully-pub"))
+        .args(args)
+        .arg("--manifest-path")
+        .arg(dir.join("Cargo.toml"))
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "`{}` failed: {stderr}", args[0]);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Checks that the crate builds, sharing the artifacts of the macro between tests.
+fn assert_builds(dir: &Path) {
+    let output = Command::new(env!("CARGO"))
+        .args(["check", "--quiet", "--offline", "--manifest-path"])
+        .arg(dir.join("Cargo.toml"))
+        .env(
+            "CARGO_TARGET_DIR",
+            Path::new(env!("CARGO_TARGET_TMPDIR")).join("fixture"),
+        )
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        output.status.success(),
+        "{} does not build: {stderr}",
+        dir.display()
+    );
+}
+
+/// Returns the contents of the sources of the crate, by file name.
+fn sources(dir: &Path) -> Vec<(String, String)> {
+    let mut sources: Vec<_> = fs::read_dir(dir.join("src"))
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, fs::read_to_string(path).unwrap())
+        })
+        .collect();
+
+    sources.sort();
+    sources
+}
+
+/// Returns the visibility as written, like `pub(crate)`, or nothing if inherited.
+fn vis(vis: &Visibility) -> String {
+    match vis {
+        Visibility::Public(_) => "pub".to_string(),
+        Visibility::Restricted(restricted) => {
+            let path: Vec<_> = restricted
+                .path
+                .segments
+                .iter()
+                .map(|segment| segment.ident.to_string())
+                .collect();
+            let path = path.join("::");
+            match restricted.in_token {
+                Some(_) => format!("pub(in {path})"),
+                None => format!("pub({path})"),
+            }
+        }
+        Visibility::Inherited => String::new(),
+    }
+}
+
+/// Returns the visibilities of the item and of its members, the items of inline
+/// modules included.
+fn members(item: &Item, prefix: &str, visibilities: &mut Visibilities) {
+    let mut push = |name: String, member: &Visibility| {
+        visibilities.push(format!("{prefix}{name}: {}", vis(member)));
+    };
+
+    match item {
+        Item::Struct(item) => {
+            push(item.ident.to_string(), &item.vis);
+            if let Fields::Named(fields) = &item.fields {
+                for field in &fields.named {
+                    let name = field.ident.as_ref().unwrap();
+                    push(format!("{}::{name}", item.ident), &field.vis);
+                }
+            }
+        }
+        Item::Enum(item) => push(item.ident.to_string(), &item.vis),
+        Item::Fn(item) => push(item.sig.ident.to_string(), &item.vis),
+        Item::Impl(item) => {
+            for member in &item.items {
+                if let ImplItem::Fn(member) = member {
+                    push(format!("impl::{}", member.sig.ident), &member.vis);
+                }
+            }
+        }
+        Item::Mod(item) => {
+            push(item.ident.to_string(), &item.vis);
+            let prefix = format!("{prefix}{}::", item.ident);
+            for item in item.content.iter().flat_map(|(_, items)| items) {
+                members(item, &prefix, visibilities);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Returns the visibilities of the members of the module and its children.
+fn collect(module: &Module, prefix: &str, visibilities: &mut Visibilities) {
+    let prefix = format!("{prefix}{}::", module.name);
+    visibilities.push(format!("{prefix}: {}", vis(&module.vis)));
+
+    for item in &module.items {
+        members(item, &prefix, visibilities);
+    }
+    for child in &module.children {
+        collect(child, &prefix, visibilities);
+    }
+}
+
+/// Returns the visibilities of the members of the crate, once the macro expanded.
+fn expanded(dir: &Path) -> Visibilities {
+    let config = Config::load(dir).unwrap();
+    let root = source::crate_root(dir).unwrap();
+    let module = source::load_crate(&root, &config).unwrap();
+
+    let mut visibilities = Vec::new();
+    collect(&module, "", &mut visibilities);
+    visibilities
+}
+
+/// Returns the visibilities of the members of the crate, as written.
+fn written(dir: &Path) -> Visibilities {
+    let mut visibilities = Vec::new();
+    for (name, source) in sources(dir) {
+        for item in syn::parse_file(&source).unwrap().items {
+            members(&item, &format!("{name}: "), &mut visibilities);
+        }
+    }
+    visibilities
+}
+
+/// Returns whether the sources of the crate still use the macro.
+fn uses_macro(dir: &Path) -> bool {
+    sources(dir)
+        .iter()
+        .any(|(_, source)| source.contains("#[fully_pub"))
+}
+
+#[test]
+fn original() {
+    let dir = fixture("original");
+    assert_builds(&dir);
+}
+
+#[test]
+fn expand() {
+    let dir = fixture("expand");
+    let before = expanded(&dir);
+
+    run(&dir, &["expand"]);
+    assert_builds(&dir);
+    assert!(!uses_macro(&dir));
+    assert_eq!(expanded(&dir), before);
+}
+
+#[test]
+fn strip() {
+    let dir = fixture("strip");
+    let before = written(&dir);
+
+    run(&dir, &["strip"]);
+    assert_builds(&dir);
+    assert!(!uses_macro(&dir));
+    assert_eq!(written(&dir), before);
+}
+
+#[test]
+fn migrate() {
+    let dir = fixture("migrate");
+    let before = expanded(&dir);
+
+    run(&dir, &["migrate"]);
+    assert_builds(&dir);
+    let lib = &sources(&dir)[0].1;
+    assert!(lib.contains("#[fully_pub::fully_pub]\nstruct Rect {\n    x: f64,\n"));
+    assert_eq!(expanded(&dir), before);
+}
+
+#[test]
+fn annotate_excludes() {
+    let dir = fixture("annotate_excludes");
+    let mut before = expanded(&dir);
+
+    run(&dir, &["annotate-excludes"]);
+    assert_builds(&dir);
+
+    // Only the token of the session is no longer published.
+    let token = before
+        .iter()
+        .position(|vis| vis.ends_with("Session::token: pub"));
+    before[token.unwrap()] = "crate::Session::token: ".to_string();
+    assert_eq!(expanded(&dir), before);
+}
+
+#[test]
+fn fix() {
+    let dir = fixture("fix");
+    let before = expanded(&dir);
+
+    run(&dir, &["fix"]);
+    assert_builds(&dir);
+    let lib = &sources(&dir)[0].1;
+    assert!(lib.contains("    user: String,\n    expires: u64,\n"));
+    assert_eq!(expanded(&dir), before);
+}
+
+#[test]
+fn diff() {
+    let dir = fixture("diff");
+    let before = sources(&dir);
+
+    let diff = run(&dir, &["diff"]);
+    assert_eq!(sources(&dir), before);
+    assert!(diff.contains("--- a/src/lib.rs\n+++ b/src/lib.rs\n"));
+    assert!(diff.contains("\n+pub struct Session {\n+    pub user: String,\n"));
+}
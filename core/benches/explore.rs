@@ -0,0 +1,125 @@
+//! Benchmarks of the exploration of items by the macro.
+//!
+//! Run them with `cargo bench -p fully_pub_core`. There is no harness: each benchmark
+//! runs on inputs prepared beforehand, and prints the mean time per run.
+
+use std::hint::black_box;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use fully_pub_core::{expand, lint, scan, splice, Args};
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::Item;
+
+/// Runs `f` on `iterations` inputs made by `setup`, returning the mean time of a run.
+///
+/// The inputs are dropped once the time is taken, as the compiler does with the
+/// output of the macro.
+fn bench<T>(iterations: u32, setup: impl FnMut() -> T, mut f: impl FnMut(&mut T)) -> Duration {
+    let mut inputs: Vec<_> = std::iter::repeat_with(setup)
+        .take(iterations as usize)
+        .collect();
+
+    let start = Instant::now();
+    for input in &mut inputs {
+        f(black_box(input));
+    }
+    start.elapsed() / iterations
+}
+
+/// Returns the source of a struct like the ones derive-heavy code generators emit, with `fields`
+/// fields carrying `attributes` attributes each.
+fn generated_struct(fields: usize, attributes: usize) -> String {
+    let mut source = String::from("#[derive(Clone, Debug)]\nstruct Message {\n");
+
+    for field in 0..fields {
+        for attribute in 0..attributes {
+            source += &format!("    #[serde(rename = \"field_{field}_{attribute}\")]\n");
+        }
+        source += &format!("    field_{field}: u64,\n");
+    }
+
+    source += "}\n";
+    source
+}
+
+/// Returns the source of a module like the ones generated from protobuf definitions, with `items`
+/// items: messages, their `impl` blocks, enums and constants, some of them nested in
+/// modules.
+fn generated_module(items: usize) -> String {
+    let mut source = String::from("mod generated {\n");
+
+    for index in 0..items / 4 {
+        source += &format!(
+            "    #[derive(Clone, PartialEq)]
+    struct Message{index} {{ id: u64, name: String, tags: Vec<String> }}
+    impl Message{index} {{ const TAG: u32 = {index}; fn id(&self) -> u64 {{ self.id }} }}
+    enum Kind{index} {{ A, B(u8), C {{ value: u32 }} }}
+    mod nested{index} {{ fn helper() -> u32 {{ {index} }} }}
+"
+        );
+    }
+
+    source += "}\n";
+    source
+}
+
+fn main() {
+    let parse = |source: &str| syn::parse_str::<Item>(source).unwrap();
+
+    for (fields, attributes) in [(100, 0), (100, 10), (100, 50)] {
+        let source = generated_struct(fields, attributes);
+        let time = bench(
+            200,
+            || parse(&source),
+            |item| drop(expand(&Args::default(), item).unwrap()),
+        );
+        println!("expand, struct with {fields} fields of {attributes} attributes: {time:?}");
+    }
+
+    let recursive: Args = syn::parse_str("recursive").unwrap();
+    let source = generated_module(20_000);
+
+    let time = bench(
+        10,
+        || parse(&source),
+        |item| drop(expand(&recursive, item).unwrap()),
+    );
+    println!("expand, module with 20000 items: {time:?}");
+
+    let time = bench(
+        10,
+        || parse(&source),
+        |item| drop(lint(&recursive, item).unwrap()),
+    );
+    println!("lint, module with 20000 items: {time:?}");
+
+    let time = bench(
+        10,
+        || parse(&source),
+        |item| {
+            expand(&recursive, item).unwrap();
+            drop(item.to_token_stream());
+        },
+    );
+    println!("expand and emit, module with 20000 items: {time:?}");
+
+    // Like the macro, which is given the tokens the item is parsed from.
+    let time = bench(
+        10,
+        || (source.parse::<TokenStream>().unwrap(), parse(&source)),
+        |(tokens, item)| {
+            expand(&recursive, item).unwrap();
+            drop(splice(mem::take(tokens), item));
+        },
+    );
+    println!("expand and splice, module with 20000 items: {time:?}");
+
+    let time = bench(
+        10,
+        || source.parse::<TokenStream>().unwrap(),
+        |tokens| drop(scan(&recursive, mem::take(tokens)).unwrap()),
+    );
+    println!("scan, module with 20000 items: {time:?}");
+}
@@ -0,0 +1,244 @@
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::*;
+
+use crate::config::{Defaults, Lints};
+use crate::{Config, CRATE_NAME};
+
+/// The marker types whose fields are left untouched, unless configured otherwise:
+/// they only carry types or lifetimes, and are no part of the interface of a type.
+pub(crate) const MARKER_TYPES: &[&str] = &["PhantomData", "PhantomPinned"];
+
+/// The `serde` attribute `sync_serde_skip` adds to the excluded fields, unless given
+/// another one of [`SERDE_SKIPS`].
+pub(crate) const SERDE_SKIP: &str = "skip_serializing";
+
+/// The `serde` attributes `sync_serde_skip` can add.
+const SERDE_SKIPS: &[&str] = &["skip", "skip_serializing", "skip_deserializing"];
+
+/// Arguments passed to the `#[fully_pub(...)]` attribute macro.
+///
+/// Arguments left out are taken from the [`Config`] of the crate by [`Args::apply`].
+#[derive(Clone, Default)]
+pub struct Args {
+    /// Whether nested `mod`s are explored too.
+    pub(crate) recursive: Option<bool>,
+    /// Whether to report the existing visibilities instead of changing them.
+    pub(crate) inventory: bool,
+    /// The visibility given to the members, `pub` if `None`.
+    pub(crate) vis: Option<Visibility>,
+    /// The patterns of the names of the members to leave untouched.
+    pub(crate) exclude_names: Option<Vec<String>>,
+    /// The group declared with `fully_pub::group!` whose arguments are used.
+    pub(crate) group: Option<LitStr>,
+    /// The set declared with `fully_pub::exclude_set!` whose names are left untouched,
+    /// in addition to `exclude_names`.
+    pub(crate) exclude_set: Option<LitStr>,
+    /// Whether failing to keep the tokens of the item as written is an error.
+    pub(crate) exact: Option<bool>,
+    /// The names of the types whose inherent `impl` blocks are left untouched.
+    pub(crate) exclude_impls_of: Vec<String>,
+    /// Whether the fields of marker types are given the visibility too.
+    pub(crate) markers: Option<bool>,
+    /// The names of the marker types, [`MARKER_TYPES`] if `None`.
+    pub(crate) marker_types: Option<Vec<String>>,
+    /// Whether the structs whose fields are all given a visibility get a private
+    /// field too, for other crates not to build them with a struct expression.
+    pub(crate) non_constructible: bool,
+    /// The `serde` attribute, like `skip_serializing`, added to the excluded fields of
+    /// the structs deriving its traits.
+    pub(crate) sync_serde_skip: Option<String>,
+    /// The levels of the diagnostics, which are only set by the configuration.
+    pub(crate) lints: Lints,
+}
+
+impl Args {
+    /// Applies the arguments of the group, then the defaults of the configuration for
+    /// the module it is for, to the arguments that were not given, and adds the names
+    /// of the exclusion set to the excluded ones.
+    ///
+    /// Fails if the group or the exclusion set is not declared.
+    pub fn apply(&mut self, config: &Config) -> Result<()> {
+        if let Some(group) = &self.group {
+            match config.group(&group.value()) {
+                Some(defaults) => self.fill(defaults.clone()),
+                None => bail!(
+                    group,
+                    "unknown group `{}`, groups are declared with `{CRATE_NAME}::group!` in \
+                    the crate root",
+                    group.value(),
+                ),
+            }
+        }
+
+        self.fill(config.defaults());
+        self.lints = config.lints.clone();
+
+        if let Some(set) = &self.exclude_set {
+            match config.exclude_set(&set.value()) {
+                Some(names) => self
+                    .exclude_names
+                    .get_or_insert_default()
+                    .extend_from_slice(names),
+                None => bail!(
+                    set,
+                    "unknown exclusion set `{}`, sets are declared with \
+                    `{CRATE_NAME}::exclude_set!` in the crate root",
+                    set.value(),
+                ),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns a description of the arguments that changes with what they do to an
+    /// item.
+    pub(crate) fn fingerprint(&self) -> String {
+        let vis = self
+            .vis
+            .as_ref()
+            .map(|vis| vis.to_token_stream().to_string());
+
+        format!(
+            "{:?} {} {vis:?} {:?} {:?} {:?} {:?} {} {:?} {:?} {:?}",
+            self.recursive,
+            self.inventory,
+            self.exclude_names,
+            self.exclude_impls_of,
+            self.markers,
+            self.marker_types,
+            self.non_constructible,
+            self.sync_serde_skip,
+            self.lints.redundant_pub,
+            self.lints.private_bounds,
+        )
+    }
+
+    /// Returns the names of the types whose fields are left untouched, none if the
+    /// fields of markers are given the visibility too.
+    pub(crate) fn marker_types(&self) -> Vec<String> {
+        match (self.markers, &self.marker_types) {
+            (Some(true), _) => Vec::new(),
+            (_, Some(names)) => names.clone(),
+            (_, None) => MARKER_TYPES.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    /// Sets the arguments that were not given from the defaults.
+    fn fill(&mut self, defaults: Defaults) {
+        self.recursive = self.recursive.or(defaults.recursive);
+        if self.vis.is_none() {
+            self.vis = defaults.vis.as_deref().and_then(|vis| parse_str(vis).ok());
+        }
+        self.exclude_names = self.exclude_names.take().or(defaults.exclude_names);
+        self.exact = self.exact.or(defaults.exact);
+        self.markers = self.markers.or(defaults.markers);
+        self.marker_types = self.marker_types.take().or(defaults.marker_types);
+    }
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = Args::default();
+        let mut seen = Vec::new();
+
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            let has_value = input.parse::<Option<Token![=]>>()?.is_some();
+
+            match () {
+                _ if ident == "recursive" => {
+                    let value = match has_value {
+                        true => input.parse::<LitBool>()?.value,
+                        false => true,
+                    };
+                    args.recursive = Some(value);
+                }
+                _ if ident == "inventory" && !has_value => {
+                    if !cfg!(feature = "report") {
+                        bail!(&ident, "`inventory` requires the `report` feature");
+                    }
+                    args.inventory = true;
+                }
+                _ if ident == "vis" && has_value => {
+                    let vis: Visibility = input.parse()?;
+                    if let Visibility::Inherited = vis {
+                        bail!(&ident, "expected a visibility after `vis =`");
+                    }
+                    args.vis = Some(vis);
+                }
+                _ if ident == "exact" => {
+                    let value = match has_value {
+                        true => input.parse::<LitBool>()?.value,
+                        false => true,
+                    };
+                    args.exact = Some(value);
+                }
+                _ if ident == "markers" => {
+                    let value = match has_value {
+                        true => input.parse::<LitBool>()?.value,
+                        false => true,
+                    };
+                    args.markers = Some(value);
+                }
+                _ if ident == "non_constructible" && !has_value => args.non_constructible = true,
+                _ if ident == "sync_serde_skip" => {
+                    let skip = match has_value {
+                        true => input.parse::<LitStr>()?,
+                        false => LitStr::new(SERDE_SKIP, ident.span()),
+                    };
+                    if !SERDE_SKIPS.contains(&skip.value().as_str()) {
+                        bail!(
+                            skip,
+                            "expected one of `skip`, `skip_serializing` and \
+                            `skip_deserializing` after `sync_serde_skip =`"
+                        );
+                    }
+                    args.sync_serde_skip = Some(skip.value());
+                }
+                _ if ident == "group" && has_value => args.group = Some(input.parse()?),
+                _ if ident == "exclude_set" && has_value => {
+                    args.exclude_set = Some(input.parse()?);
+                }
+                _ if ident == "exclude_impls_of" && !has_value => {
+                    let content;
+                    parenthesized!(content in input);
+                    let names = Punctuated::<Ident, Comma>::parse_terminated(&content)?;
+                    args.exclude_impls_of =
+                        names.iter().map(|name| name.unraw().to_string()).collect();
+                }
+                _ if ident == "exclude_names" && has_value => {
+                    let content;
+                    bracketed!(content in input);
+                    let names = Punctuated::<LitStr, Comma>::parse_terminated(&content)?;
+                    args.exclude_names = Some(names.iter().map(LitStr::value).collect());
+                }
+                _ if ident == "marker_types" && has_value => {
+                    let content;
+                    bracketed!(content in input);
+                    let names = Punctuated::<LitStr, Comma>::parse_terminated(&content)?;
+                    args.marker_types = Some(names.iter().map(LitStr::value).collect());
+                }
+                _ => bail!(&ident, "invalid argument to `{CRATE_NAME}` attribute macro"),
+            }
+
+            if seen.contains(&ident) {
+                bail!(
+                    &ident,
+                    "duplicate argument `{ident}` to `{CRATE_NAME}` attribute macro"
+                );
+            }
+            seen.push(ident);
+
+            if !input.is_empty() {
+                input.parse::<Comma>()?;
+            }
+        }
+
+        Ok(args)
+    }
+}
@@ -0,0 +1,236 @@
+//! The `private_bounds` diagnostic of the macro: the generic members it publishes
+//! whose bounds name a trait of the same item it left less visible, which the
+//! compiler warns about with its own `private_bounds` lint, in generated code.
+//!
+//! Only the members the expansion makes more visible are looked at, and traits are
+//! told apart by their names: the paths of the bounds are not resolved.
+
+use std::collections::HashMap;
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::ext::IdentExt;
+use syn::punctuated::Punctuated;
+use syn::token::Plus;
+use syn::*;
+
+use crate::config::Level;
+use crate::{warning, Args};
+
+/// A member of the item that can have bounds.
+struct Bounded<'a> {
+    name: &'a Ident,
+    vis: &'a Visibility,
+    generics: &'a Generics,
+    /// The supertraits of a trait, or the bounds of a trait alias.
+    supertraits: Option<&'a Punctuated<TypeParamBound, Plus>>,
+}
+
+/// Returns `true` if the bounds of the item are checked: a trait and a member it
+/// bounds can only be met by the same expansion in a module explored recursively.
+pub(crate) fn is_checked(args: &Args) -> bool {
+    args.lints.private_bounds != Level::Allow && args.recursive.unwrap_or(false) && !args.inventory
+}
+
+/// The rank of a visibility, the higher the more visible. Restricted visibilities are
+/// all ranked the same, since their scopes can't be compared without resolving them.
+fn rank(vis: &Visibility) -> u8 {
+    match vis {
+        Visibility::Inherited => 0,
+        Visibility::Restricted(_) => 1,
+        Visibility::Public(_) => 2,
+    }
+}
+
+/// Pushes the members of the item that can have bounds to `out`, in the order they
+/// are written, which the expansion does not change.
+fn bounded<'a>(item: &'a Item, out: &mut Vec<Bounded<'a>>) {
+    let member = |name, vis, generics| Bounded {
+        name,
+        vis,
+        generics,
+        supertraits: None,
+    };
+
+    match item {
+        Item::Enum(ItemEnum {
+            vis,
+            ident,
+            generics,
+            ..
+        })
+        | Item::Struct(ItemStruct {
+            vis,
+            ident,
+            generics,
+            ..
+        })
+        | Item::Type(ItemType {
+            vis,
+            ident,
+            generics,
+            ..
+        })
+        | Item::Union(ItemUnion {
+            vis,
+            ident,
+            generics,
+            ..
+        }) => out.push(member(ident, vis, generics)),
+        Item::Fn(ItemFn { vis, sig, .. }) => out.push(member(&sig.ident, vis, &sig.generics)),
+        Item::Trait(ItemTrait {
+            vis,
+            ident,
+            generics,
+            supertraits,
+            ..
+        })
+        | Item::TraitAlias(ItemTraitAlias {
+            vis,
+            ident,
+            generics,
+            bounds: supertraits,
+            ..
+        }) => out.push(Bounded {
+            supertraits: Some(supertraits),
+            ..member(ident, vis, generics)
+        }),
+        Item::Impl(ItemImpl {
+            trait_: None,
+            items,
+            ..
+        }) => {
+            for item in items {
+                match item {
+                    ImplItem::Fn(ImplItemFn { vis, sig, .. }) => {
+                        out.push(member(&sig.ident, vis, &sig.generics))
+                    }
+                    ImplItem::Type(ImplItemType {
+                        vis,
+                        ident,
+                        generics,
+                        ..
+                    }) => out.push(member(ident, vis, generics)),
+                    _ => (),
+                }
+            }
+        }
+        Item::ForeignMod(ItemForeignMod { items, .. }) => {
+            for item in items {
+                if let ForeignItem::Fn(ForeignItemFn { vis, sig, .. }) = item {
+                    out.push(member(&sig.ident, vis, &sig.generics));
+                }
+            }
+        }
+        Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) => {
+            for item in items {
+                bounded(item, out);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Returns the traits of the item, with the highest rank of the ones of each name.
+fn traits(item: &Item, traits: &mut HashMap<String, u8>) {
+    match item {
+        Item::Trait(ItemTrait { vis, ident, .. })
+        | Item::TraitAlias(ItemTraitAlias { vis, ident, .. }) => {
+            let rank = rank(vis);
+            let highest = traits.entry(ident.unraw().to_string()).or_insert(rank);
+            *highest = rank.max(*highest);
+        }
+        Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) => {
+            for item in items {
+                self::traits(item, traits);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Returns the ranks of the visibilities of the members of the item that can have
+/// bounds, to be given to [`diagnose`] once the item is expanded.
+pub(crate) fn ranks(item: &Item) -> Vec<u8> {
+    let mut members = Vec::new();
+    bounded(item, &mut members);
+    members.iter().map(|member| rank(member.vis)).collect()
+}
+
+/// Returns the diagnostics of the expanded item at the level of the configuration, as
+/// tokens emitting warnings, or as an error if it is denied.
+///
+/// `before` are the [`ranks`] of the item before it was expanded.
+pub(crate) fn diagnose(args: &Args, item: &Item, before: &[u8]) -> Result<TokenStream2> {
+    let mut traits = HashMap::new();
+    self::traits(item, &mut traits);
+
+    let mut members = Vec::new();
+    bounded(item, &mut members);
+
+    let mut found = Vec::new();
+    for (member, before) in members.iter().zip(before) {
+        let rank = rank(member.vis);
+        if rank <= *before {
+            continue;
+        }
+
+        let params = member.generics.params.iter().flat_map(|param| match param {
+            GenericParam::Type(param) => Some(&param.bounds),
+            _ => None,
+        });
+        let predicates = member.generics.where_clause.iter().flat_map(|clause| {
+            clause
+                .predicates
+                .iter()
+                .flat_map(|predicate| match predicate {
+                    WherePredicate::Type(predicate) => Some(&predicate.bounds),
+                    _ => None,
+                })
+        });
+
+        let bounds = params.chain(predicates).chain(member.supertraits).flatten();
+        for bound in bounds {
+            let TypeParamBound::Trait(TraitBound { path, .. }) = bound else {
+                continue;
+            };
+            let Some(last) = path.segments.last() else {
+                continue;
+            };
+
+            let name = last.ident.unraw().to_string();
+            if traits
+                .get(&name)
+                .is_some_and(|trait_rank| *trait_rank < rank)
+            {
+                let message = format!(
+                    "the trait `{name}` bounds `{}`, which the macro makes more visible \
+                    than it: the compiler warns about it with the `private_bounds` lint",
+                    member.name.unraw(),
+                );
+                found.push(Error::new(last.ident.span(), message));
+            }
+        }
+    }
+
+    if args.lints.private_bounds == Level::Warn {
+        return Ok(found
+            .iter()
+            .map(|error| warning(error.span(), &error.to_string()))
+            .collect());
+    }
+
+    let mut errors = found.into_iter();
+    match errors.next() {
+        Some(mut error) => {
+            error.extend(errors);
+            Err(error)
+        }
+        None => Ok(TokenStream2::new()),
+    }
+}
@@ -0,0 +1,162 @@
+//! Caching of the expansions of the macro in the target directory, for the large
+//! generated items that are expanded again on every build although they did not
+//! change.
+//!
+//! An expansion is stored as the edits it makes to the tokens of the item rather than
+//! as the expanded tokens: applying them to the tokens given to the macro keeps the
+//! spans of these tokens, which tokens read back from a file would not have.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use proc_macro2::TokenStream as TokenStream2;
+
+use crate::splice::Edit;
+use crate::{Args, CRATE_NAME};
+
+/// Returns the target directory of the crate whose manifest is in `manifest_dir`:
+/// the one of `CARGO_TARGET_DIR`, or else the `target` directory next to the
+/// `Cargo.lock` of its workspace.
+fn target_dir(manifest_dir: &Path) -> PathBuf {
+    if let Some(dir) = env::var_os("CARGO_TARGET_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    manifest_dir
+        .ancestors()
+        .find(|dir| dir.join("Cargo.lock").is_file())
+        .unwrap_or(manifest_dir)
+        .join("target")
+}
+
+/// The entry of the cache for an item expanded with some arguments.
+pub(crate) struct Cache {
+    path: PathBuf,
+}
+
+impl Cache {
+    /// Returns the entry of the cache of the crate whose manifest is in `manifest_dir`
+    /// for the item, expanded with the arguments once the configuration has been
+    /// applied to them, or disabled.
+    pub(crate) fn new(
+        manifest_dir: &Path,
+        args: &Args,
+        item: &TokenStream2,
+        disabled: bool,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        args.fingerprint().hash(&mut hasher);
+        disabled.hash(&mut hasher);
+        item.to_string().hash(&mut hasher);
+
+        let path = target_dir(manifest_dir)
+            .join(CRATE_NAME)
+            .join(format!("{:016x}", hasher.finish()));
+        Cache { path }
+    }
+
+    /// Returns the edits stored in the entry, if there is one that can be read.
+    pub(crate) fn load(&self) -> Option<Vec<Edit>> {
+        let source = fs::read_to_string(&self.path).ok()?;
+        let mut rest = source.as_str();
+        let mut edits = Vec::new();
+
+        while !rest.is_empty() {
+            let (header, after) = rest.split_once('\n')?;
+            let mut fields = header.split(' ');
+            let path = match fields.next()? {
+                "" => Vec::new(),
+                path => path
+                    .split('.')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .ok()?,
+            };
+            let index = fields.next()?.parse().ok()?;
+            let deleted = fields.next()?.parse().ok()?;
+            let len: usize = fields.next()?.parse().ok()?;
+            if fields.next().is_some() {
+                return None;
+            }
+
+            let inserted = after.get(..len)?;
+            rest = after[len..].strip_prefix('\n')?;
+            edits.push(Edit {
+                path,
+                index,
+                deleted,
+                inserted: inserted.to_string(),
+            });
+        }
+
+        Some(edits)
+    }
+
+    /// Stores the edits in the entry. The cache is only an optimization, so failing to
+    /// write it is not an error.
+    ///
+    /// Each edit is stored as a line with its path, index, number of deleted tokens and
+    /// length of the inserted tokens, followed by these tokens on the next lines, for
+    /// the ones spanning lines, like a string literal, to be read back as they were:
+    ///
+    #[cfg_attr(all(feature = "files", feature = "report"), doc = "```")]
+    #[cfg_attr(not(all(feature = "files", feature = "report")), doc = "```ignore")]
+    /// # let manifest_dir = std::env::temp_dir().join("fully_pub_core_cache_doc");
+    /// # let _ = std::fs::remove_dir_all(&manifest_dir);
+    /// # std::fs::create_dir_all(&manifest_dir).unwrap();
+    /// std::fs::write(
+    ///     manifest_dir.join("Cargo.toml"),
+    ///     "[package]\nname = \"app\"\n\n[package.metadata.fully_pub]\ncache = true\n",
+    /// )
+    /// .unwrap();
+    /// std::env::set_var("CARGO_MANIFEST_DIR", &manifest_dir);
+    /// std::env::set_var("CARGO_TARGET_DIR", manifest_dir.join("target"));
+    /// // The report of the time taken tells when the cached expansion is applied.
+    /// std::env::set_var(fully_pub_core::TIME_BUDGET_VAR, "0");
+    ///
+    /// // The condition is copied in the `serde` attribute added to the field.
+    /// let item = r#"
+    ///     #[cfg_attr(feature = "serde
+    /// 0 0 0 pub", derive(Serialize))]
+    ///     struct Account { login: String, #[fully_pub(exclude)] secret: String }
+    /// "#;
+    /// let expand = || {
+    ///     let args = "sync_serde_skip".parse().unwrap();
+    ///     fully_pub_core::fully_pub(args, item.parse().unwrap()).unwrap().to_string()
+    /// };
+    ///
+    /// let expanded = expand();
+    /// assert!(expanded.contains("parsing"));
+    /// let cached = expand();
+    /// assert!(cached.contains("applying the cached expansion") && !cached.contains("parsing"));
+    ///
+    /// let item = |expanded: &str| expanded.split(" const _").next().unwrap().to_string();
+    /// assert_eq!(item(&cached), item(&expanded));
+    /// ```
+    pub(crate) fn store(&self, edits: &[Edit]) {
+        let mut source = String::new();
+        for Edit {
+            path,
+            index,
+            deleted,
+            inserted,
+        } in edits
+        {
+            let path: Vec<_> = path.iter().map(usize::to_string).collect();
+            let len = inserted.len();
+            source += &format!("{} {index} {deleted} {len}\n{inserted}\n", path.join("."));
+        }
+
+        // Compilers run in parallel, so the entry is written whole or not at all.
+        let temporary = self.path.with_extension(process::id().to_string());
+        let _ = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&temporary, source))
+            .and_then(|_| fs::rename(&temporary, &self.path));
+    }
+}
@@ -0,0 +1,925 @@
+//! Project-wide configuration of the macro, read from the
+//! `[package.metadata.fully_pub]` table of the `Cargo.toml` of the crate, on top of
+//! the `[workspace.metadata.fully_pub]` table of its workspace, and from the
+//! `fully_pub::config!` invocation of its root, with the `files` feature.
+
+#[cfg(feature = "files")]
+use std::ffi::OsString;
+#[cfg(feature = "files")]
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "files")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "files")]
+use std::sync::Mutex;
+#[cfg(feature = "files")]
+use std::time::SystemTime;
+use std::{env, fmt};
+
+#[cfg(feature = "files")]
+use proc_macro2::Span;
+#[cfg(feature = "files")]
+use syn::Ident;
+use syn::{parse_str, Visibility};
+
+use crate::explore::matches;
+#[cfg(feature = "files")]
+use crate::source::crate_root;
+use crate::Tracker;
+#[cfg(feature = "files")]
+use crate::{CRATE_NAME, VIS_VAR};
+
+mod code;
+mod toml;
+#[cfg(feature = "files")]
+mod workspace;
+
+pub use code::{config, exclude_set, group, ConfigArgs, ExcludeSetArgs, GroupArgs};
+use toml::{Entry, Position, Value};
+#[cfg(feature = "files")]
+pub use workspace::{packages, Package};
+
+/// The path of the table of the configuration in the manifest.
+const TABLE: [&str; 3] = ["package", "metadata", "fully_pub"];
+
+/// The path of the table of the configuration shared by the members of a workspace,
+/// in its root manifest.
+#[cfg(feature = "files")]
+const WORKSPACE_TABLE: [&str; 3] = ["workspace", "metadata", "fully_pub"];
+
+/// The keys the configuration accepts.
+const KEYS: &[&str] = &[
+    "recursive",
+    "vis",
+    "exclude_names",
+    "exact",
+    "markers",
+    "marker_types",
+    "modules",
+    "cache",
+    "lints.redundant_pub",
+    "lints.private_bounds",
+];
+
+/// The keys the rules of `modules` accept.
+const DEFAULT_KEYS: &[&str] = &[
+    "recursive",
+    "vis",
+    "exclude_names",
+    "exact",
+    "markers",
+    "marker_types",
+];
+
+/// The level of a diagnostic of the macro.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum Level {
+    #[default]
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// The levels of the diagnostics of the macro.
+#[derive(Clone, Debug)]
+pub(crate) struct Lints {
+    /// Visibilities written on members the macro gives the same visibility anyway.
+    pub(crate) redundant_pub: Level,
+    /// Traits left private in the bounds of members the macro publishes.
+    pub(crate) private_bounds: Level,
+}
+
+impl Default for Lints {
+    fn default() -> Self {
+        Lints {
+            redundant_pub: Level::Allow,
+            private_bounds: Level::Warn,
+        }
+    }
+}
+
+/// Defaults of the arguments of the attributes, for the whole crate or for the
+/// modules matching a rule. Those left to `None` are not set.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Defaults {
+    /// Whether the attributes are recursive.
+    pub(crate) recursive: Option<bool>,
+    /// The visibility given to the members. It is known to be valid.
+    pub(crate) vis: Option<String>,
+    /// The patterns of the names of the members to leave untouched.
+    pub(crate) exclude_names: Option<Vec<String>>,
+    /// Whether the tokens of the items must be kept as written.
+    pub(crate) exact: Option<bool>,
+    /// Whether the fields of marker types are given the visibility too.
+    pub(crate) markers: Option<bool>,
+    /// The names of the marker types, whose fields are left untouched.
+    pub(crate) marker_types: Option<Vec<String>>,
+}
+
+impl Defaults {
+    /// Overrides these defaults with the ones `other` sets.
+    fn merge(&mut self, other: &Defaults) {
+        if other.recursive.is_some() {
+            self.recursive = other.recursive;
+        }
+        if other.vis.is_some() {
+            self.vis.clone_from(&other.vis);
+        }
+        if other.exclude_names.is_some() {
+            self.exclude_names.clone_from(&other.exclude_names);
+        }
+        if other.exact.is_some() {
+            self.exact = other.exact;
+        }
+        if other.markers.is_some() {
+            self.markers = other.markers;
+        }
+        if other.marker_types.is_some() {
+            self.marker_types.clone_from(&other.marker_types);
+        }
+    }
+}
+
+/// Defaults applying to the modules whose path matches a pattern.
+#[derive(Clone, Debug)]
+struct Rule {
+    /// The segments of the pattern, without a leading `crate`.
+    pattern: Vec<String>,
+    defaults: Defaults,
+}
+
+/// Returns `true` if the module path matches the pattern, in which `**` stands for
+/// any number of segments, and `*` for any sequence of characters in a segment.
+fn matches_path(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|i| matches_path(rest, &path[i..]))
+        }
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(name, path)| matches(first, name) && matches_path(rest, path)),
+    }
+}
+
+/// The configuration of the macro for a crate, giving the defaults of the
+/// arguments of its attributes.
+///
+/// The defaults may depend on the module the attributes are in, as given by
+/// [`Config::in_file`].
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    defaults: Defaults,
+    /// The rules of `modules`, in the order of the manifest.
+    rules: Vec<Rule>,
+    /// The groups of arguments declared with `fully_pub::group!`, by name.
+    groups: Vec<(String, Defaults)>,
+    /// The sets of names declared with `fully_pub::exclude_set!`, by name.
+    exclude_sets: Vec<(String, Vec<String>)>,
+    /// The visibility given by [`VIS_VAR`], overriding every other default.
+    vis_override: Option<String>,
+    pub(crate) lints: Lints,
+    /// Whether the expansions are cached in the target directory.
+    pub(crate) cache: bool,
+    /// The directory of the manifest the configuration was loaded from.
+    pub(crate) manifest_dir: Option<PathBuf>,
+    /// The path of the module the attributes are in, if known.
+    module: Option<Vec<String>>,
+}
+
+/// An invalid configuration, with the location of the offending key or value.
+#[derive(Clone, Debug)]
+pub struct Error {
+    /// The manifest, or the environment variable, the configuration comes from.
+    origin: String,
+    /// The position of the error in the manifest, if it could be read.
+    position: Option<Position>,
+    message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some(Position { line, column }) => {
+                write!(f, "{}:{line}:{column}: {}", self.origin, self.message)
+            }
+            None => write!(f, "{}: {}", self.origin, self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Returns the name of a key, as written in messages.
+fn key_name(key: &[String]) -> String {
+    format!("`{}`", key.join("."))
+}
+
+/// Replaces the entries whose values are inline tables by the entries of these
+/// tables, with their keys prefixed.
+fn flatten(entries: Vec<Entry>) -> Vec<Entry> {
+    let mut flat = Vec::new();
+
+    for entry in entries {
+        match entry.value {
+            Value::Table(entries) => flat.extend(flatten(entries).into_iter().map(|mut inner| {
+                inner.key.splice(..0, entry.key.iter().cloned());
+                inner
+            })),
+            _ => flat.push(entry),
+        }
+    }
+
+    flat
+}
+
+/// Parses the manifest at `path`, returning the entries of its `table`, flattened and
+/// with their keys relative to it.
+fn table_entries(source: &str, path: &Path, table: &[&str]) -> Result<Vec<Entry>, Error> {
+    let entries = toml::parse(source).map_err(|e| Error {
+        origin: path.display().to_string(),
+        position: Some(e.position),
+        message: e.message,
+    })?;
+
+    let prefix: Vec<_> = table.iter().map(|key| key.to_string()).collect();
+    let mut relative = Vec::new();
+    for mut entry in entries {
+        if !entry.key.starts_with(&prefix) {
+            continue;
+        }
+
+        entry.key.drain(..prefix.len());
+        relative.push(entry);
+    }
+
+    Ok(flatten(relative))
+}
+
+/// Returns `true` if the string is a visibility other than the inherited one.
+fn is_visibility(vis: &str) -> bool {
+    matches!(
+        parse_str(vis),
+        Ok(Visibility::Public(_) | Visibility::Restricted(_))
+    )
+}
+
+/// Returns the list of the valid keys, as written in messages.
+fn expected_keys(keys: &[&str]) -> String {
+    let keys: Vec<_> = keys.iter().map(|key| format!("`{key}`")).collect();
+    keys.join(", ")
+}
+
+/// Sets the default named `key` from the value found at `position`, returning
+/// `Ok(false)` if there is no such default. `name` is the full name of the key.
+///
+/// Errors are returned with their position and message.
+fn set_default(
+    defaults: &mut Defaults,
+    key: &str,
+    name: &str,
+    value: &Value,
+    position: Position,
+) -> Result<bool, (Position, String)> {
+    let invalid = |expected: &str| {
+        let message = format!("expected {expected} for {name}, found {}", value.kind());
+        Err((position, message))
+    };
+
+    match (key, value) {
+        ("recursive", Value::Bool(value)) => defaults.recursive = Some(*value),
+        ("recursive", _) => return invalid("a boolean"),
+        ("vis", Value::String(vis)) if is_visibility(vis) => defaults.vis = Some(vis.clone()),
+        ("vis", _) => return invalid("a visibility such as `\"pub(crate)\"`"),
+        (key @ ("exclude_names" | "marker_types"), Value::Array(values)) => {
+            let mut names = Vec::new();
+            for (position, value) in values {
+                match value {
+                    Value::String(name) => names.push(name.clone()),
+                    _ => {
+                        let message =
+                            format!("expected a string in {name}, found {}", value.kind());
+                        return Err((*position, message));
+                    }
+                }
+            }
+            match key {
+                "exclude_names" => defaults.exclude_names = Some(names),
+                _ => defaults.marker_types = Some(names),
+            }
+        }
+        ("exclude_names" | "marker_types", _) => return invalid("an array of strings"),
+        ("exact", Value::Bool(value)) => defaults.exact = Some(*value),
+        ("exact", _) => return invalid("a boolean"),
+        ("markers", Value::Bool(value)) => defaults.markers = Some(*value),
+        ("markers", _) => return invalid("a boolean"),
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+/// Adds the value declared in code under the name of `ident` to the list, failing if
+/// a `kind` of the same name was already declared.
+#[cfg(feature = "files")]
+fn add_named<T>(
+    list: &mut Vec<(String, T)>,
+    kind: &str,
+    ident: &Ident,
+    value: T,
+) -> Result<(), String> {
+    let name = ident.to_string();
+    if list.iter().any(|(other, _)| *other == name) {
+        return Err(format!("duplicate {kind} `{name}`"));
+    }
+
+    list.push((name, value));
+    Ok(())
+}
+
+/// Parses a pattern of module paths, returning its segments without a leading
+/// `crate`, or `None` if it is invalid.
+fn parse_pattern(pattern: &str) -> Option<Vec<String>> {
+    let mut segments: Vec<_> = pattern.split("::").map(str::to_string).collect();
+
+    if segments.first().is_some_and(|first| first == "crate") {
+        segments.remove(0);
+    }
+
+    let is_valid = segments.iter().all(|segment| {
+        segment == "**"
+            || !segment.is_empty()
+                && segment
+                    .chars()
+                    .all(|c| c == '*' || c == '_' || c.is_alphanumeric())
+    });
+
+    is_valid.then_some(segments)
+}
+
+/// Returns the position of a token parsed from the crate root, where it is known: a
+/// procedural macro parses it into tokens with the span of its call site, so only the
+/// tools, out of one and with the `span-locations` feature of `proc-macro2` that
+/// `codegen` enables, know where they are.
+#[cfg(all(feature = "files", feature = "codegen"))]
+fn position(span: Span) -> Option<Position> {
+    let start = span.start();
+    (!proc_macro::is_available()).then_some(Position {
+        line: start.line,
+        column: start.column + 1,
+    })
+}
+
+#[cfg(all(feature = "files", not(feature = "codegen")))]
+fn position(_: Span) -> Option<Position> {
+    None
+}
+
+impl Config {
+    /// Parses and validates the configuration in the source of a manifest, located
+    /// at `path` (which gives the location of the `src` directory). A manifest without
+    /// a `[package.metadata.fully_pub]` table gives the default configuration.
+    ///
+    /// ```
+    /// use std::path::{Path, PathBuf};
+    /// use fully_pub_core::Config;
+    ///
+    /// let manifest = "[package]\nname = \"app\"\n\n[package.metadata.fully_pub]\nrecusive = true\n";
+    /// let error = Config::parse(manifest, Path::new("Cargo.toml")).unwrap_err();
+    ///
+    /// assert_eq!(
+    ///     error.to_string(),
+    ///     "Cargo.toml:5:1: unknown key `recusive` in `[package.metadata.fully_pub]`, \
+    ///     expected one of `recursive`, `vis`, `exclude_names`, `exact`, `markers`, \
+    ///     `marker_types`, `modules`, `cache`, `lints.redundant_pub`, \
+    ///     `lints.private_bounds`",
+    /// );
+    /// ```
+    pub fn parse(source: &str, path: &Path) -> Result<Config, Error> {
+        let entries = table_entries(source, path, &TABLE)?;
+        Config::from_entries(&entries, path, &TABLE)
+    }
+
+    /// Validates the entries of the configuration, read from the `table` of the
+    /// manifest at `path`, with their keys relative to it.
+    fn from_entries(entries: &[Entry], path: &Path, table: &[&str]) -> Result<Config, Error> {
+        let error = |position, message| Error {
+            origin: path.display().to_string(),
+            position: Some(position),
+            message,
+        };
+
+        let mut config = Config {
+            manifest_dir: path.parent().map(Path::to_path_buf),
+            ..Config::default()
+        };
+        let mut seen = Vec::new();
+
+        for Entry {
+            key,
+            position,
+            value_position,
+            value,
+        } in entries
+        {
+            let name = key_name(key);
+            let key: Vec<_> = key.iter().map(String::as_str).collect();
+            let unknown = |keys: &[&str]| {
+                let message = format!(
+                    "unknown key {name} in `[{}]`, expected one of {}",
+                    table.join("."),
+                    expected_keys(keys),
+                );
+                Err(error(*position, message))
+            };
+
+            match key.as_slice() {
+                ["cache"] => match value {
+                    Value::Bool(cache) => config.cache = *cache,
+                    _ => {
+                        let message =
+                            format!("expected a boolean for {name}, found {}", value.kind());
+                        return Err(error(*value_position, message));
+                    }
+                },
+                [key] => {
+                    let defaults = &mut config.defaults;
+                    match set_default(defaults, key, &name, value, *value_position) {
+                        Ok(true) => (),
+                        Ok(false) => return unknown(KEYS),
+                        Err((position, message)) => return Err(error(position, message)),
+                    }
+                }
+                ["modules", pattern, key] => {
+                    let Some(pattern) = parse_pattern(pattern) else {
+                        let message = format!("invalid module path pattern in {name}");
+                        return Err(error(*position, message));
+                    };
+
+                    let index = match config.rules.iter().position(|r| r.pattern == pattern) {
+                        Some(index) => index,
+                        None => {
+                            config.rules.push(Rule {
+                                pattern,
+                                defaults: Defaults::default(),
+                            });
+                            config.rules.len() - 1
+                        }
+                    };
+
+                    let defaults = &mut config.rules[index].defaults;
+                    match set_default(defaults, key, &name, value, *value_position) {
+                        Ok(true) => (),
+                        Ok(false) => return unknown(DEFAULT_KEYS),
+                        Err((position, message)) => return Err(error(position, message)),
+                    }
+                }
+                ["lints", lint @ ("redundant_pub" | "private_bounds")] => {
+                    let level = match value {
+                        Value::String(level) if level == "allow" => Level::Allow,
+                        Value::String(level) if level == "warn" => Level::Warn,
+                        Value::String(level) if level == "deny" => Level::Deny,
+                        _ => {
+                            let message = format!(
+                                "expected one of `\"allow\"`, `\"warn\"` or `\"deny\"` \
+                                for {name}, found {}",
+                                value.kind(),
+                            );
+                            return Err(error(*value_position, message));
+                        }
+                    };
+                    match *lint {
+                        "redundant_pub" => config.lints.redundant_pub = level,
+                        _ => config.lints.private_bounds = level,
+                    }
+                }
+                _ => return unknown(KEYS),
+            }
+
+            if seen.contains(&key) {
+                return Err(error(*position, format!("duplicate key {name}")));
+            }
+            seen.push(key);
+        }
+
+        Ok(config)
+    }
+
+    /// Reads and validates the configuration of the crate whose manifest is in
+    /// `manifest_dir`, including the `fully_pub::config!` invocation of its root,
+    /// whose defaults take precedence over the ones of the manifest.
+    ///
+    /// The visibility given to the members can be overridden by setting the
+    /// [`VIS_VAR`] environment variable, to build a variant of the crate without
+    /// changing its sources.
+    ///
+    /// The crates of a workspace share the `[workspace.metadata.fully_pub]` table of
+    /// its root manifest, whose keys each crate can override in its own table:
+    ///
+    /// ```
+    /// use fully_pub_core::{expand, Args, Config};
+    /// use quote::ToTokens;
+    ///
+    /// # let dir = std::env::temp_dir().join("fully_pub_core_config_doc");
+    /// # std::fs::create_dir_all(dir.join("app")).unwrap();
+    /// std::fs::write(
+    ///     dir.join("Cargo.toml"),
+    ///     "[workspace]\nmembers = [\"app\"]\n\n\
+    ///     [workspace.metadata.fully_pub]\nvis = \"pub(crate)\"\nexclude_names = [\"_*\"]\n",
+    /// )
+    /// .unwrap();
+    /// std::fs::write(
+    ///     dir.join("app/Cargo.toml"),
+    ///     "[package]\nname = \"app\"\n\n[package.metadata.fully_pub]\nvis = \"pub\"\n",
+    /// )
+    /// .unwrap();
+    ///
+    /// let mut args = Args::default();
+    /// args.apply(&Config::load(&dir.join("app")).unwrap()).unwrap();
+    /// let mut item = syn::parse_str("struct Point { x: f64, _tag: u8 }").unwrap();
+    /// expand(&args, &mut item).unwrap();
+    ///
+    /// assert_eq!(
+    ///     item.to_token_stream().to_string(),
+    ///     "pub struct Point { pub x : f64 , _tag : u8 }",
+    /// );
+    /// ```
+    #[cfg(feature = "files")]
+    pub fn load(manifest_dir: &Path) -> Result<Config, Error> {
+        let mut config = Config::load_manifest(manifest_dir)?;
+        config.apply_code(manifest_dir)?;
+        Ok(config)
+    }
+
+    /// Reads and validates the configuration in the manifest, and [`VIS_VAR`].
+    ///
+    /// The `[workspace.metadata.fully_pub]` table of the root manifest of the
+    /// workspace of the crate, if any, gives the base of the configuration, and the
+    /// keys of the table of the crate override the ones it sets.
+    #[cfg(feature = "files")]
+    fn load_manifest(manifest_dir: &Path) -> Result<Config, Error> {
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|e| Error {
+                origin: path.display().to_string(),
+                position: None,
+                message: e.to_string(),
+            })
+        };
+
+        let path = manifest_dir.join("Cargo.toml");
+        let source = read(&path)?;
+        let mut entries = table_entries(&source, &path, &TABLE)?;
+        // Validated first, for its errors to point into its own table.
+        Config::from_entries(&entries, &path, &TABLE)?;
+
+        if let Some(root) = workspace::workspace_root(manifest_dir)? {
+            let root = root.join("Cargo.toml");
+            let mut shared = table_entries(&read(&root)?, &root, &WORKSPACE_TABLE)?;
+            Config::from_entries(&shared, &root, &WORKSPACE_TABLE)?;
+
+            shared.retain(|entry| entries.iter().all(|own| own.key != entry.key));
+            entries.splice(..0, shared);
+        }
+
+        let mut config = Config::from_entries(&entries, &path, &TABLE)?;
+
+        if let Some(vis) = env::var_os(VIS_VAR).filter(|vis| !vis.is_empty()) {
+            let vis = vis.to_string_lossy();
+            if !is_visibility(&vis) {
+                return Err(Error {
+                    origin: format!("environment variable `{VIS_VAR}`"),
+                    position: None,
+                    message: format!("expected a visibility such as `pub(crate)`, found `{vis}`"),
+                });
+            }
+            config.vis_override = Some(vis.into_owned());
+        }
+
+        Ok(config)
+    }
+
+    /// Merges the defaults given by the `fully_pub::config!` invocation of the crate
+    /// root into the ones of the whole crate, and adds the groups and the exclusion
+    /// sets declared by its `fully_pub::group!` and `fully_pub::exclude_set!`
+    /// invocations.
+    ///
+    /// A crate root that can't be read or parsed gives no defaults: the error is left
+    /// to the compiler. Otherwise, the invocations are applied as far as they are valid
+    /// and the first error is returned.
+    #[cfg(feature = "files")]
+    fn apply_code(&mut self, manifest_dir: &Path) -> Result<(), Error> {
+        let Ok(root) = crate_root(manifest_dir) else {
+            return Ok(());
+        };
+        let Some(file) = fs::read_to_string(&root)
+            .ok()
+            .and_then(|source| syn::parse_file(&source).ok())
+        else {
+            return Ok(());
+        };
+
+        let error = |span: Span, message| Error {
+            origin: root.display().to_string(),
+            position: position(span),
+            message,
+        };
+        let mut first_error = None;
+
+        let invocations = code::invocations(&file, "config");
+        if let Some(mac) = invocations.first() {
+            match mac.parse_body::<ConfigArgs>() {
+                Ok(args) => self.defaults.merge(&args.defaults),
+                Err(e) => first_error = Some(error(e.span(), e.to_string())),
+            }
+        }
+
+        if let Some(mac) = invocations.get(1) {
+            let message = format!("`{CRATE_NAME}::config!` can only be invoked once per crate");
+            let span = mac.path.segments[0].ident.span();
+            first_error = first_error.or(Some(error(span, message)));
+        }
+
+        for mac in code::invocations(&file, "group") {
+            match mac.parse_body::<GroupArgs>() {
+                Ok(args) => {
+                    for (ident, defaults) in args.groups {
+                        if let Err(message) = add_named(&mut self.groups, "group", &ident, defaults)
+                        {
+                            first_error = first_error.or(Some(error(ident.span(), message)));
+                        }
+                    }
+                }
+                Err(e) => first_error = first_error.or(Some(error(e.span(), e.to_string()))),
+            }
+        }
+
+        for mac in code::invocations(&file, "exclude_set") {
+            match mac.parse_body::<ExcludeSetArgs>() {
+                Ok(args) => {
+                    for (ident, names) in args.sets {
+                        let sets = &mut self.exclude_sets;
+                        if let Err(message) = add_named(sets, "exclusion set", &ident, names) {
+                            first_error = first_error.or(Some(error(ident.span(), message)));
+                        }
+                    }
+                }
+                Err(e) => first_error = first_error.or(Some(error(e.span(), e.to_string()))),
+            }
+        }
+
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Returns the names and patterns of the set declared with `fully_pub::exclude_set!`
+    /// under that name, if any.
+    pub(crate) fn exclude_set(&self, name: &str) -> Option<&[String]> {
+        self.exclude_sets
+            .iter()
+            .find_map(|(set, names)| (set == name).then_some(names.as_slice()))
+    }
+
+    /// Returns the arguments of the group declared with `fully_pub::group!` under
+    /// that name, if any.
+    pub(crate) fn group(&self, name: &str) -> Option<&Defaults> {
+        self.groups
+            .iter()
+            .find_map(|(group, defaults)| (group == name).then_some(defaults))
+    }
+
+    /// Returns the configuration for the attributes of the given source file, whose
+    /// module path is deduced from its location in the `src` directory of the crate.
+    ///
+    /// Modules declared inline, or loaded through a `#[path]` attribute, are not seen:
+    /// the attributes they contain follow the rules of the module of their file.
+    /// Files outside of `src` follow no rules, only the defaults of the whole crate.
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use fully_pub_core::{expand, Args, Config};
+    /// use quote::ToTokens;
+    ///
+    /// let manifest = "\
+    /// [package.metadata.fully_pub.modules.\"internal::**\"]
+    /// vis = \"pub(crate)\"
+    /// ";
+    /// let config = Config::parse(manifest, Path::new("/app/Cargo.toml")).unwrap();
+    /// let config = config.in_file(Path::new("/app/src/internal/db.rs"));
+    ///
+    /// let mut args = Args::default();
+    /// args.apply(&config).unwrap();
+    /// let mut item = syn::parse_str("fn f() {}").unwrap();
+    /// expand(&args, &mut item).unwrap();
+    ///
+    /// assert_eq!(item.to_token_stream().to_string(), "pub (crate) fn f () { }");
+    /// ```
+    pub fn in_file(&self, file: &Path) -> Config {
+        let absolute = |path: &Path| {
+            env::current_dir()
+                .map(|dir| dir.join(path))
+                .unwrap_or_else(|_| path.to_path_buf())
+        };
+
+        let file = absolute(file);
+        let module = self
+            .manifest_dir
+            .as_ref()
+            .and_then(|dir| file.strip_prefix(absolute(&dir.join("src"))).ok())
+            .map(|path| {
+                let mut segments: Vec<_> = path
+                    .with_extension("")
+                    .iter()
+                    .map(|segment| segment.to_string_lossy().into_owned())
+                    .collect();
+
+                // The binaries of `src/bin` are crate roots of their own.
+                if segments.first().is_some_and(|first| first == "bin") {
+                    segments.drain(..segments.len().min(2));
+                }
+
+                let is_root = segments.len() == 1 && matches!(&*segments[0], "lib" | "main");
+                if is_root || segments.last().is_some_and(|last| last == "mod") {
+                    segments.pop();
+                }
+
+                segments
+            });
+
+        Config {
+            module,
+            ..self.clone()
+        }
+    }
+
+    /// Returns the defaults applying to the module of the configuration.
+    pub(crate) fn defaults(&self) -> Defaults {
+        let mut defaults = self.defaults.clone();
+
+        if let Some(module) = &self.module {
+            for rule in &self.rules {
+                if matches_path(&rule.pattern, module) {
+                    defaults.merge(&rule.defaults);
+                }
+            }
+        }
+
+        if self.vis_override.is_some() {
+            defaults.vis.clone_from(&self.vis_override);
+        }
+
+        defaults
+    }
+
+    /// Returns the configuration of the crate being compiled, recording its manifest
+    /// and [`VIS_VAR`] into the tracker.
+    ///
+    /// The configuration is loaded once per crate and kept for the following calls,
+    /// as long as its manifest, the root manifest of its workspace, its root and
+    /// [`VIS_VAR`] do not change, which only happens in long-lived processes such as
+    /// the ones of IDEs. If it is invalid, the error is returned by the first call
+    /// only, and the following ones use the default configuration, so as not to
+    /// repeat the error on every attribute.
+    ///
+    /// Errors in the invocations of `fully_pub::config!`, `fully_pub::group!` and
+    /// `fully_pub::exclude_set!` are reported by these macros, with their exact spans,
+    /// so they are ignored here.
+    ///
+    /// Without the `files` feature, nothing is read and this is the default
+    /// configuration.
+    pub fn current(tracker: &mut Tracker) -> syn::Result<&'static Config> {
+        static DEFAULT: Config = Config {
+            defaults: Defaults {
+                recursive: None,
+                vis: None,
+                exclude_names: None,
+                exact: None,
+                markers: None,
+                marker_types: None,
+            },
+            rules: Vec::new(),
+            groups: Vec::new(),
+            exclude_sets: Vec::new(),
+            vis_override: None,
+            lints: Lints {
+                redundant_pub: Level::Allow,
+                private_bounds: Level::Warn,
+            },
+            cache: false,
+            manifest_dir: None,
+            module: None,
+        };
+
+        #[cfg(feature = "files")]
+        if let Some(cached) = Config::cached(tracker) {
+            return match &cached.config {
+                Ok(config) => Ok(config),
+                Err(e) if !cached.reported.swap(true, Ordering::Relaxed) => Err(syn::Error::new(
+                    Span::call_site(),
+                    format!("invalid configuration of `fully_pub`: {e}"),
+                )),
+                _ => Ok(&DEFAULT),
+            };
+        }
+
+        #[cfg(not(feature = "files"))]
+        let _ = tracker;
+        Ok(&DEFAULT)
+    }
+
+    /// Returns the cached configuration of the crate being compiled, loading it if it
+    /// changed, or `None` out of cargo.
+    #[cfg(feature = "files")]
+    fn cached(tracker: &mut Tracker) -> Option<&'static Cached> {
+        static CACHE: Mutex<Vec<&'static Cached>> = Mutex::new(Vec::new());
+
+        let manifest_dir = env::var_os("CARGO_MANIFEST_DIR")?;
+        let manifest_dir = Path::new(&manifest_dir);
+        let manifest = manifest_dir.join("Cargo.toml");
+        tracker.file(&manifest);
+
+        let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+        let vis = tracker.var(VIS_VAR);
+        let root = crate_root(manifest_dir)
+            .ok()
+            .and_then(|root| modified(&root));
+
+        let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+        let found = cache
+            .iter()
+            .position(|cached| cached.manifest_dir == manifest_dir);
+
+        // The workspace of a crate is only looked for again when its manifest
+        // changes, which it does to join or leave one.
+        let workspace = match found {
+            Some(index) if cache[index].stamp.manifest == modified(&manifest) => {
+                cache[index].workspace.clone()
+            }
+            _ => workspace::workspace_root(manifest_dir)
+                .ok()
+                .flatten()
+                .map(|root| root.join("Cargo.toml")),
+        };
+        if let Some(workspace) = &workspace {
+            tracker.file(workspace);
+        }
+
+        let stamp = Stamp {
+            manifest: modified(&manifest),
+            workspace: workspace.as_deref().and_then(modified),
+            root,
+            vis,
+        };
+
+        Some(match found {
+            Some(index) if cache[index].stamp == stamp => cache[index],
+            found => {
+                let config = Config::load_manifest(manifest_dir).map(|mut config| {
+                    let _ = config.apply_code(manifest_dir);
+                    config
+                });
+
+                // Entries are only replaced when the crate changes, so leaking the
+                // previous ones is bounded by the number of edits.
+                let cached: &'static Cached = Box::leak(Box::new(Cached {
+                    manifest_dir: manifest_dir.to_path_buf(),
+                    workspace,
+                    stamp,
+                    config,
+                    reported: AtomicBool::new(false),
+                }));
+
+                match found {
+                    Some(index) => cache[index] = cached,
+                    None => cache.push(cached),
+                }
+                cached
+            }
+        })
+    }
+}
+
+/// What a configuration cached by [`Config::current`] was loaded from, for it to be
+/// loaded again when it changes.
+#[cfg(feature = "files")]
+#[derive(PartialEq)]
+struct Stamp {
+    /// The last modification of the manifest.
+    manifest: Option<SystemTime>,
+    /// The last modification of the root manifest of the workspace.
+    workspace: Option<SystemTime>,
+    /// The last modification of the crate root.
+    root: Option<SystemTime>,
+    /// The value of [`VIS_VAR`].
+    vis: Option<OsString>,
+}
+
+/// A configuration cached by [`Config::current`].
+#[cfg(feature = "files")]
+struct Cached {
+    manifest_dir: PathBuf,
+    /// The root manifest of the workspace of the crate, if any.
+    workspace: Option<PathBuf>,
+    stamp: Stamp,
+    config: Result<Config, Error>,
+    /// Whether the error of an invalid configuration was reported.
+    reported: AtomicBool,
+}
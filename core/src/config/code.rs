@@ -0,0 +1,254 @@
+//! The configuration written in the crate root with the `fully_pub::config!` and
+//! `fully_pub::group!` macros, as an alternative to the table of the manifest.
+
+use std::fs;
+use std::path::Path;
+
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::*;
+
+use super::{expected_keys, Defaults, DEFAULT_KEYS};
+use crate::source::crate_root;
+use crate::CRATE_NAME;
+
+/// Parses the defaults given by the `key: value` pairs of the input, as written in
+/// the `fully_pub::<name>!` macro.
+fn parse_defaults(input: ParseStream, name: &str) -> Result<Defaults> {
+    let mut defaults = Defaults::default();
+    let mut seen = Vec::new();
+
+    while !input.is_empty() {
+        let ident: Ident = input.parse()?;
+        input.parse::<Token![:]>()?;
+
+        match () {
+            _ if ident == "recursive" => {
+                defaults.recursive = Some(input.parse::<LitBool>()?.value);
+            }
+            _ if ident == "vis" => {
+                let vis: Visibility = input.parse()?;
+                if let Visibility::Inherited = vis {
+                    bail!(&ident, "expected a visibility after `vis:`");
+                }
+                defaults.vis = Some(vis.into_token_stream().to_string());
+            }
+            _ if ident == "exclude_names" || ident == "marker_types" => {
+                let content;
+                bracketed!(content in input);
+                let names = Punctuated::<LitStr, Comma>::parse_terminated(&content)?;
+                let names = Some(names.iter().map(LitStr::value).collect());
+                match ident == "exclude_names" {
+                    true => defaults.exclude_names = names,
+                    false => defaults.marker_types = names,
+                }
+            }
+            _ if ident == "exact" => defaults.exact = Some(input.parse::<LitBool>()?.value),
+            _ if ident == "markers" => defaults.markers = Some(input.parse::<LitBool>()?.value),
+            _ => bail!(
+                &ident,
+                "unknown key `{ident}` in `{CRATE_NAME}::{name}!`, expected one of {}",
+                expected_keys(DEFAULT_KEYS),
+            ),
+        }
+
+        if seen.contains(&ident) {
+            bail!(&ident, "duplicate key `{ident}` in `{CRATE_NAME}::{name}!`");
+        }
+        seen.push(ident);
+
+        if !input.is_empty() {
+            input.parse::<Comma>()?;
+        }
+    }
+
+    Ok(defaults)
+}
+
+/// Arguments passed to the `fully_pub::config! { ... }` macro.
+pub struct ConfigArgs {
+    // Only read from the crate root, with the `files` feature: without it, the
+    // arguments are only checked.
+    #[cfg_attr(not(feature = "files"), allow(dead_code))]
+    pub(crate) defaults: Defaults,
+}
+
+impl Parse for ConfigArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let defaults = parse_defaults(input, "config")?;
+        Ok(ConfigArgs { defaults })
+    }
+}
+
+/// Arguments passed to the `fully_pub::group! { ... }` macro: named bundles of
+/// arguments for the attributes.
+pub struct GroupArgs {
+    #[cfg_attr(not(feature = "files"), allow(dead_code))]
+    pub(crate) groups: Vec<(Ident, Defaults)>,
+}
+
+impl Parse for GroupArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut groups: Vec<(Ident, Defaults)> = Vec::new();
+
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            input.parse::<Token![:]>()?;
+
+            let content;
+            braced!(content in input);
+            let defaults = parse_defaults(&content, "group")?;
+
+            if groups.iter().any(|(name, _)| *name == ident) {
+                bail!(&ident, "duplicate group `{ident}`");
+            }
+            groups.push((ident, defaults));
+
+            if !input.is_empty() {
+                input.parse::<Comma>()?;
+            }
+        }
+
+        Ok(GroupArgs { groups })
+    }
+}
+
+/// Arguments passed to the `fully_pub::exclude_set!(...)` macro: named sets of names
+/// and patterns of members to leave untouched.
+pub struct ExcludeSetArgs {
+    #[cfg_attr(not(feature = "files"), allow(dead_code))]
+    pub(crate) sets: Vec<(Ident, Vec<String>)>,
+}
+
+impl Parse for ExcludeSetArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut sets: Vec<(Ident, Vec<String>)> = Vec::new();
+
+        while !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+
+            let content;
+            bracketed!(content in input);
+            let mut names = Vec::new();
+            while !content.is_empty() {
+                match content.peek(LitStr) {
+                    true => names.push(content.parse::<LitStr>()?.value()),
+                    false => names.push(Ident::parse_any(&content)?.unraw().to_string()),
+                }
+
+                if !content.is_empty() {
+                    content.parse::<Comma>()?;
+                }
+            }
+
+            if sets.iter().any(|(name, _)| *name == ident) {
+                bail!(&ident, "duplicate exclusion set `{ident}`");
+            }
+            sets.push((ident, names));
+
+            if !input.is_empty() {
+                input.parse::<Comma>()?;
+            }
+        }
+
+        Ok(ExcludeSetArgs { sets })
+    }
+}
+
+/// Returns `true` if the macro is `fully_pub::<name>!`, written by its full path.
+fn is_macro(mac: &Macro, name: &str) -> bool {
+    let segments: Vec<_> = mac.path.segments.iter().map(|s| &s.ident).collect();
+    matches!(segments.as_slice(), [krate, ident] if *krate == CRATE_NAME && *ident == name)
+}
+
+/// Returns the invocations of `fully_pub::<name>!` among the items of the crate root.
+pub(crate) fn invocations<'a>(file: &'a File, name: &str) -> Vec<&'a Macro> {
+    file.items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Macro(ItemMacro { mac, .. }) if is_macro(mac, name) => Some(mac),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Checks that an invocation of the `fully_pub::<name>!` macro made from `file` is
+/// in the root of the crate being compiled, and written by its full path.
+///
+/// Returns how many times the macro is invoked in the crate root, if it is known.
+fn check_location(file: Option<&Path>, name: &str) -> Result<Option<usize>> {
+    let span = proc_macro2::Span::call_site();
+
+    // Without the location of the invocation, or the `files` feature to read the crate
+    // root, there is nothing to check.
+    let (Some(file), Some(manifest_dir)) = (file, std::env::var_os("CARGO_MANIFEST_DIR")) else {
+        return Ok(None);
+    };
+    if !cfg!(feature = "files") {
+        return Ok(None);
+    }
+
+    let root = crate_root(Path::new(&manifest_dir))
+        .ok()
+        .and_then(|root| fs::canonicalize(root).ok());
+    let is_root = root.is_some_and(|root| fs::canonicalize(file).is_ok_and(|file| file == root));
+
+    if !is_root {
+        return Err(Error::new(
+            span,
+            format!("`{CRATE_NAME}::{name}!` must be invoked in the crate root"),
+        ));
+    }
+
+    let count = fs::read_to_string(file)
+        .ok()
+        .and_then(|source| parse_file(&source).ok())
+        .map(|parsed| invocations(&parsed, name).len());
+
+    if count == Some(0) {
+        return Err(Error::new(
+            span,
+            format!(
+                "`{CRATE_NAME}::{name}!` must be invoked by its full path, for the \
+                attributes to find it"
+            ),
+        ));
+    }
+
+    Ok(count)
+}
+
+/// Checks an invocation of the `fully_pub::config!` macro made from `file`: it must be
+/// in the root of the crate being compiled, and be the only invocation in it.
+///
+/// The attributes read the configuration from the sources of the crate root, so the
+/// macro itself expands to nothing once its arguments are parsed.
+pub fn config(file: Option<&Path>) -> Result<()> {
+    // The invocations of the crate root can't be told apart, so all of them fail.
+    if check_location(file, "config")?.is_some_and(|count| count > 1) {
+        return Err(Error::new(
+            proc_macro2::Span::call_site(),
+            format!("`{CRATE_NAME}::config!` can only be invoked once per crate"),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks an invocation of the `fully_pub::group!` macro made from `file`, which must
+/// be in the root of the crate being compiled. Like `fully_pub::config!`, the macro
+/// expands to nothing.
+pub fn group(file: Option<&Path>) -> Result<()> {
+    check_location(file, "group").map(|_| ())
+}
+
+/// Checks an invocation of the `fully_pub::exclude_set!` macro made from `file`,
+/// which must be in the root of the crate being compiled. Like `fully_pub::config!`,
+/// the macro expands to nothing.
+pub fn exclude_set(file: Option<&Path>) -> Result<()> {
+    check_location(file, "exclude_set").map(|_| ())
+}
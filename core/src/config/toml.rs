@@ -0,0 +1,445 @@
+//! A small TOML reader, enough to find the keys of a table in a `Cargo.toml`
+//! and report precise locations.
+//!
+//! Every value is read, so that the document is known to be well-formed, but only
+//! booleans, strings, arrays and tables are kept: other values (integers, floats and
+//! dates) are only classified.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A position in the document, 1-based.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Position {
+    pub(crate) line: usize,
+    pub(crate) column: usize,
+}
+
+/// A TOML value.
+#[derive(Clone, Debug)]
+pub(crate) enum Value {
+    Bool(bool),
+    Integer,
+    String(String),
+    Array(Vec<(Position, Value)>),
+    Table(Vec<Entry>),
+    /// A float or a date.
+    Other,
+}
+
+impl Value {
+    /// The name of the kind of value, to be used in messages.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "a boolean",
+            Value::Integer => "an integer",
+            Value::String(_) => "a string",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+            Value::Other => "a float or a date",
+        }
+    }
+}
+
+/// A key and its value, the key being the full dotted path from the root of the
+/// document.
+#[derive(Clone, Debug)]
+pub(crate) struct Entry {
+    pub(crate) key: Vec<String>,
+    /// The position of the key.
+    pub(crate) position: Position,
+    /// The position of the value.
+    pub(crate) value_position: Position,
+    pub(crate) value: Value,
+}
+
+/// A syntax error.
+pub(crate) struct SyntaxError {
+    pub(crate) position: Position,
+    pub(crate) message: String,
+}
+
+type Result<T> = std::result::Result<T, SyntaxError>;
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: Position,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.position.line += 1;
+            self.position.column = 1;
+        } else {
+            self.position.column += 1;
+        }
+
+        Some(c)
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T> {
+        Err(SyntaxError {
+            position: self.position,
+            message: message.into(),
+        })
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let is_c = self.peek() == Some(c);
+        if is_c {
+            self.bump();
+        }
+        is_c
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        match self.eat(c) {
+            true => Ok(()),
+            false => self.error(format!("expected `{c}`")),
+        }
+    }
+
+    /// Skips spaces and tabs.
+    fn skip_spaces(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    /// Skips whitespace, newlines and comments.
+    fn skip_blank(&mut self) {
+        loop {
+            match self.peek() {
+                Some(' ' | '\t' | '\r' | '\n') => {
+                    self.bump();
+                }
+                Some('#') => self.skip_comment(),
+                _ => return,
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        while !matches!(self.peek(), Some('\n') | None) {
+            self.bump();
+        }
+    }
+
+    /// Expects the end of the line, after a key-value pair or a table header.
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_spaces();
+        if self.peek() == Some('#') {
+            self.skip_comment();
+        }
+
+        match self.peek() {
+            Some('\r' | '\n') | None => Ok(()),
+            Some(c) => self.error(format!("expected a newline, found `{c}`")),
+        }
+    }
+
+    /// Parses a dotted key.
+    fn key(&mut self) -> Result<Vec<String>> {
+        let mut key = Vec::new();
+
+        loop {
+            self.skip_spaces();
+            key.push(match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let mut part = String::new();
+                    while let Some(c @ ('A'..='Z' | 'a'..='z' | '0'..='9' | '_' | '-')) =
+                        self.peek()
+                    {
+                        part.push(c);
+                        self.bump();
+                    }
+
+                    if part.is_empty() {
+                        return self.error("expected a key");
+                    }
+                    part
+                }
+            });
+
+            self.skip_spaces();
+            if !self.eat('.') {
+                return Ok(key);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('"') => self.basic_string().map(Value::String),
+            Some('\'') => self.literal_string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.inline_table(),
+            _ => self.scalar(),
+        }
+    }
+
+    /// Parses a string between double quotes, single or multi-line.
+    fn basic_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let multiline = self.starts_multiline('"');
+        let mut string = String::new();
+
+        loop {
+            if !multiline && matches!(self.peek(), Some('\n')) {
+                return self.error("unterminated string");
+            }
+
+            match self.bump() {
+                None => return self.error("unterminated string"),
+                Some('"') if !multiline => return Ok(string),
+                Some('"') if self.ends_multiline('"', &mut string) => return Ok(string),
+                Some('\\') => match self.bump() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('b') => string.push('\u{8}'),
+                    Some('f') => string.push('\u{c}'),
+                    Some('e') => string.push('\u{1b}'),
+                    Some('"') => string.push('"'),
+                    Some('\\') => string.push('\\'),
+                    Some('u') => string.push(self.unicode(4)?),
+                    Some('U') => string.push(self.unicode(8)?),
+                    Some(' ' | '\t' | '\r' | '\n') if multiline => {
+                        while matches!(self.peek(), Some(' ' | '\t' | '\r' | '\n')) {
+                            self.bump();
+                        }
+                    }
+                    _ => return self.error("invalid escape sequence"),
+                },
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    /// Parses a string between single quotes, single or multi-line.
+    fn literal_string(&mut self) -> Result<String> {
+        self.expect('\'')?;
+        let multiline = self.starts_multiline('\'');
+        let mut string = String::new();
+
+        loop {
+            if !multiline && matches!(self.peek(), Some('\n')) {
+                return self.error("unterminated string");
+            }
+
+            match self.bump() {
+                None => return self.error("unterminated string"),
+                Some('\'') if !multiline => return Ok(string),
+                Some('\'') if self.ends_multiline('\'', &mut string) => return Ok(string),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    /// After an opening quote, reads the two others of a multi-line string if they
+    /// are there, and the newline that may follow. An empty string is left as is.
+    fn starts_multiline(&mut self, quote: char) -> bool {
+        if self.peek() != Some(quote) {
+            return false;
+        }
+
+        let mut lookahead = self.chars.clone();
+        lookahead.next();
+        if lookahead.next() != Some(quote) {
+            return false;
+        }
+
+        self.bump();
+        self.bump();
+        self.eat('\r');
+        self.eat('\n');
+        true
+    }
+
+    /// After a quote in a multi-line string, returns whether it closes the string,
+    /// pushing it to the string otherwise. Up to two extra quotes can precede the
+    /// closing ones.
+    fn ends_multiline(&mut self, quote: char, string: &mut String) -> bool {
+        let mut quotes = 1;
+        while quotes < 5 && self.eat(quote) {
+            quotes += 1;
+        }
+
+        let closes = quotes >= 3;
+        let pushed = if closes { quotes - 3 } else { quotes };
+        string.extend(std::iter::repeat_n(quote, pushed));
+        closes
+    }
+
+    fn unicode(&mut self, digits: usize) -> Result<char> {
+        let mut code = 0;
+
+        for _ in 0..digits {
+            match self.bump().and_then(|c| c.to_digit(16)) {
+                Some(digit) => code = code * 16 + digit,
+                None => return self.error("invalid unicode escape"),
+            }
+        }
+
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => self.error("invalid unicode scalar value"),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+
+        loop {
+            self.skip_blank();
+            if self.eat(']') {
+                return Ok(Value::Array(values));
+            }
+
+            let position = self.position;
+            values.push((position, self.value()?));
+
+            self.skip_blank();
+            if !self.eat(',') {
+                self.skip_blank();
+                self.expect(']')?;
+                return Ok(Value::Array(values));
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+
+        loop {
+            self.skip_blank();
+            if self.eat('}') {
+                return Ok(Value::Table(entries));
+            }
+
+            entries.push(self.entry(&[])?);
+
+            self.skip_blank();
+            if !self.eat(',') {
+                self.skip_blank();
+                self.expect('}')?;
+                return Ok(Value::Table(entries));
+            }
+        }
+    }
+
+    /// Parses a boolean, an integer, a float or a date.
+    fn scalar(&mut self) -> Result<Value> {
+        let mut text = String::new();
+
+        while let Some(c) = self.peek() {
+            // Dates may contain a space between the day and the time.
+            let is_date_space = c == ' '
+                && text.len() == 10
+                && matches!(self.chars.clone().nth(1), Some('0'..='9'));
+
+            if matches!(c, ',' | ']' | '}' | '#' | '\r' | '\n' | ' ' | '\t') && !is_date_space {
+                break;
+            }
+
+            text.push(c);
+            self.bump();
+        }
+
+        let digits = text.replace('_', "");
+        let integer = match digits.strip_prefix("0x") {
+            Some(hex) => i64::from_str_radix(hex, 16).ok(),
+            None => match digits.strip_prefix("0o") {
+                Some(octal) => i64::from_str_radix(octal, 8).ok(),
+                None => match digits.strip_prefix("0b") {
+                    Some(binary) => i64::from_str_radix(binary, 2).ok(),
+                    None => digits.parse().ok(),
+                },
+            },
+        };
+
+        match text.as_str() {
+            "" => self.error("expected a value"),
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ if integer.is_some() => Ok(Value::Integer),
+            _ if text.starts_with(|c: char| c.is_ascii_digit() || "+-.in".contains(c)) => {
+                Ok(Value::Other)
+            }
+            _ => self.error(format!("invalid value `{text}`")),
+        }
+    }
+
+    /// Parses a key-value pair, whose key is prefixed by `table`.
+    fn entry(&mut self, table: &[String]) -> Result<Entry> {
+        let position = self.position;
+        let mut key = table.to_vec();
+        key.extend(self.key()?);
+
+        self.expect('=')?;
+        self.skip_spaces();
+
+        let value_position = self.position;
+        let value = self.value()?;
+
+        Ok(Entry {
+            key,
+            position,
+            value_position,
+            value,
+        })
+    }
+}
+
+/// Parses the document, returning its key-value pairs in order.
+///
+/// The tables declared by headers are given as empty tables, for them to be known
+/// even without keys. Keys are not checked for duplicates.
+pub(crate) fn parse(source: &str) -> Result<Vec<Entry>> {
+    let mut parser = Parser {
+        chars: source.chars().peekable(),
+        position: Position { line: 1, column: 1 },
+    };
+    let mut table = Vec::new();
+    let mut entries = Vec::new();
+
+    loop {
+        parser.skip_blank();
+
+        match parser.peek() {
+            None => return Ok(entries),
+            Some('[') => {
+                parser.bump();
+                let is_array = parser.eat('[');
+
+                let position = parser.position;
+                table = parser.key()?;
+                entries.push(Entry {
+                    key: table.clone(),
+                    position,
+                    value_position: position,
+                    value: Value::Table(Vec::new()),
+                });
+
+                parser.expect(']')?;
+                if is_array {
+                    parser.expect(']')?;
+                }
+            }
+            Some(_) => entries.push(parser.entry(&table)?),
+        }
+
+        parser.end_of_line()?;
+    }
+}
@@ -0,0 +1,256 @@
+//! The packages of a workspace, read from the `[workspace]` table of its root
+//! manifest, for the tools to work on all of them at once.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::toml::{self, Entry, Value};
+use super::Error;
+use crate::explore::matches;
+
+/// A package to work on, with its own configuration.
+#[derive(Clone, Debug)]
+pub struct Package {
+    /// The name of the package, as written in its manifest.
+    pub name: String,
+    /// The directory containing the manifest of the package.
+    pub manifest_dir: PathBuf,
+}
+
+/// What a manifest declares.
+#[derive(Default)]
+struct Manifest {
+    /// The name of the package, if the manifest has a `[package]` table.
+    package: Option<String>,
+    /// The patterns of the members and the excluded paths, if the manifest has a
+    /// `[workspace]` table.
+    workspace: Option<(Vec<String>, Vec<String>)>,
+}
+
+/// Reads the manifest in the directory.
+fn read(manifest_dir: &Path) -> Result<Manifest, Error> {
+    let path = manifest_dir.join("Cargo.toml");
+    let error = |position, message| Error {
+        origin: path.display().to_string(),
+        position,
+        message,
+    };
+
+    let source = fs::read_to_string(&path).map_err(|e| error(None, e.to_string()))?;
+    let entries = toml::parse(&source).map_err(|e| error(Some(e.position), e.message))?;
+    let mut manifest = Manifest::default();
+
+    for Entry {
+        key,
+        value_position,
+        value,
+        ..
+    } in entries
+    {
+        let key: Vec<_> = key.iter().map(String::as_str).collect();
+
+        match (key.as_slice(), value) {
+            (["package", "name"], Value::String(name)) => manifest.package = Some(name),
+            (["package", ..], _) => {
+                manifest.package.get_or_insert_with(String::new);
+            }
+            (["workspace", list @ ("members" | "exclude")], value) => {
+                let Value::Array(values) = value else {
+                    let message = format!("expected an array of paths for `workspace.{list}`");
+                    return Err(error(Some(value_position), message));
+                };
+
+                let mut paths = Vec::new();
+                for (position, value) in values {
+                    let Value::String(path) = value else {
+                        let message = format!("expected a path, found {}", value.kind());
+                        return Err(error(Some(position), message));
+                    };
+                    paths.push(path);
+                }
+
+                let (members, exclude) = manifest.workspace.get_or_insert_with(Default::default);
+                match *list {
+                    "members" => *members = paths,
+                    _ => *exclude = paths,
+                }
+            }
+            (["workspace", ..], _) => {
+                manifest.workspace.get_or_insert_with(Default::default);
+            }
+            _ => (),
+        }
+    }
+
+    // A package without a name is invalid for Cargo, it is named after its directory.
+    if manifest.package.as_deref() == Some("") {
+        let name = manifest_dir.file_name().unwrap_or_default();
+        manifest.package = Some(name.to_string_lossy().into_owned());
+    }
+
+    Ok(manifest)
+}
+
+/// Returns the directories matching the pattern of a member, relative to `root`, in
+/// which `*` stands for any sequence of characters in a directory name.
+fn expand(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![root.to_path_buf()];
+
+    for component in pattern.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if !component.contains('*') {
+            dirs = dirs.into_iter().map(|dir| dir.join(component)).collect();
+            continue;
+        }
+
+        let mut matched = Vec::new();
+        for dir in dirs {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name();
+                if entry.path().is_dir() && matches(component, &name.to_string_lossy()) {
+                    matched.push(entry.path());
+                }
+            }
+        }
+        dirs = matched;
+    }
+
+    dirs
+}
+
+/// Returns the packages of the workspace whose root manifest is in `root`, sorted by
+/// name.
+fn members(root: &Path, manifest: &Manifest) -> Result<Vec<Package>, Error> {
+    let mut packages = Vec::new();
+    if let Some(name) = &manifest.package {
+        packages.push(Package {
+            name: name.clone(),
+            manifest_dir: root.to_path_buf(),
+        });
+    }
+
+    let (patterns, exclude) = manifest.workspace.clone().unwrap_or_default();
+    let exclude: Vec<_> = exclude.iter().map(|path| root.join(path)).collect();
+
+    for pattern in &patterns {
+        for dir in expand(root, pattern) {
+            let is_excluded = exclude.iter().any(|excluded| dir.starts_with(excluded));
+            let is_known = packages.iter().any(|package| package.manifest_dir == dir);
+            if is_excluded || is_known || !dir.join("Cargo.toml").is_file() {
+                continue;
+            }
+
+            let Some(name) = read(&dir)?.package else {
+                return Err(Error {
+                    origin: dir.join("Cargo.toml").display().to_string(),
+                    position: None,
+                    message: "a member of a workspace must have a `[package]` table".to_string(),
+                });
+            };
+            packages.push(Package {
+                name,
+                manifest_dir: dir,
+            });
+        }
+    }
+
+    packages.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(packages)
+}
+
+/// Returns the root of the workspace the package in `manifest_dir` is a member of,
+/// looked for in its parents, with the packages of the workspace.
+fn containing(manifest_dir: &Path) -> Result<Option<(PathBuf, Vec<Package>)>, Error> {
+    // The parents are only found from an absolute path.
+    let dir = fs::canonicalize(manifest_dir).unwrap_or_else(|_| manifest_dir.to_path_buf());
+
+    for root in dir.ancestors().skip(1) {
+        if !root.join("Cargo.toml").is_file() {
+            continue;
+        }
+
+        let manifest = read(root)?;
+        if manifest.workspace.is_none() {
+            continue;
+        }
+
+        let packages = members(root, &manifest)?;
+        if packages.iter().any(|package| package.manifest_dir == dir) {
+            return Ok(Some((root.to_path_buf(), packages)));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the directory of the root manifest of the workspace the package in
+/// `manifest_dir` belongs to, which is `manifest_dir` itself for the root package of a
+/// workspace, or `None` if the package is not part of a workspace.
+pub(crate) fn workspace_root(manifest_dir: &Path) -> Result<Option<PathBuf>, Error> {
+    if read(manifest_dir)?.workspace.is_some() {
+        return Ok(Some(manifest_dir.to_path_buf()));
+    }
+    Ok(containing(manifest_dir)?.map(|(root, _)| root))
+}
+
+/// Returns the packages to work on for the manifest in `manifest_dir`, as Cargo
+/// chooses them: its own package, or all the members of its workspace if it is a
+/// virtual manifest, or if `workspace` is set, as with `cargo build --workspace`.
+///
+/// The members are found from the `members` and `exclude` keys of the `[workspace]`
+/// table of the root manifest, looked for in the parents of `manifest_dir`.
+///
+/// ```
+/// # let dir = std::env::temp_dir().join("fully_pub_core_workspace_doc");
+/// # for member in ["crates/app", "crates/models", "crates/old"] {
+/// #     std::fs::create_dir_all(dir.join(member)).unwrap();
+/// # }
+/// std::fs::write(
+///     dir.join("Cargo.toml"),
+///     "[workspace]\nmembers = [\"crates/*\"]\nexclude = [\"crates/old\"]\n",
+/// )
+/// .unwrap();
+/// for name in ["app", "models", "old"] {
+///     let manifest = format!("[package]\nname = \"{name}\"\n");
+///     std::fs::write(dir.join("crates").join(name).join("Cargo.toml"), manifest).unwrap();
+/// }
+///
+/// let names = |packages: Vec<fully_pub_core::Package>| {
+///     packages.into_iter().map(|package| package.name).collect::<Vec<_>>()
+/// };
+///
+/// let app = dir.join("crates/app");
+/// assert_eq!(names(fully_pub_core::packages(&dir, false).unwrap()), ["app", "models"]);
+/// assert_eq!(names(fully_pub_core::packages(&app, false).unwrap()), ["app"]);
+/// assert_eq!(names(fully_pub_core::packages(&app, true).unwrap()), ["app", "models"]);
+/// ```
+pub fn packages(manifest_dir: &Path, workspace: bool) -> Result<Vec<Package>, Error> {
+    let manifest = read(manifest_dir)?;
+
+    if manifest.workspace.is_some() && (workspace || manifest.package.is_none()) {
+        return members(manifest_dir, &manifest);
+    }
+
+    let Some(name) = manifest.package else {
+        return Err(Error {
+            origin: manifest_dir.join("Cargo.toml").display().to_string(),
+            position: None,
+            message: "expected a `[package]` or a `[workspace]` table".to_string(),
+        });
+    };
+    let package = Package {
+        name,
+        manifest_dir: manifest_dir.to_path_buf(),
+    };
+
+    if workspace && manifest.workspace.is_none() {
+        if let Some((_, packages)) = containing(manifest_dir)? {
+            return Ok(packages);
+        }
+    }
+
+    // A package outside of any workspace is a workspace of its own.
+    Ok(vec![package])
+}
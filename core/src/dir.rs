@@ -0,0 +1,175 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::*;
+
+use crate::explore::matches;
+use crate::{local_file, Config, Tracker, CRATE_NAME, DISABLE_VAR};
+
+/// Arguments passed to the `fully_pub::dir!(...)` macro.
+pub struct DirArgs {
+    /// The directory, relative to the manifest of the crate.
+    path: LitStr,
+    /// The modules not to declare.
+    exclude: Vec<Ident>,
+}
+
+impl Parse for DirArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let path = input.parse()?;
+        let mut exclude = Vec::new();
+
+        if !input.is_empty() {
+            input.parse::<Comma>()?;
+        }
+
+        if !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            if ident != "exclude" {
+                bail!(&ident, "invalid argument to `{CRATE_NAME}::dir` macro");
+            }
+
+            let content;
+            parenthesized!(content in input);
+            exclude.extend(Punctuated::<Ident, Comma>::parse_terminated(&content)?);
+            input.parse::<Option<Comma>>()?;
+        }
+
+        Ok(DirArgs { path, exclude })
+    }
+}
+
+/// Returns the name of the module the directory entry would be, if any.
+///
+/// Modules are either `.rs` files, other than `mod.rs`, or directories with a
+/// `mod.rs` file.
+fn module_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+
+    if name.starts_with('.') {
+        return None;
+    }
+
+    if path.is_dir() {
+        return path.join("mod.rs").is_file().then(|| name.to_string());
+    }
+
+    match name.strip_suffix(".rs") {
+        Some("mod") | None => None,
+        Some(stem) => Some(stem.to_string()),
+    }
+}
+
+/// Returns the identifier of the module, raw if its name is a keyword, as for a
+/// `type.rs` file.
+fn module_ident(name: &str) -> Option<Ident> {
+    parse_str(name)
+        .or_else(|_| parse_str(&format!("r#{name}")))
+        .ok()
+}
+
+/// Declares a module for each file of the directory, `pub` unless [`DISABLE_VAR`] is
+/// set, or unless its name matches the `exclude_names` of the configuration, which
+/// leaves it private as the macro does.
+///
+/// ```
+/// use fully_pub_core::{dir, DirArgs, Tracker};
+///
+/// # let manifest_dir = std::env::temp_dir().join("fully_pub_core_dir_doc");
+/// # std::fs::create_dir_all(manifest_dir.join("src/handlers")).unwrap();
+/// std::fs::write(
+///     manifest_dir.join("Cargo.toml"),
+///     "[package]\nname = \"app\"\n\n[package.metadata.fully_pub]\nexclude_names = [\"_*\"]\n",
+/// )
+/// .unwrap();
+/// for file in ["type.rs", "login.rs", "_internal.rs", "legacy.rs"] {
+///     std::fs::write(manifest_dir.join("src/handlers").join(file), "").unwrap();
+/// }
+/// std::env::set_var("CARGO_MANIFEST_DIR", &manifest_dir);
+///
+/// let args: DirArgs = syn::parse_str("\"src/handlers\", exclude(legacy)").unwrap();
+/// let modules = dir(&args, &mut Tracker::default()).unwrap();
+///
+/// assert_eq!(
+///     modules.to_string(),
+///     "mod _internal ; pub mod login ; pub mod r#type ;",
+/// );
+/// ```
+pub fn dir(args: &DirArgs, tracker: &mut Tracker) -> Result<TokenStream2> {
+    let disabled = tracker.var(DISABLE_VAR).is_some();
+
+    // An invalid configuration is reported once, the modules are still declared with
+    // the default configuration to avoid follow-up errors.
+    let (exclude_names, config_error) = match Config::current(tracker) {
+        Ok(config) => {
+            let config = match local_file(Span::call_site()) {
+                Some(file) => config.in_file(&file),
+                None => config.clone(),
+            };
+            (config.defaults().exclude_names.unwrap_or_default(), None)
+        }
+        Err(e) => (Vec::new(), Some(e.to_compile_error())),
+    };
+
+    let Some(manifest_dir) = env::var_os("CARGO_MANIFEST_DIR") else {
+        bail!(
+            &args.path,
+            "`CARGO_MANIFEST_DIR` is not set, is the crate built by cargo?"
+        );
+    };
+
+    let dir = Path::new(&manifest_dir).join(args.path.value());
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) => bail!(&args.path, "failed to read `{}`: {e}", dir.display()),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(e) => bail!(&args.path, "failed to read `{}`: {e}", dir.display()),
+        };
+
+        names.extend(module_name(&path));
+    }
+
+    names.sort();
+    names.dedup();
+
+    for excluded in &args.exclude {
+        if !names.iter().any(|name| excluded.unraw() == name) {
+            bail!(excluded, "no module `{excluded}` in `{}`", dir.display());
+        }
+    }
+
+    let mut modules = Vec::new();
+    for name in names {
+        if args.exclude.iter().any(|excluded| excluded.unraw() == name) {
+            continue;
+        }
+
+        let Some(ident) = module_ident(&name) else {
+            bail!(&args.path, "`{name}` is not a valid module name");
+        };
+
+        let is_excluded = exclude_names.iter().any(|pattern| matches(pattern, &name));
+        let vis = match disabled || is_excluded {
+            true => quote!(),
+            false => quote!(pub),
+        };
+        modules.push(quote!(#vis mod #ident;));
+    }
+
+    Ok(quote! {
+        #(#modules)*
+        #config_error
+    })
+}
@@ -0,0 +1,595 @@
+use proc_macro2::{Group, Span, TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::parse::{ParseStream, Parser};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::token::Pub;
+use syn::*;
+
+use crate::inventory::Inventory;
+use crate::visit::{Decision, Member, MemberKind, VisibilityPolicy, Visitor};
+use crate::{Args, CRATE_NAME};
+
+/// What a `#[fully_pub(exclude)]` attribute leaves untouched.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Exclusion {
+    /// The member and its content, with `#[fully_pub(exclude)]`.
+    All,
+    /// The visibility of the member, but not its content, with
+    /// `#[fully_pub(exclude(self))]`.
+    Itself,
+    /// The content of the member, but not its visibility, with
+    /// `#[fully_pub(exclude(members))]`.
+    Members,
+}
+
+/// Parses the arguments of a helper attribute: `exclude`, `exclude(self)` or
+/// `exclude(members)`.
+fn parse_exclusion(input: ParseStream) -> Result<Exclusion> {
+    let arg: Ident = input.parse()?;
+
+    if arg != "exclude" {
+        bail!(&arg, "unknown {CRATE_NAME} attribute `{arg}`");
+    }
+
+    if input.is_empty() {
+        return Ok(Exclusion::All);
+    }
+
+    let content;
+    parenthesized!(content in input);
+    let target = content.call(Ident::parse_any)?;
+
+    let exclusion = match &*target.to_string() {
+        "self" => Exclusion::Itself,
+        "members" => Exclusion::Members,
+        _ => bail!(&target, "expected `self` or `members`, found `{target}`"),
+    };
+
+    if !content.is_empty() || !input.is_empty() {
+        bail!(&target, "`exclude` takes either `self` or `members`");
+    }
+
+    Ok(exclusion)
+}
+
+/// Returns what the `#[fully_pub(exclude)]` attribute of the attributes list excludes,
+/// if there is one, with the attribute once removed from the list.
+///
+/// The list is scanned once and only changed if the attribute is found, since members
+/// often carry many other attributes.
+///
+/// If the attribute is ill-formatted or present more than once, returns an `Err`.
+fn is_exclude(attrs: &mut Vec<Attribute>) -> Result<Option<(Exclusion, Attribute)>> {
+    let mut found = None;
+
+    for (index, attr) in attrs.iter().enumerate() {
+        if !attr.path().is_ident(CRATE_NAME) {
+            continue;
+        }
+
+        let exclusion = attr.parse_args_with(parse_exclusion)?;
+
+        if found.is_some() {
+            bail!(attr, "duplicate {CRATE_NAME} attribute `exclude`");
+        }
+
+        found = Some((index, exclusion));
+    }
+
+    Ok(found.map(|(index, exclusion)| (exclusion, attrs.remove(index))))
+}
+
+/// Returns `true` if the type is one of the marker types, whatever its path and
+/// generics, like `std::marker::PhantomData<T>`.
+pub(crate) fn is_marker(ty: Option<&Type>, marker_types: &[String]) -> bool {
+    match ty {
+        Some(Type::Path(TypePath { qself: None, path })) if !marker_types.is_empty() => {
+            path.segments.last().is_some_and(|last| {
+                let name = last.ident.unraw().to_string();
+                marker_types.contains(&name)
+            })
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if the visibility leaves the member private, as `pub(self)` does.
+fn is_private(vis: &Visibility) -> bool {
+    match vis {
+        Visibility::Inherited => true,
+        Visibility::Restricted(VisRestricted { path, .. }) => path.is_ident("self"),
+        Visibility::Public(_) => false,
+    }
+}
+
+/// An attribute, with the condition of the `cfg_attr` applying it, if any.
+type Applied = (Option<TokenStream2>, Meta);
+
+/// Pushes the attribute, or the ones it applies if it is a `cfg_attr`, with the
+/// condition they are applied under, `None` if they always are.
+fn push_applied(condition: Option<TokenStream2>, meta: Meta, applied: &mut Vec<Applied>) {
+    let Meta::List(list) = &meta else {
+        applied.push((condition, meta));
+        return;
+    };
+    if !list.path.is_ident("cfg_attr") {
+        applied.push((condition, meta));
+        return;
+    }
+
+    let parsed = list.parse_args_with(|input: ParseStream| {
+        let predicate: Meta = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let metas = Punctuated::<Meta, Token![,]>::parse_terminated(input)?;
+        Ok((predicate, metas))
+    });
+    if let Ok((predicate, metas)) = parsed {
+        let condition = match condition {
+            Some(outer) => quote!(all(#outer, #predicate)),
+            None => quote!(#predicate),
+        };
+        for meta in metas {
+            push_applied(Some(condition.clone()), meta, applied);
+        }
+    }
+}
+
+/// Returns the attributes, the ones applied by `cfg_attr` included.
+fn applied(attrs: &[Attribute]) -> Vec<Applied> {
+    let mut applied = Vec::new();
+    for attr in attrs {
+        push_applied(None, attr.meta.clone(), &mut applied);
+    }
+    applied
+}
+
+/// Returns whether the attributes derive `Serialize` or `Deserialize`, whatever their
+/// path, like `serde::Serialize`: `Some` with the condition they do under, like the
+/// `feature = "serde"` of `#[cfg_attr(feature = "serde", derive(Serialize))]`, or
+/// with `None` if they always do.
+fn derives_serde(attrs: &[Attribute]) -> Option<Option<TokenStream2>> {
+    let conditions: Vec<_> = applied(attrs)
+        .into_iter()
+        .filter(|(_, meta)| match meta {
+            Meta::List(list) if list.path.is_ident("derive") => list
+                .parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+                .is_ok_and(|paths| {
+                    paths.iter().any(|path| {
+                        path.segments.last().is_some_and(|last| {
+                            last.ident == "Serialize" || last.ident == "Deserialize"
+                        })
+                    })
+                }),
+            _ => false,
+        })
+        .map(|(condition, _)| condition)
+        .collect();
+
+    if conditions.is_empty() {
+        return None;
+    }
+    let Some(conditions) = conditions.into_iter().collect::<Option<Vec<_>>>() else {
+        return Some(None);
+    };
+    Some(Some(match conditions.as_slice() {
+        [condition] => condition.clone(),
+        _ => quote!(any(#(#conditions),*)),
+    }))
+}
+
+/// Adds the `serde` attribute `skip`, like `#[serde(skip_serializing)]`, to the
+/// attributes, under the condition the traits are derived under, unless they already
+/// have a `serde` attribute skipping the field in the same direction.
+fn add_serde_skip(attrs: &mut Vec<Attribute>, skip: &str, condition: Option<&TokenStream2>) {
+    let skips: &[&str] = match skip {
+        "skip_serializing" => &["skip", "skip_serializing"],
+        "skip_deserializing" => &["skip", "skip_deserializing"],
+        _ => &["skip"],
+    };
+    let same_condition = |other: &Option<TokenStream2>| match other {
+        Some(other) => condition.is_some_and(|c| c.to_string() == other.to_string()),
+        None => true,
+    };
+
+    let is_skipped = applied(attrs).iter().any(|(other, meta)| match meta {
+        Meta::List(list) if list.path.is_ident("serde") && same_condition(other) => list
+            .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .is_ok_and(|metas| {
+                metas.iter().any(|meta| {
+                    matches!(meta, Meta::Path(path) if skips.iter().any(|skip| path.is_ident(skip)))
+                })
+            }),
+        _ => false,
+    });
+
+    if !is_skipped {
+        let skip = Ident::new(skip, Span::call_site());
+        attrs.push(match condition {
+            Some(condition) => parse_quote!(#[cfg_attr(#condition, serde(#skip))]),
+            None => parse_quote!(#[serde(#skip)]),
+        });
+    }
+}
+
+/// What the explorer does to the visibilities it meets.
+pub(crate) enum Mode {
+    /// Makes them public.
+    Publish,
+    /// Records them, leaving them untouched.
+    Inventory(Inventory),
+    /// Collects the spans of the visibilities already written as they would be set.
+    Lint(Vec<Span>),
+    /// Leaves them untouched, only removing the helper attributes.
+    Disabled,
+}
+
+/// Returns `true` if the name matches the pattern, in which `*` stands for any
+/// sequence of characters.
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => name.strip_prefix(prefix).is_some_and(|name| {
+            (0..=name.len())
+                .filter(|&i| name.is_char_boundary(i))
+                .any(|i| matches(rest, &name[i..]))
+        }),
+    }
+}
+
+/// Returns the tokens with all their spans set to the call site.
+fn respan(stream: TokenStream2) -> TokenStream2 {
+    stream
+        .into_iter()
+        .map(|tree| match tree {
+            TokenTree::Group(group) => {
+                let mut respanned = Group::new(group.delimiter(), respan(group.stream()));
+                respanned.set_span(Span::call_site());
+                TokenTree::Group(respanned)
+            }
+            mut tree => {
+                tree.set_span(Span::call_site());
+                tree
+            }
+        })
+        .collect()
+}
+
+/// Returns `true` if the two visibilities are written the same, without formatting
+/// them.
+fn same_visibility(a: &Visibility, b: &Visibility) -> bool {
+    match (a, b) {
+        (Visibility::Public(_), Visibility::Public(_))
+        | (Visibility::Inherited, Visibility::Inherited) => true,
+        (Visibility::Restricted(a), Visibility::Restricted(b)) => {
+            a.in_token.is_some() == b.in_token.is_some()
+                && a.path.leading_colon.is_some() == b.path.leading_colon.is_some()
+                && a.path.segments.len() == b.path.segments.len()
+                && a.path
+                    .segments
+                    .iter()
+                    .zip(&b.path.segments)
+                    .all(|(a, b)| a.ident == b.ident)
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if the attribute is `#![fully_pub::defaults(...)]`, the defaults of
+/// the module it is in.
+fn is_defaults(attr: &Attribute) -> bool {
+    let segments: Vec<_> = attr.path().segments.iter().map(|s| &s.ident).collect();
+
+    matches!(attr.style, AttrStyle::Inner(_))
+        && matches!(segments.as_slice(), [krate, name] if *krate == CRATE_NAME && *name == "defaults")
+}
+
+/// The name of the private field `non_constructible` adds to the structs.
+pub(crate) const PRIVATE_FIELD: &str = "_priv";
+
+/// The arguments of the explorer a module can change with `#![fully_pub::defaults]`,
+/// as they were before it.
+struct Scope {
+    vis: Visibility,
+    exclude_names: Vec<String>,
+    exclude_impls_of: Vec<String>,
+    marker_types: Vec<String>,
+}
+
+/// The policy of the macro: gives the visibility of the arguments to the members
+/// that are not excluded, or takes an inventory of them.
+pub(crate) struct Explorer {
+    /// The visibility given to the members.
+    pub(crate) vis: Visibility,
+    /// The patterns of the names of the members to leave untouched.
+    pub(crate) exclude_names: Vec<String>,
+    /// The names of the types whose inherent `impl` blocks are left untouched.
+    pub(crate) exclude_impls_of: Vec<String>,
+    /// The names of the marker types, whose fields are left untouched.
+    pub(crate) marker_types: Vec<String>,
+    /// Whether the structs whose fields are all given a visibility get a private
+    /// [`PRIVATE_FIELD`].
+    pub(crate) non_constructible: bool,
+    /// The `serde` attribute added to the excluded fields of the structs deriving its
+    /// traits, if any.
+    pub(crate) sync_serde_skip: Option<String>,
+    /// Whether the last item met derives a trait of `serde`, with the condition it
+    /// does under, its fields being visited right after it. Only looked for with
+    /// `sync_serde_skip` or `non_constructible`.
+    derives_serde: Option<Option<TokenStream2>>,
+    pub(crate) mode: Mode,
+    /// Whether a visibility or a helper attribute was changed.
+    pub(crate) changed: bool,
+    /// The arguments to restore when leaving each of the modules being visited, if
+    /// they changed them.
+    scopes: Vec<Option<Scope>>,
+}
+
+impl Explorer {
+    /// Returns a visitor following the arguments, once the configuration has been
+    /// applied to them.
+    pub(crate) fn new(args: &Args, mode: Mode) -> Visitor<Self> {
+        let explorer = Explorer {
+            vis: match &args.vis {
+                // The visibility may be copied from some other place in the
+                // source, which tools rewriting it must not mistake it for.
+                Some(vis) => parse2(respan(quote!(#vis))).unwrap_or(Visibility::Inherited),
+                None => Visibility::Public(Pub::default()),
+            },
+            exclude_names: args.exclude_names.clone().unwrap_or_default(),
+            exclude_impls_of: args.exclude_impls_of.clone(),
+            marker_types: args.marker_types(),
+            non_constructible: args.non_constructible,
+            sync_serde_skip: args.sync_serde_skip.clone(),
+            derives_serde: None,
+            mode,
+            changed: false,
+            scopes: Vec::new(),
+        };
+
+        Visitor::new(explorer).recursive(args.recursive.unwrap_or(false))
+    }
+
+    /// Returns what is excluded of the member, either by a `#[fully_pub(exclude)]`
+    /// attribute, which is then removed, or by its name.
+    fn is_excluded(
+        &mut self,
+        attrs: &mut Vec<Attribute>,
+        ident: Option<&Ident>,
+    ) -> Result<Option<Exclusion>> {
+        // Formatting the name is only worth it if there are patterns to match.
+        let is_named = !self.exclude_names.is_empty()
+            && ident.is_some_and(|ident| {
+                let name = ident.unraw().to_string();
+                self.exclude_names
+                    .iter()
+                    .any(|pattern| matches(pattern, &name))
+            });
+
+        let attributed = is_exclude(attrs)?.map(|(exclusion, _)| exclusion);
+        self.changed |= attributed.is_some();
+
+        Ok(match is_named {
+            true => Some(Exclusion::All),
+            false => attributed,
+        })
+    }
+}
+
+impl VisibilityPolicy for Explorer {
+    /// Gives the visibility of the arguments to the member, or records it if taking an
+    /// inventory.
+    fn decide(&mut self, member: Member<'_>) -> Result<Decision> {
+        if member.kind == MemberKind::Item {
+            self.derives_serde = match self.sync_serde_skip.is_some() || self.non_constructible {
+                true => derives_serde(member.attrs),
+                false => None,
+            };
+        }
+
+        let exclusion = self.is_excluded(member.attrs, member.name)?;
+        if let (Some(Exclusion::All | Exclusion::Itself), Some(skip), Some(condition)) =
+            (&exclusion, &self.sync_serde_skip, &self.derives_serde)
+        {
+            if member.kind == MemberKind::Field {
+                add_serde_skip(member.attrs, skip, condition.as_ref());
+                self.changed = true;
+            }
+        }
+
+        let exclusion = match exclusion {
+            None if is_marker(member.ty, &self.marker_types) => Some(Exclusion::All),
+            exclusion => exclusion,
+        };
+        match exclusion {
+            Some(Exclusion::All) => return Ok(Decision::Skip),
+            Some(Exclusion::Itself) => return Ok(Decision::Keep),
+            Some(Exclusion::Members) | None => (),
+        }
+
+        // The decision for the member, whose content is left untouched if excluded.
+        let shallow = |decision| match exclusion {
+            Some(Exclusion::Members) => match decision {
+                Decision::Replace(vis) => Decision::ReplaceOnly(vis),
+                _ => Decision::Skip,
+            },
+            _ => decision,
+        };
+
+        Ok(shallow(match &mut self.mode {
+            Mode::Publish if same_visibility(member.vis, &self.vis) => Decision::Keep,
+            Mode::Publish => {
+                self.changed = true;
+                Decision::Replace(self.vis.clone())
+            }
+            Mode::Inventory(inventory) => {
+                inventory.record(member.vis);
+                Decision::Keep
+            }
+            Mode::Lint(spans) => {
+                if same_visibility(member.vis, &self.vis) {
+                    spans.push(member.vis.span());
+                }
+                Decision::Keep
+            }
+            Mode::Disabled => Decision::Keep,
+        }))
+    }
+
+    /// Skips the blocks with a `#[fully_pub(exclude)]` or `#[fully_pub(exclude(members))]`
+    /// attribute. Blocks have no visibility, so `#[fully_pub(exclude(self))]` is an error.
+    fn enter_block(&mut self, attrs: &mut Vec<Attribute>) -> Result<bool> {
+        let attributed = is_exclude(attrs)?;
+        self.changed |= attributed.is_some();
+
+        match attributed {
+            Some((Exclusion::Itself, attr)) => Err(Error::new_spanned(
+                attr,
+                "`exclude(self)` can't be used on a block, which has no visibility",
+            )),
+            Some(_) => Ok(false),
+            None => Ok(true),
+        }
+    }
+
+    /// Skips the blocks of the types excluded by `exclude_impls_of`, whatever their
+    /// path and generics, like `impl<T> crate::Scratch<T>`.
+    fn enter_impl(&mut self, attrs: &mut Vec<Attribute>, self_ty: &Type) -> Result<bool> {
+        if !self.enter_block(attrs)? {
+            return Ok(false);
+        }
+
+        let is_excluded = match self_ty {
+            Type::Path(TypePath { qself: None, path }) => {
+                path.segments.last().is_some_and(|last| {
+                    let name = last.ident.unraw().to_string();
+                    self.exclude_impls_of.contains(&name)
+                })
+            }
+            _ => false,
+        };
+        Ok(!is_excluded)
+    }
+
+    /// Applies the `#![fully_pub::defaults(...)]` attribute of the module to its
+    /// items, removing it.
+    fn enter_mod(&mut self, attrs: &mut Vec<Attribute>) -> Result<()> {
+        let mut found = None;
+        let mut index = 0;
+
+        while index < attrs.len() {
+            if !is_defaults(&attrs[index]) {
+                index += 1;
+                continue;
+            }
+
+            let attr = attrs.remove(index);
+            if found.is_some() {
+                bail!(attr, "duplicate `{CRATE_NAME}::defaults` attribute");
+            }
+            found = Some(attr);
+        }
+
+        let Some(attr) = found else {
+            self.scopes.push(None);
+            return Ok(());
+        };
+        self.changed = true;
+
+        let args: Args = attr.parse_args()?;
+        let unsupported = [
+            ("recursive", args.recursive.is_some()),
+            ("inventory", args.inventory),
+            ("group", args.group.is_some()),
+            ("exclude_set", args.exclude_set.is_some()),
+            ("exact", args.exact.is_some()),
+            ("non_constructible", args.non_constructible),
+            ("sync_serde_skip", args.sync_serde_skip.is_some()),
+        ];
+        if let Some((name, _)) = unsupported.iter().find(|(_, given)| *given) {
+            bail!(
+                attr,
+                "`{name}` can't be set by `{CRATE_NAME}::defaults`, which only sets `vis`, \
+                `exclude_names`, `exclude_impls_of`, `markers` and `marker_types`"
+            );
+        }
+
+        self.scopes.push(Some(Scope {
+            vis: self.vis.clone(),
+            exclude_names: self.exclude_names.clone(),
+            exclude_impls_of: self.exclude_impls_of.clone(),
+            marker_types: self.marker_types.clone(),
+        }));
+
+        if let Some(vis) = &args.vis {
+            self.vis = parse2(respan(quote!(#vis))).unwrap_or(Visibility::Inherited);
+        }
+        if let Some(names) = &args.exclude_names {
+            self.exclude_names = names.clone();
+        }
+        if !args.exclude_impls_of.is_empty() {
+            self.exclude_impls_of = args.exclude_impls_of.clone();
+        }
+        if args.markers.is_some() || args.marker_types.is_some() {
+            self.marker_types = args.marker_types();
+        }
+
+        Ok(())
+    }
+
+    /// Adds a private [`PRIVATE_FIELD`] of type `()` to the struct with named fields if
+    /// `non_constructible` is set and none of the fields is left private, for the
+    /// struct not to be built with a struct expression out of its module. The field is
+    /// hidden from the documentation, and skipped by `serde` if the struct derives its
+    /// traits, under the same condition.
+    ///
+    /// Tuple structs are left as they are: a positional field would break the uses of
+    /// their constructor and their patterns.
+    fn leave_struct(&mut self, ident: &Ident, fields: &mut Fields) -> Result<()> {
+        let Fields::Named(FieldsNamed { named, .. }) = fields else {
+            return Ok(());
+        };
+        let is_untouched = !self.non_constructible
+            || !matches!(self.mode, Mode::Publish)
+            || named.is_empty()
+            || named.iter().any(|field| is_private(&field.vis));
+        if is_untouched {
+            return Ok(());
+        }
+
+        let name = Ident::new(PRIVATE_FIELD, Span::call_site());
+        if let Some(field) = named
+            .iter()
+            .find(|field| field.ident.as_ref() == Some(&name))
+        {
+            bail!(
+                &field.ident,
+                "`non_constructible` adds a private field `{PRIVATE_FIELD}` to `{ident}`, \
+                which already has one"
+            );
+        }
+
+        if !named.empty_or_trailing() {
+            named.push_punct(Default::default());
+        }
+        let mut field = Field::parse_named.parse2(quote!(#[doc(hidden)] #name: ()))?;
+        if let Some(condition) = &self.derives_serde {
+            add_serde_skip(&mut field.attrs, "skip", condition.as_ref());
+        }
+        named.push(field);
+        self.changed = true;
+
+        Ok(())
+    }
+
+    /// Restores the arguments the module changed.
+    fn leave_mod(&mut self) {
+        if let Some(Some(scope)) = self.scopes.pop() {
+            self.vis = scope.vis;
+            self.exclude_names = scope.exclude_names;
+            self.exclude_impls_of = scope.exclude_impls_of;
+            self.marker_types = scope.marker_types;
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use syn::*;
+
+/// Counts of the visibilities met during an inventory.
+#[derive(Default)]
+pub(crate) struct Inventory {
+    public: usize,
+    crate_: usize,
+    restricted: usize,
+    private: usize,
+}
+
+impl Inventory {
+    /// Records the visibility of one more member.
+    pub(crate) fn record(&mut self, vis: &Visibility) {
+        match vis {
+            Visibility::Public(_) => self.public += 1,
+            Visibility::Restricted(VisRestricted { path, in_token, .. })
+                if in_token.is_none() && path.is_ident("crate") =>
+            {
+                self.crate_ += 1
+            }
+            Visibility::Restricted(_) => self.restricted += 1,
+            Visibility::Inherited => self.private += 1,
+        }
+    }
+
+    /// Formats the inventory as a human readable report.
+    pub(crate) fn report(&self, name: &str) -> String {
+        let Self {
+            public,
+            crate_,
+            restricted,
+            private,
+        } = self;
+        let total = public + crate_ + restricted + private;
+        let members = if total == 1 { "member" } else { "members" };
+
+        format!(
+            "inventory of {name}: {total} {members}, {public} `pub`, {crate_} `pub(crate)`, \
+            {restricted} otherwise restricted, {private} private"
+        )
+    }
+}
@@ -0,0 +1,725 @@
+//! Implementation of the [`fully_pub`](https://docs.rs/fully_pub) attribute macro.
+//!
+//! This crate is shared by the procedural macro itself and by the `cargo fully-pub`
+//! tool, which needs to know what the macro does to a crate without compiling it.
+//! Other procedural macros can apply the attribute to the items they generate with
+//! [`fully_pub()`], and the inverse attribute, `fully_private`, with
+//! [`fully_private()`].
+//!
+//! Tools that work on source text can use [`publish`], which applies the exact same
+//! transformation as the macro outside of a procedural macro context, with the
+//! `codegen` feature:
+//!
+#![cfg_attr(feature = "codegen", doc = "```")]
+#![cfg_attr(not(feature = "codegen"), doc = "```ignore")]
+//! use fully_pub_core::{publish, Options};
+//!
+//! let source = "#[fully_pub::fully_pub]\nstruct Meters(f64);\n";
+//!
+//! assert_eq!(
+//!     publish(source, &Options::default()).unwrap(),
+//!     "pub struct Meters(pub f64);\n",
+//! );
+//! ```
+
+extern crate proc_macro;
+
+use std::iter;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use proc_macro2::{Delimiter, Group, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::spanned::Spanned;
+use syn::*;
+
+macro_rules! bail {
+    ($span: expr, $($arg:tt)*) => {
+        return Err(syn::Error::new_spanned($span, format!($($arg)*)))
+    }
+}
+
+mod args;
+mod bounds;
+mod cache;
+mod config;
+#[cfg(feature = "files")]
+mod dir;
+mod explore;
+mod inventory;
+mod private;
+mod profile;
+#[cfg(feature = "codegen")]
+pub mod rewrite;
+mod scan;
+pub mod source;
+mod splice;
+mod track;
+pub mod visit;
+
+pub use args::Args;
+pub use config::{
+    config, exclude_set, group, Config, ConfigArgs, Error as ConfigError, ExcludeSetArgs, GroupArgs,
+};
+#[cfg(feature = "files")]
+pub use config::{packages, Package};
+#[cfg(feature = "files")]
+pub use dir::{dir, DirArgs};
+pub use private::{fully_private, privatize, PrivateArgs};
+pub use scan::scan;
+pub use splice::splice;
+pub use track::Tracker;
+
+use cache::Cache;
+use config::{Level, Lints};
+use explore::{Explorer, Mode};
+use inventory::Inventory;
+use profile::Profile;
+
+/// The name of the macro, and of its helper attribute.
+pub(crate) const CRATE_NAME: &str = "fully_pub";
+
+/// The environment variable that disables the macro when set at build time.
+pub const DISABLE_VAR: &str = "FULLY_PUB_DISABLE";
+
+/// The environment variable that overrides the visibility the configuration gives to
+/// the members, when set at build time.
+pub const VIS_VAR: &str = "FULLY_PUB_VIS";
+
+/// The environment variable that, when set at build time to a number of milliseconds,
+/// makes the macro warn about the expansions taking longer than that.
+pub const TIME_BUDGET_VAR: &str = "FULLY_PUB_TIME_BUDGET";
+
+/// The environment variable that, when set at build time, makes the macro only change
+/// visibilities and report errors: its reports and warnings are skipped, for
+/// `cargo check` and the editors to get the items sooner.
+///
+#[cfg_attr(feature = "files", doc = "```")]
+#[cfg_attr(not(feature = "files"), doc = "```ignore")]
+/// # let manifest_dir = std::env::temp_dir().join("fully_pub_core_check_doc");
+/// # std::fs::create_dir_all(&manifest_dir).unwrap();
+/// std::fs::write(
+///     manifest_dir.join("Cargo.toml"),
+///     "[package]\nname = \"app\"\n\n\
+///     [package.metadata.fully_pub.lints]\nredundant_pub = \"deny\"\n",
+/// )
+/// .unwrap();
+/// std::env::set_var("CARGO_MANIFEST_DIR", &manifest_dir);
+/// std::env::set_var(fully_pub_core::CHECK_VAR, "1");
+///
+/// // The denied lint fails the check as it fails the build.
+/// let item = "struct Meters { pub value: f64 }".parse().unwrap();
+/// let error = fully_pub_core::fully_pub(Default::default(), item).unwrap_err();
+/// assert_eq!(
+///     error.to_string(),
+///     "redundant visibility, the macro already gives it to this member",
+/// );
+/// ```
+pub const CHECK_VAR: &str = "FULLY_PUB_CHECK";
+
+/// The environment variable set by the server of rust-analyzer expanding procedural
+/// macros, where the macro behaves as with [`CHECK_VAR`].
+const RUST_ANALYZER_VAR: &str = "RUST_ANALYZER_INTERNALS_DO_NOT_USE";
+
+/// Options of [`publish`].
+#[cfg(feature = "codegen")]
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct Options {
+    /// Whether every item of the source is made public, as if the whole source was
+    /// the content of a module annotated with the macro. When `false`, only the items
+    /// annotated in the source are expanded, as the compiler would. Defaults to `false`.
+    pub all: bool,
+    /// Whether the items of nested modules are made public too when `all` is set,
+    /// as with `#[fully_pub(recursive)]`. Defaults to `false`.
+    pub recursive: bool,
+    /// The configuration giving the defaults of the attributes. Defaults to the
+    /// default configuration, whatever the manifest of the crate says.
+    pub config: Config,
+}
+
+/// Applies the transformation of the macro to Rust source text, returning the
+/// rewritten source.
+///
+/// Only visibilities and the attributes of the macro are changed: comments and
+/// formatting are preserved. See [`rewrite`] for the other transformations the
+/// `cargo fully-pub` tool applies to sources.
+///
+/// ```
+/// use fully_pub_core::{publish, Options};
+///
+/// let mut options = Options::default();
+/// options.all = true;
+///
+/// assert_eq!(
+///     publish("fn id(x: u8) -> u8 { x }", &options).unwrap(),
+///     "pub fn id(x: u8) -> u8 { x }",
+/// );
+/// ```
+#[cfg(feature = "codegen")]
+pub fn publish(source: &str, options: &Options) -> Result<String> {
+    match options.all {
+        true => rewrite::publish_source(source, options.recursive, &options.config),
+        false => rewrite::expand_source(source, &options.config),
+    }
+}
+
+/// Applies the macro to the item, returning the tokens it expands to: this is what
+/// `#[fully_pub(...)]` does with the arguments `attr`, for other procedural macros to
+/// build on.
+///
+/// The configuration is the one of the crate being compiled, as for the attribute,
+/// and the rules of its modules follow the file the item is written in when it is
+/// known, which is the one invoking the macro for the items `macro_rules!` generate.
+/// When the configuration is invalid, its error is emitted alongside the expanded
+/// item, which is expanded with the default configuration.
+///
+/// It does not panic, whatever the tokens: invalid ones are reported as errors. The
+/// fuzz targets of the repository check it.
+///
+/// ```
+/// use fully_pub_core::fully_pub;
+/// use quote::quote;
+///
+/// let expanded = fully_pub(quote!(), quote!(struct Meters(f64);)).unwrap();
+/// assert!(expanded.to_string().starts_with("pub struct Meters (pub f64) ;"));
+///
+/// // Items with nothing to change are given back as they came.
+/// let item = quote!(pub struct Meters(pub f64););
+/// let expanded = fully_pub(quote!(), item.clone()).unwrap();
+/// assert!(expanded.to_string().starts_with(&item.to_string()));
+///
+/// assert!(fully_pub(quote!(unknown), quote!(struct Meters(f64);)).is_err());
+/// ```
+///
+/// The members of a recursive module it makes more visible than a trait of the module
+/// in their bounds are warned about, as the compiler would in the expanded code with
+/// its `private_bounds` lint, unless the configuration allows it:
+///
+/// ```
+/// use fully_pub_core::fully_pub;
+/// use quote::quote;
+///
+/// let item = quote! {
+///     mod codec {
+///         #[fully_pub(exclude)]
+///         trait Sealed {}
+///
+///         fn encode<T: Sealed>(value: T) {}
+///     }
+/// };
+/// let expanded = fully_pub(quote!(recursive), item).unwrap();
+/// assert!(expanded.to_string().contains("the trait `Sealed` bounds `encode`"));
+/// ```
+///
+/// Besides the visibilities it changes and its helper attributes, the item is given
+/// back with the tokens it came with, spans and spacing included, for the attributes
+/// of other macros like `#[wasm_bindgen]` or `#[pyo3(get)]` to see the same tokens as
+/// without it. With the `exact` argument, the rare items whose tokens parsing
+/// normalizes, which are otherwise emitted as parsed, are an error instead:
+///
+/// ```
+/// use fully_pub_core::fully_pub;
+/// use proc_macro2::{TokenStream, TokenTree};
+///
+/// /// Lists the tokens with their spacing and location, the ones of groups included.
+/// fn tokens(stream: TokenStream, list: &mut Vec<String>) {
+///     for tree in stream {
+///         let span = tree.span().byte_range();
+///         match tree {
+///             TokenTree::Group(group) => {
+///                 list.push(format!("{:?} {span:?}", group.delimiter()));
+///                 tokens(group.stream(), list);
+///             }
+///             TokenTree::Punct(punct) => {
+///                 list.push(format!("{punct} {:?} {span:?}", punct.spacing()));
+///             }
+///             tree => list.push(format!("{tree} {span:?}")),
+///         }
+///     }
+/// }
+///
+/// for source in [
+///     r#"#[wasm_bindgen(js_name = "Point")] #[pyo3(get)]
+///        struct Point { #[serde(rename = "X")] x: f64 }"#,
+///     "#[async_trait] impl Server { async fn run(&self) -> u8 { 'a: loop { break 'a 1 } } }",
+/// ] {
+///     let item: TokenStream = source.parse().unwrap();
+///     let expanded = fully_pub("exact".parse().unwrap(), item.clone()).unwrap();
+///
+///     let (mut before, mut after) = (Vec::new(), Vec::new());
+///     tokens(item, &mut before);
+///     tokens(expanded, &mut after);
+///     after.retain(|token| !token.starts_with("pub "));
+///     assert!(after.starts_with(&before));
+/// }
+///
+/// // Parsing drops the empty `where` clause of a trait, which the `light` feature
+/// // keeps by scanning the tokens of the item instead.
+/// let item = "trait Service where {}".parse().unwrap();
+/// let is_normalized = !cfg!(feature = "light");
+/// assert_eq!(fully_pub("exact".parse().unwrap(), item).is_err(), is_normalized);
+///
+/// // The `#![fully_pub::defaults(..)]` attributes are removed like the other helpers.
+/// let item = "mod app { mod store { #![fully_pub::defaults(vis = pub(crate))] struct Store; } }";
+/// let expanded = fully_pub("recursive, exact".parse().unwrap(), item.parse().unwrap());
+/// let expanded = expanded.unwrap().to_string();
+/// assert!(expanded.starts_with("pub mod app { pub mod store { pub (crate) struct Store ; } }"));
+/// ```
+pub fn fully_pub(attr: TokenStream2, item: TokenStream2) -> Result<TokenStream2> {
+    let mut tracker = Tracker::default();
+    let check = is_check(&mut tracker);
+    let mut profile = Profile::new(&mut tracker, !check)?;
+
+    let (expanded, extra, errors) =
+        expand_attribute(attr, item, check, &mut tracker, &mut profile)?;
+    let report = profile.report();
+    let tracked = tracker.tokens();
+
+    let expanded = with_items(expanded, quote! { #extra #report #tracked });
+    Ok(quote! { #expanded #errors })
+}
+
+/// Does what [`fully_pub()`] does, recording its inputs into the tracker and the time
+/// its phases take into the profile. When `check` is set, only the visibilities are
+/// changed.
+///
+/// Returns the expanded item, the items to be emitted alongside it, like the warnings,
+/// and the errors of the configuration.
+fn expand_attribute(
+    attr: TokenStream2,
+    item: TokenStream2,
+    check: bool,
+    tracker: &mut Tracker,
+    profile: &mut Profile,
+) -> Result<(TokenStream2, TokenStream2, Option<TokenStream2>)> {
+    let mut args: Args = parse2(attr)?;
+    let mut cached_in = None;
+
+    // An invalid configuration is reported once, the item is still expanded with the
+    // default configuration to avoid follow-up errors.
+    let config_error = match Config::current(tracker) {
+        Ok(config) => {
+            let cache = cfg!(feature = "files") && config.cache;
+            cached_in = config.manifest_dir.as_ref().filter(|_| cache);
+            let applied = match item_file(&item) {
+                Some(file) => args.apply(&config.in_file(&file)),
+                None => args.apply(config),
+            };
+            applied.err().map(|e| e.to_compile_error())
+        }
+        Err(e) => Some(e.to_compile_error()),
+    };
+
+    // Only the warnings are skipped, the lints denied still fail the check as they
+    // would fail the build.
+    if check {
+        let skip_warning = |level| match level {
+            Level::Warn => Level::Allow,
+            level => level,
+        };
+        args.inventory = false;
+        args.lints = Lints {
+            redundant_pub: skip_warning(args.lints.redundant_pub),
+            private_bounds: skip_warning(args.lints.private_bounds),
+        };
+    }
+
+    let disabled = tracker.var(DISABLE_VAR).is_some();
+    profile.phase("configuration");
+
+    if cfg!(feature = "light") && !disabled {
+        let scanned = scan(&args, item.clone());
+        profile.phase("scanning");
+
+        if let Some(item) = scanned {
+            return Ok((item, TokenStream2::new(), config_error));
+        }
+    }
+
+    // Diagnostics are located at the members, so only their absence can be cached.
+    let cache = cached_in
+        .filter(|_| {
+            !args.inventory
+                && args.lints.redundant_pub == Level::Allow
+                && !bounds::is_checked(&args)
+        })
+        .map(|dir| Cache::new(dir, &args, &item, disabled));
+
+    if let Some(edits) = cache.as_ref().and_then(Cache::load) {
+        let applied = splice::apply(item.clone(), &edits);
+        profile.phase("applying the cached expansion");
+
+        if let Some(item) = applied {
+            return Ok((item, TokenStream2::new(), config_error));
+        }
+    }
+
+    let original = item.clone();
+    let mut item: Item = parse2(item)?;
+    profile.phase("parsing");
+
+    let timings = profile.is_enabled().then_some(&mut profile.modules);
+    let (extra, changed) = match disabled {
+        true => (
+            TokenStream2::new(),
+            explore(&args, &mut item, Mode::Disabled, timings)?.changed,
+        ),
+        false => {
+            let diagnostics = diagnose(&args, &item)?;
+            let ranks = bounds::is_checked(&args).then(|| bounds::ranks(&item));
+            let (extra, changed) = expand_item(&args, &mut item, timings)?;
+            let bounds = match ranks {
+                Some(ranks) => bounds::diagnose(&args, &item, &ranks)?,
+                None => TokenStream2::new(),
+            };
+            (quote! { #diagnostics #bounds #extra }, changed)
+        }
+    };
+    profile.phase("exploring");
+
+    // Items the macro has nothing to change in, like the ones already public, are
+    // given back exactly as they came.
+    let exact = args.exact.unwrap_or(false);
+    let item = match (changed, &cache) {
+        (true, Some(cache)) => match splice::splice_edits(original, &item) {
+            (spliced, Some(edits)) => {
+                cache.store(&edits);
+                spliced
+            }
+            _ if exact => return Err(inexact(&item)),
+            (spliced, None) => spliced,
+        },
+        (true, None) => match splice::splice_exact(original, &item) {
+            Some(spliced) => spliced,
+            None if exact => return Err(inexact(&item)),
+            None => item.to_token_stream(),
+        },
+        (false, cache) => {
+            if let Some(cache) = cache {
+                cache.store(&[]);
+            }
+            original
+        }
+    };
+    profile.phase("splicing");
+
+    Ok((item, extra, config_error))
+}
+
+/// Returns `true` if the expansion only needs its visibilities and its errors, when
+/// [`CHECK_VAR`] is set or under rust-analyzer, whose server is not a build to be
+/// tracked.
+fn is_check(tracker: &mut Tracker) -> bool {
+    tracker.var(CHECK_VAR).is_some() || std::env::var_os(RUST_ANALYZER_VAR).is_some()
+}
+
+/// Returns the file the item is written in, whose module the rules of the
+/// configuration are looked up for.
+///
+/// This is the file of the call site, except for the attributes written by a
+/// `macro_rules!` macro: their call site is in the definition of the macro, while the
+/// item is in the module invoking it. The file of the first token given by the
+/// invocation is then used, looking at the tokens of the item and at the first ones
+/// of its groups, like the name of a struct or the items given to a module.
+fn item_file(item: &TokenStream2) -> Option<PathBuf> {
+    let file = local_file(Span::call_site())?;
+
+    let spans = item.clone().into_iter().flat_map(|tree| {
+        let first = match &tree {
+            TokenTree::Group(group) => group.stream().into_iter().next(),
+            _ => None,
+        };
+        iter::once(tree.span()).chain(first.map(|first| first.span()))
+    });
+    let invocation = spans.filter_map(local_file).find(|other| *other != file);
+
+    Some(invocation.unwrap_or(file))
+}
+
+/// Returns the file the span is in, if it comes from a file read by the compiler.
+///
+/// The span of the compiler is asked, since the `local_file` of `proc-macro2` needs its
+/// `span-locations` feature, which would be enabled for every procedural macro of the
+/// build: only `codegen` enables it, for the tools.
+pub(crate) fn local_file(span: Span) -> Option<PathBuf> {
+    proc_macro::is_available()
+        .then(|| span.unwrap().local_file())
+        .flatten()
+}
+
+/// Returns the output of an attribute: the item followed by the items `extra`, like
+/// warnings or the tracking of the inputs.
+///
+/// An attribute can't tell whether its item is in a module, where other items can
+/// follow it, or in an `impl` block or a trait, where they can't. Functions, which can
+/// be in either, get them at the start of their body instead, and they are left out
+/// for the other items that can be associated, constants and type aliases, and for
+/// functions without a body.
+///
+/// ```
+/// use fully_pub_core::fully_pub;
+/// use quote::quote;
+///
+/// let expanded = fully_pub(quote!(), quote!(fn area(&self) -> f64 { self.w * self.h }));
+/// let method: syn::ImplItemFn = syn::parse2(expanded.unwrap()).unwrap();
+/// assert!(matches!(method.vis, syn::Visibility::Public(_)));
+///
+/// // The warnings are emitted in the body too.
+/// # if cfg!(feature = "report") {
+/// let expanded = fully_pub(quote!(inventory), quote!(fn area(&self) {}));
+/// let method: syn::ImplItemFn = syn::parse2(expanded.unwrap()).unwrap();
+/// assert!(quote!(#method).to_string().contains("deprecated"));
+/// # }
+/// ```
+pub(crate) fn with_items(item: TokenStream2, extra: TokenStream2) -> TokenStream2 {
+    if extra.is_empty() {
+        return item;
+    }
+
+    let mut trees: Vec<_> = item.into_iter().collect();
+    let mut keywords = trees
+        .iter()
+        .filter_map(|tree| match tree {
+            TokenTree::Ident(ident) => Some(ident.to_string()),
+            _ => None,
+        })
+        .skip_while(|ident| matches!(ident.as_str(), "pub" | "default"))
+        .peekable();
+    // The keyword telling the kind of the item, after the qualifiers of functions. The
+    // name of a constant comes instead, as it has none.
+    let is_const = keywords.peek().is_some_and(|ident| ident == "const");
+    let keyword = keywords.find(|ident| {
+        !matches!(
+            ident.as_str(),
+            "const" | "async" | "unsafe" | "extern" | "safe"
+        )
+    });
+
+    match keyword.as_deref() {
+        Some("fn") => match trees.last_mut() {
+            Some(TokenTree::Group(body)) if body.delimiter() == Delimiter::Brace => {
+                let stream = body.stream();
+                let mut group = Group::new(Delimiter::Brace, quote! { #extra #stream });
+                group.set_span(body.span());
+                *body = group;
+            }
+            _ => (),
+        },
+        Some("type") => (),
+        _ if is_const => (),
+        _ => trees.extend(extra),
+    }
+
+    trees.into_iter().collect()
+}
+
+/// Returns a token stream that makes the compiler emit a warning with the given
+/// message at `span`.
+///
+/// There is no stable way for a procedural macro to emit warnings, so this
+/// goes through the `deprecated` lint, in an item to be emitted with [`with_items`].
+fn warning(span: Span, message: &str) -> TokenStream2 {
+    let name = Ident::new(CRATE_NAME, span);
+
+    quote_spanned! {span=>
+        const _: () = {
+            #[deprecated(note = #message)]
+            #[allow(non_upper_case_globals)]
+            const #name: () = ();
+            #name
+        };
+    }
+}
+
+/// Returns the error of an `exact` attribute whose item can't be given back with its
+/// tokens as written.
+fn inexact(item: &Item) -> Error {
+    let message = format!(
+        "the tokens of {} can't be kept as written, since parsing it normalized them: \
+        remove `exact` for them to be emitted as parsed",
+        describe(item),
+    );
+    Error::new(item.span(), message)
+}
+
+/// Returns a short description of the item, to be used in messages.
+fn describe(item: &Item) -> String {
+    let ident = match item {
+        Item::Const(ItemConst { ident, .. })
+        | Item::Enum(ItemEnum { ident, .. })
+        | Item::Fn(ItemFn {
+            sig: Signature { ident, .. },
+            ..
+        })
+        | Item::Mod(ItemMod { ident, .. })
+        | Item::Static(ItemStatic { ident, .. })
+        | Item::Struct(ItemStruct { ident, .. })
+        | Item::Trait(ItemTrait { ident, .. })
+        | Item::TraitAlias(ItemTraitAlias { ident, .. })
+        | Item::Type(ItemType { ident, .. })
+        | Item::Union(ItemUnion { ident, .. }) => ident,
+        Item::Impl(ItemImpl { self_ty, .. }) => {
+            return format!("`impl {}`", quote!(#self_ty).to_string().replace(' ', ""))
+        }
+        Item::ForeignMod(_) => return "`extern` block".to_string(),
+        _ => return "item".to_string(),
+    };
+
+    format!("`{ident}`")
+}
+
+/// Explores the item with the policy of the macro, returning the policy once done.
+///
+/// The time spent exploring each module of the item is added to `timings`, if given.
+fn explore(
+    args: &Args,
+    item: &mut Item,
+    mode: Mode,
+    timings: Option<&mut Vec<(String, Duration)>>,
+) -> Result<Explorer> {
+    let mut visitor = Explorer::new(args, mode);
+    if timings.is_some() {
+        visitor = visitor.timed();
+    }
+
+    visitor.visit_item_mut(item)?;
+
+    if let Some(timings) = timings {
+        timings.extend(visitor.take_timings());
+    }
+    Ok(visitor.into_policy())
+}
+
+/// Like [`expand`], also returning whether the item was changed.
+fn expand_item(
+    args: &Args,
+    item: &mut Item,
+    timings: Option<&mut Vec<(String, Duration)>>,
+) -> Result<(TokenStream2, bool)> {
+    let mode = match args.inventory {
+        true => Mode::Inventory(Inventory::default()),
+        false => Mode::Publish,
+    };
+    let explorer = explore(args, item, mode, timings)?;
+
+    let extra = match explorer.mode {
+        Mode::Inventory(inventory) => warning(item.span(), &inventory.report(&describe(item))),
+        _ => TokenStream2::new(),
+    };
+
+    Ok((extra, explorer.changed))
+}
+
+/// Explore the item, making its parts public according to the arguments.
+///
+/// Returns the tokens to be emitted alongside the item.
+///
+/// With `sync_serde_skip`, the excluded fields of the structs deriving the traits of
+/// `serde` are skipped by it too, under the condition these traits are derived under.
+/// A `skip_serializing_if` does not skip a field:
+///
+/// ```
+/// use fully_pub_core::{expand, Args};
+/// use quote::ToTokens;
+///
+/// let args: Args = syn::parse_str("sync_serde_skip").unwrap();
+/// let mut item = syn::parse_str(
+///     "#[cfg_attr(feature = \"serde\", derive(serde::Serialize))]
+///     struct Account {
+///         login: String,
+///         #[fully_pub(exclude)]
+///         #[serde(skip_serializing_if = \"String::is_empty\")]
+///         secret: String,
+///     }",
+/// )
+/// .unwrap();
+/// expand(&args, &mut item).unwrap();
+///
+/// let expanded = item.to_token_stream().to_string();
+/// let skip = "# [cfg_attr (feature = \"serde\" , serde (skip_serializing))] secret : String";
+/// assert!(expanded.contains(skip));
+/// ```
+///
+/// With `non_constructible`, the private field added to the structs is hidden from
+/// the documentation, and skipped by `serde` under the same condition:
+///
+/// ```
+/// # use fully_pub_core::{expand, Args};
+/// # use quote::ToTokens;
+/// let args: Args = syn::parse_str("non_constructible").unwrap();
+/// let mut item = syn::parse_str(
+///     "#[cfg_attr(feature = \"serde\", derive(serde::Deserialize))]
+///     struct Account {
+///         login: String,
+///     }",
+/// )
+/// .unwrap();
+/// expand(&args, &mut item).unwrap();
+///
+/// let expanded = item.to_token_stream().to_string();
+/// let skip = "# [doc (hidden)] # [cfg_attr (feature = \"serde\" , serde (skip))] _priv : ()";
+/// assert!(expanded.contains(skip));
+///
+/// let mut item = syn::parse_str("struct Account { login: String }").unwrap();
+/// expand(&args, &mut item).unwrap();
+///
+/// let expanded = item.to_token_stream().to_string();
+/// assert!(expanded.contains("login : String , # [doc (hidden)] _priv : ()"));
+/// ```
+pub fn expand(args: &Args, item: &mut Item) -> Result<TokenStream2> {
+    expand_item(args, item, None).map(|(extra, _)| extra)
+}
+
+/// Explore the item without changing any visibility, only removing the
+/// `#[fully_pub(exclude)]` attributes it contains.
+///
+/// This is what the macro does when [`DISABLE_VAR`] is set.
+pub fn disable(args: &Args, item: &mut Item) -> Result<()> {
+    explore(args, item, Mode::Disabled, None).map(|_| ())
+}
+
+/// Explore the item without changing any visibility, returning the spans of the
+/// visibilities written on the parts the macro would give the same visibility anyway.
+///
+/// ```
+/// use fully_pub_core::{lint, Args};
+///
+/// let mut item = syn::parse_str("struct Point { pub x: f32, y: f32 }").unwrap();
+///
+/// assert_eq!(lint(&Args::default(), &mut item).unwrap().len(), 1);
+/// ```
+pub fn lint(args: &Args, item: &mut Item) -> Result<Vec<Span>> {
+    match explore(args, item, Mode::Lint(Vec::new()), None)?.mode {
+        Mode::Lint(spans) => Ok(spans),
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the diagnostics the levels of the configuration ask for on the item,
+/// as tokens emitting warnings, or as an error if one of them is denied.
+pub fn diagnose(args: &Args, item: &Item) -> Result<TokenStream2> {
+    let level = args.lints.redundant_pub;
+    if level == Level::Allow || args.inventory {
+        return Ok(TokenStream2::new());
+    }
+
+    let spans = lint(args, &mut item.clone())?;
+    let message = "redundant visibility, the macro already gives it to this member";
+
+    if level == Level::Warn {
+        return Ok(spans
+            .into_iter()
+            .map(|span| warning(span, message))
+            .collect());
+    }
+
+    let mut errors = spans.into_iter().map(|span| Error::new(span, message));
+    match errors.next() {
+        Some(mut error) => {
+            error.extend(errors);
+            Err(error)
+        }
+        None => Ok(TokenStream2::new()),
+    }
+}
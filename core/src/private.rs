@@ -0,0 +1,178 @@
+//! The `fully_private` attribute, which undoes what `fully_pub` does: it makes the
+//! members of an item private, except the ones marked `#[fully_private(include)]`.
+
+use proc_macro2::TokenStream as TokenStream2;
+use syn::parse::{Parse, ParseStream};
+use syn::*;
+
+use crate::visit::{Decision, Member, VisibilityPolicy, Visitor};
+use crate::{splice, with_items, Tracker, DISABLE_VAR};
+
+/// The name of the attribute, and of its helper attribute.
+pub(crate) const PRIVATE_NAME: &str = "fully_private";
+
+/// Arguments passed to the `#[fully_private(...)]` attribute macro.
+#[derive(Clone, Default)]
+pub struct PrivateArgs {
+    /// Whether nested `mod`s are explored too.
+    recursive: bool,
+}
+
+impl Parse for PrivateArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = PrivateArgs::default();
+
+        if !input.is_empty() {
+            let ident: Ident = input.parse()?;
+            if ident != "recursive" {
+                bail!(
+                    &ident,
+                    "invalid argument to `{PRIVATE_NAME}` attribute macro"
+                );
+            }
+            args.recursive = true;
+        }
+
+        if !input.is_empty() {
+            return Err(input.error(format!(
+                "unexpected token, `{PRIVATE_NAME}` only takes `recursive`"
+            )));
+        }
+
+        Ok(args)
+    }
+}
+
+/// Returns `Ok(true)` if the attributes list contains a `#[fully_private(include)]`
+/// attribute, then removes it from the list.
+///
+/// If the attribute is ill-formatted or present more than once, returns an `Err`.
+fn is_include(attrs: &mut Vec<Attribute>) -> Result<bool> {
+    let mut found = None;
+
+    for (index, attr) in attrs.iter().enumerate() {
+        if !attr.path().is_ident(PRIVATE_NAME) {
+            continue;
+        }
+
+        let arg = attr.parse_args::<Ident>()?;
+
+        if arg != "include" {
+            bail!(&arg, "unknown {PRIVATE_NAME} attribute `{arg}`");
+        }
+
+        if found.is_some() {
+            bail!(attr, "duplicate {PRIVATE_NAME} attribute `include`");
+        }
+
+        found = Some(index);
+    }
+
+    if let Some(index) = found {
+        attrs.remove(index);
+    }
+
+    Ok(found.is_some())
+}
+
+/// The policy of the attribute: removes the visibility of the members that are not
+/// included.
+struct Privatizer {
+    /// Whether visibilities are left untouched, as when [`DISABLE_VAR`] is set.
+    disabled: bool,
+    /// Whether a visibility or a helper attribute was changed.
+    changed: bool,
+}
+
+impl VisibilityPolicy for Privatizer {
+    fn decide(&mut self, member: Member<'_>) -> Result<Decision> {
+        if is_include(member.attrs)? {
+            self.changed = true;
+            return Ok(Decision::Skip);
+        }
+
+        Ok(match member.vis {
+            Visibility::Inherited => Decision::Keep,
+            _ if self.disabled => Decision::Keep,
+            _ => {
+                self.changed = true;
+                Decision::Replace(Visibility::Inherited)
+            }
+        })
+    }
+
+    fn enter_block(&mut self, attrs: &mut Vec<Attribute>) -> Result<bool> {
+        let is_included = is_include(attrs)?;
+        self.changed |= is_included;
+
+        Ok(!is_included)
+    }
+}
+
+/// Explores the item, removing the visibilities of its parts according to the
+/// arguments, and returns whether it was changed.
+///
+/// ```
+/// use fully_pub_core::{privatize, PrivateArgs};
+/// use quote::ToTokens;
+///
+/// let mut item = syn::parse_str(
+///     "pub struct Point { pub(crate) x: f32, #[fully_private(include)] pub y: f32 }",
+/// )
+/// .unwrap();
+/// privatize(&PrivateArgs::default(), &mut item).unwrap();
+///
+/// assert_eq!(
+///     item.to_token_stream().to_string(),
+///     "struct Point { x : f32 , pub y : f32 }",
+/// );
+/// ```
+pub fn privatize(args: &PrivateArgs, item: &mut Item) -> Result<bool> {
+    explore(args, item, false)
+}
+
+/// Explores the item with the policy of the attribute, returning whether it was
+/// changed.
+fn explore(args: &PrivateArgs, item: &mut Item, disabled: bool) -> Result<bool> {
+    let policy = Privatizer {
+        disabled,
+        changed: false,
+    };
+    let mut visitor = Visitor::new(policy).recursive(args.recursive);
+
+    visitor.visit_item_mut(item)?;
+    Ok(visitor.into_policy().changed)
+}
+
+/// Applies the `fully_private` attribute to the item, returning the tokens it expands
+/// to: this is what `#[fully_private(...)]` does with the arguments `attr`.
+///
+/// As for [`fully_pub()`](crate::fully_pub()), the item is given back with the tokens
+/// it came with wherever they are unchanged, and when [`DISABLE_VAR`] is set, only the
+/// helper attributes are removed.
+///
+/// ```
+/// use fully_pub_core::fully_private;
+/// use quote::quote;
+///
+/// let expanded = fully_private(quote!(), quote!(pub struct Meters(pub f64);)).unwrap();
+/// assert!(expanded.to_string().starts_with("struct Meters (f64) ;"));
+///
+/// assert!(fully_private(quote!(exclude), quote!(struct Meters(f64);)).is_err());
+/// ```
+pub fn fully_private(attr: TokenStream2, item: TokenStream2) -> Result<TokenStream2> {
+    let args: PrivateArgs = parse2(attr)?;
+    let mut tracker = Tracker::default();
+    let disabled = tracker.var(DISABLE_VAR).is_some();
+
+    let original = item.clone();
+    let mut item: Item = parse2(item)?;
+
+    let item = match explore(&args, &mut item, disabled)? {
+        true => splice(original, &item),
+        false => original,
+    };
+    let tracked = tracker.tokens();
+
+    Ok(with_items(item, tracked))
+}
@@ -0,0 +1,121 @@
+//! Timing of the expansions, reported when they take longer than the budget set by
+//! [`TIME_BUDGET_VAR`](crate::TIME_BUDGET_VAR).
+
+use std::cmp::Reverse;
+use std::time::{Duration, Instant};
+
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::{Error, Result};
+
+use crate::{warning, Tracker, TIME_BUDGET_VAR};
+
+/// How many of the slowest modules a report lists.
+const MODULES: usize = 10;
+
+/// The time an expansion takes, by phase and by module. It measures nothing if no
+/// budget is set.
+pub(crate) struct Profile {
+    budget: Option<Duration>,
+    /// When the expansion started, if it is measured: the clock is only read when a
+    /// budget is set, for the expansions that are not measured not to pay for it.
+    start: Option<Instant>,
+    /// The end of the last phase.
+    last: Option<Instant>,
+    phases: Vec<(&'static str, Duration)>,
+    /// The time spent exploring each module of the expanded one, with their own.
+    pub(crate) modules: Vec<(String, Duration)>,
+}
+
+impl Profile {
+    /// Starts measuring an expansion if it is `enabled` and [`TIME_BUDGET_VAR`] is set,
+    /// recording it into the tracker. Without the `report` feature, nothing is measured.
+    pub(crate) fn new(tracker: &mut Tracker, enabled: bool) -> Result<Self> {
+        let var = match cfg!(feature = "report") && enabled {
+            true => tracker.var(TIME_BUDGET_VAR),
+            false => None,
+        };
+
+        let budget = match var {
+            Some(budget) => match budget.to_str().and_then(|b| b.parse().ok()) {
+                Some(millis) => Some(Duration::from_millis(millis)),
+                None => {
+                    return Err(Error::new(
+                        Span::call_site(),
+                        format!(
+                            "expected a number of milliseconds in `{TIME_BUDGET_VAR}`, found \
+                            `{}`",
+                            budget.to_string_lossy(),
+                        ),
+                    ))
+                }
+            },
+            None => None,
+        };
+
+        let start = budget.map(|_| Instant::now());
+        Ok(Profile {
+            budget,
+            start,
+            last: start,
+            phases: Vec::new(),
+            modules: Vec::new(),
+        })
+    }
+
+    /// Returns `true` if the expansion is measured.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.budget.is_some()
+    }
+
+    /// Ends the phase of the expansion, which started at the end of the last one.
+    pub(crate) fn phase(&mut self, name: &'static str) {
+        if let Some(last) = &mut self.last {
+            let now = Instant::now();
+            self.phases.push((name, now - *last));
+            *last = now;
+        }
+    }
+
+    /// Returns the tokens of a warning detailing the expansion, if it went over the
+    /// budget.
+    pub(crate) fn report(&mut self) -> TokenStream2 {
+        let (Some(budget), Some(start)) = (self.budget, self.start) else {
+            return TokenStream2::new();
+        };
+        let total = start.elapsed();
+        if total <= budget {
+            return TokenStream2::new();
+        }
+
+        let phases: Vec<_> = self
+            .phases
+            .iter()
+            .map(|(name, time)| format!("{name} {time:.1?}"))
+            .collect();
+        let mut message = format!(
+            "expansion took {total:.1?}, over the budget of {budget:?} set by \
+            `{TIME_BUDGET_VAR}`: {}",
+            phases.join(", "),
+        );
+
+        if !self.modules.is_empty() {
+            self.modules.sort_by_key(|&(_, time)| Reverse(time));
+            let modules: Vec<_> = self
+                .modules
+                .iter()
+                .take(MODULES)
+                .map(|(name, time)| format!("`{name}` {time:.1?}"))
+                .collect();
+
+            message += &format!(
+                "; exploring the slowest modules took {}",
+                modules.join(", ")
+            );
+            if self.modules.len() > MODULES {
+                message += &format!(" and {} others", self.modules.len() - MODULES);
+            }
+        }
+
+        warning(Span::call_site(), &message)
+    }
+}
@@ -0,0 +1,659 @@
+//! Rewriting of source files, applying the expansion of the macro to the source text
+//! itself.
+//!
+//! The rewrites are computed by comparing the tokens of a file before and after the
+//! expansion: tokens coming from the source are identified by their location in it,
+//! so the tokens that disappeared are deleted from the text and the tokens created by
+//! the expansion are inserted in it, leaving everything else untouched.
+//!
+//! Comments, formatting and `cfg` attributes are thus preserved exactly: only the
+//! whitespace around deleted tokens is adjusted, so they don't leave blank lines or
+//! doubled spaces behind.
+
+use std::mem;
+use std::ops::Range;
+
+use proc_macro2::{Delimiter, Spacing, TokenStream as TokenStream2, TokenTree};
+use quote::ToTokens;
+use syn::*;
+
+use crate::source::{expand_all, expand_annotated};
+use crate::{expand, Args, Config, CRATE_NAME};
+
+mod annotate;
+mod fix;
+mod migrate;
+
+pub use annotate::{annotate_excludes_source, SENSITIVE_NAMES};
+pub use fix::fix_source;
+pub use migrate::migrate_source;
+
+/// A token of a flattened stream.
+enum Token {
+    /// A token from the source, with its location in it.
+    Source(Range<usize>),
+    /// A token created by the expansion.
+    Synthetic(TokenTree),
+}
+
+/// Flattens the stream into a list of tokens, delimiters of groups included.
+fn flatten(stream: TokenStream2, tokens: &mut Vec<Token>) {
+    for tree in stream {
+        let range = tree.span().byte_range();
+
+        match tree {
+            _ if range.is_empty() => tokens.push(Token::Synthetic(tree)),
+            TokenTree::Group(group) => {
+                tokens.push(Token::Source(group.span_open().byte_range()));
+                flatten(group.stream(), tokens);
+                tokens.push(Token::Source(group.span_close().byte_range()));
+            }
+            _ => tokens.push(Token::Source(range)),
+        }
+    }
+}
+
+/// Renders tokens created by a rewrite the way they would be written by hand, like
+/// `#[fully_pub(exclude)]` instead of `# [fully_pub (exclude)]`.
+fn render(trees: &[TokenTree]) -> String {
+    let mut out = String::new();
+    let mut previous: Option<&TokenTree> = None;
+
+    for tree in trees {
+        let spaced = match (previous, tree) {
+            (None, _) => false,
+            (Some(TokenTree::Punct(punct)), _)
+                if punct.spacing() == Spacing::Joint || matches!(punct.as_char(), '#' | '!') =>
+            {
+                false
+            }
+            (Some(_), TokenTree::Punct(punct)) => !matches!(punct.as_char(), ',' | ';' | ':'),
+            (Some(TokenTree::Ident(_)), TokenTree::Group(group)) => !matches!(
+                group.delimiter(),
+                Delimiter::Parenthesis | Delimiter::Bracket
+            ),
+            _ => true,
+        };
+
+        // The second colon of a `::` is not followed by a space either.
+        let after_path_separator = out.ends_with("::");
+
+        if spaced && !after_path_separator {
+            out.push(' ');
+        }
+
+        match tree {
+            TokenTree::Group(group) => {
+                let inner = render(&group.stream().into_iter().collect::<Vec<_>>());
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace if inner.is_empty() => ("{", "}"),
+                    Delimiter::Brace => ("{ ", " }"),
+                    Delimiter::None => ("", ""),
+                };
+                out += open;
+                out += &inner;
+                out += close;
+            }
+            _ => out += &tree.to_string(),
+        }
+
+        previous = Some(tree);
+    }
+
+    out
+}
+
+/// A textual edit, replacing a range of the source with some text.
+struct Edit {
+    range: Range<usize>,
+    text: String,
+}
+
+/// Computes the edits turning `source`, whose tokens are `before`, into a text
+/// whose tokens are `after`.
+fn diff(source: &str, before: TokenStream2, after: TokenStream2) -> Vec<Edit> {
+    let mut old = Vec::new();
+    let mut new = Vec::new();
+    flatten(before, &mut old);
+    flatten(after, &mut new);
+
+    let mut old = old.into_iter().filter_map(|token| match token {
+        Token::Source(range) => Some(range),
+        Token::Synthetic(_) => None,
+    });
+
+    let mut edits = Vec::new();
+    let mut deleted: Vec<Range<usize>> = Vec::new();
+    let mut inserted: Vec<TokenTree> = Vec::new();
+
+    // The end of the last token of the source met.
+    let mut last_end = 0;
+
+    let mut flush = |deleted: &mut Vec<Range<usize>>,
+                     inserted: &mut Vec<TokenTree>,
+                     last_end: usize,
+                     at: usize| {
+        // Deleted tokens only separated by whitespace are deleted at once.
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for range in deleted.drain(..) {
+            match ranges.last_mut() {
+                Some(last) if source[last.end..range.start].trim().is_empty() => {
+                    last.end = range.end
+                }
+                _ => ranges.push(range),
+            }
+        }
+
+        let mut edits_here: Vec<_> = ranges
+            .into_iter()
+            .map(|range| Edit {
+                range,
+                text: String::new(),
+            })
+            .collect();
+
+        if !inserted.is_empty() {
+            let trees = mem::take(inserted);
+            let text = render(&trees);
+
+            // Tokens inserted right after deleted ones on the same line take their place.
+            let replaced = edits_here.last_mut().filter(|last| {
+                let between = &source[last.range.end..at];
+                between.trim().is_empty() && !between.contains('\n')
+            });
+
+            let is_group_end = replaced.is_none() && source[at..].starts_with([')', ']', '}']);
+
+            match replaced {
+                // Attributes are kept on their own line.
+                Some(last) if !text.starts_with('#') => last.text = text,
+                replaced => {
+                    let at = replaced.map_or(at, |last| last.range.start);
+                    let line_start = source[..at].rfind('\n').map_or(0, |i| i + 1);
+                    let indent = &source[line_start..at];
+
+                    let text = match text.starts_with('#') && indent.trim().is_empty() {
+                        true => format!("{text}\n{indent}"),
+                        // Nothing is written between tokens and a closing delimiter.
+                        false if source[at..].starts_with([')', ']', '}', ',', ';']) => text,
+                        false => text + " ",
+                    };
+
+                    match is_group_end {
+                        true => edits_here.extend(group_end(source, &trees, last_end, at)),
+                        false => edits_here.push(Edit {
+                            range: at..at,
+                            text,
+                        }),
+                    }
+                    edits_here.sort_by_key(|edit| (edit.range.start, edit.range.end));
+                }
+            }
+        }
+
+        edits.extend(edits_here);
+    };
+
+    for token in new {
+        match token {
+            Token::Synthetic(tree) => inserted.push(tree),
+            Token::Source(range) => {
+                for old in old.by_ref() {
+                    if old == range {
+                        break;
+                    }
+                    deleted.push(old);
+                }
+                flush(&mut deleted, &mut inserted, last_end, range.start);
+                last_end = range.end;
+            }
+        }
+    }
+
+    deleted.extend(old);
+    flush(&mut deleted, &mut inserted, last_end, source.len());
+
+    edits
+}
+
+/// Returns the edits inserting the tokens added at the end of a group, like the
+/// private field of a `non_constructible` struct, before its closing delimiter at
+/// `at`. A leading comma is written right after the token of the source ending at
+/// `last_end`.
+///
+/// If the delimiter starts its line, the tokens get lines of their own, indented like
+/// the line of this token, and so does each of their leading attributes.
+fn group_end(source: &str, trees: &[TokenTree], last_end: usize, at: usize) -> Vec<Edit> {
+    let mut edits = Vec::new();
+    let mut trees = trees;
+    let mut after_comma = false;
+    if let [TokenTree::Punct(punct), rest @ ..] = trees {
+        if punct.as_char() == ',' {
+            edits.push(Edit {
+                range: last_end..last_end,
+                text: ",".to_string(),
+            });
+            trees = rest;
+            after_comma = true;
+        }
+    }
+
+    let line_start = source[..at].rfind('\n').map_or(0, |i| i + 1);
+    if !source[line_start..at].trim().is_empty() {
+        let mut text = render(trees);
+        if after_comma && source[last_end..at].is_empty() {
+            text.insert(0, ' ');
+        }
+        if source[..at].ends_with(is_blank) {
+            text.push(' ');
+        }
+        edits.push(Edit {
+            range: at..at,
+            text,
+        });
+        return edits;
+    }
+
+    let line = &source[source[..last_end].rfind('\n').map_or(0, |i| i + 1)..];
+    let indent = &line[..line.len() - line.trim_start().len()];
+
+    let mut text = String::new();
+    while let [TokenTree::Punct(pound), TokenTree::Group(group), rest @ ..] = trees {
+        if pound.as_char() != '#' || group.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        text += &format!("{indent}{}\n", render(&trees[..2]));
+        trees = rest;
+    }
+    if !trees.is_empty() {
+        text += &format!("{indent}{}\n", render(trees));
+    }
+
+    edits.push(Edit {
+        range: line_start..line_start,
+        text,
+    });
+    edits
+}
+
+/// Returns `true` for the whitespace that can be removed along with a deleted token.
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// Extends the range of the deletions to the whitespace around them, so that a
+/// deletion that empties a line removes the whole line, and one in the middle of
+/// a line does not leave two spaces behind.
+///
+/// The edits must be sorted, and are kept from overlapping.
+fn tidy(source: &str, edits: &mut [Edit]) {
+    for i in 0..edits.len() {
+        if !edits[i].text.is_empty() {
+            continue;
+        }
+
+        let min = if i == 0 { 0 } else { edits[i - 1].range.end };
+        let max = edits
+            .get(i + 1)
+            .map_or(source.len(), |next| next.range.start);
+        let Range { start, end } = edits[i].range;
+
+        let line_start = source[min..start].rfind('\n').map_or(min, |i| min + i + 1);
+        let line_end = source[end..max].find('\n').map_or(max, |i| end + i);
+        let before = &source[line_start..start];
+        let after = source[end..line_end].trim_end_matches('\r');
+
+        edits[i].range = match (before.trim().is_empty(), after.trim().is_empty()) {
+            // The line only contained deleted tokens.
+            (true, true) if line_end < max => line_start..line_end + 1,
+            (true, true) => line_start..line_end,
+            // The deleted tokens started the line.
+            (true, false) => start..end + (after.len() - after.trim_start_matches(is_blank).len()),
+            // The deleted tokens were in the middle or at the end of the line.
+            (false, _) => start - (before.len() - before.trim_end_matches(is_blank).len())..end,
+        };
+    }
+}
+
+/// Applies the edits, which must be sorted and not overlap, to the source.
+fn apply(source: &str, edits: &[Edit]) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut last = 0;
+
+    for Edit { range, text } in edits {
+        out += &source[last..range.start];
+        out += text;
+        last = range.end;
+    }
+
+    out + &source[last..]
+}
+
+/// Returns `true` if the item is `use fully_pub::fully_pub;`.
+fn is_macro_import(item: &Item) -> bool {
+    let Item::Use(ItemUse {
+        tree: UseTree::Path(UsePath { ident, tree, .. }),
+        ..
+    }) = item
+    else {
+        return false;
+    };
+
+    ident == CRATE_NAME
+        && matches!(&**tree, UseTree::Name(UseName { ident }) if ident == CRATE_NAME)
+}
+
+/// Returns `true` if the tokens refer to the macro.
+fn mentions_macro(stream: TokenStream2) -> bool {
+    stream.into_iter().any(|tree| match tree {
+        TokenTree::Ident(ident) => ident == CRATE_NAME,
+        TokenTree::Group(group) => mentions_macro(group.stream()),
+        _ => false,
+    })
+}
+
+/// Removes the imports of the macro from the items, and from the inline modules
+/// they contain, unless the macro is still used next to them.
+fn remove_imports(items: &mut Vec<Item>) {
+    let used = items
+        .iter()
+        .filter(|item| !is_macro_import(item))
+        .any(|item| mentions_macro(item.to_token_stream()));
+
+    if !used {
+        items.retain(|item| !is_macro_import(item));
+    }
+
+    for item in items {
+        if let Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            remove_imports(items);
+        }
+    }
+}
+
+/// Parses the source file, transforms it with `f`, and returns the source edited
+/// to match the transformed file.
+///
+/// The transformation may only remove tokens from the file, or insert new ones.
+pub(crate) fn rewrite(source: &str, f: impl FnOnce(&mut File) -> Result<()>) -> Result<String> {
+    let file = syn::parse_file(source)?;
+    let mut rewritten = file.clone();
+
+    f(&mut rewritten)?;
+
+    let mut edits = diff(
+        source,
+        file.into_token_stream(),
+        rewritten.into_token_stream(),
+    );
+    tidy(source, &mut edits);
+
+    Ok(apply(source, &edits))
+}
+
+/// Expands all the `fully_pub` attributes of a source file, returning the new source
+/// where they are replaced by explicit visibilities. The arguments left out of the
+/// attributes are taken from `config`.
+///
+/// The imports of the macro (`use fully_pub::fully_pub;`) are removed as well, if it is
+/// not used anymore. Everything else in the file is left as written, comments and formatting included.
+/// Attributes applied conditionally, like `#[cfg_attr(test, fully_pub)]`, can't be
+/// expanded statically and are left in place.
+///
+/// ```
+/// let source = "\
+/// use fully_pub::fully_pub;
+///
+/// #[fully_pub]
+/// struct User {
+///     name: String, // the full name
+///     #[fully_pub(exclude)]
+///     secret: String,
+/// }
+/// ";
+///
+/// let expanded = fully_pub_core::rewrite::expand_source(source, &Default::default()).unwrap();
+///
+/// assert_eq!(expanded, "\
+///
+/// pub struct User {
+///     pub name: String, // the full name
+///     secret: String,
+/// }
+/// ");
+/// ```
+///
+/// The inner `#![fully_pub::defaults(..)]` attributes of the modules are applied, and
+/// removed:
+///
+/// ```
+/// let source = "\
+/// #[fully_pub::fully_pub(recursive)]
+/// mod app {
+///     mod storage {
+///         #![fully_pub::defaults(vis = pub(crate))]
+///         struct Store { path: String }
+///     }
+/// }
+/// ";
+///
+/// let expanded = fully_pub_core::rewrite::expand_source(source, &Default::default()).unwrap();
+///
+/// assert_eq!(expanded, "\
+/// pub mod app {
+///     pub mod storage {
+///         pub(crate) struct Store { pub(crate) path: String }
+///     }
+/// }
+/// ");
+/// ```
+///
+/// The private field of the `non_constructible` structs is written on lines of its
+/// own, its attributes included, unless the struct is written on one line:
+///
+/// ```
+/// let source = "\
+/// #[fully_pub::fully_pub(non_constructible)]
+/// struct Options {
+///     jobs: usize // the count
+/// }
+///
+/// #[fully_pub::fully_pub(non_constructible)]
+/// struct Point { x: f32, y: f32 }
+/// ";
+///
+/// let expanded = fully_pub_core::rewrite::expand_source(source, &Default::default()).unwrap();
+///
+/// assert_eq!(expanded, "\
+/// pub struct Options {
+///     pub jobs: usize, // the count
+///     #[doc(hidden)]
+///     _priv: ()
+/// }
+///
+/// pub struct Point { pub x: f32, pub y: f32, #[doc(hidden)] _priv: () }
+/// ");
+/// ```
+pub fn expand_source(source: &str, config: &Config) -> Result<String> {
+    rewrite(source, |file| {
+        expand_all(&mut file.items, config)?;
+        remove_imports(&mut file.items);
+        Ok(())
+    })
+}
+
+/// Makes every item of a source file public, as if the file was the content of a
+/// module annotated with `#[fully_pub]`, or `#[fully_pub(recursive)]` if `recursive`
+/// is `true`.
+///
+/// The items of the file annotated with the macro are expanded as usual, and
+/// `#[fully_pub(exclude)]` can be used to keep items private. The defaults of
+/// `config` apply, except for `recursive`. This is meant for generated code, that
+/// is never annotated itself.
+///
+/// ```
+/// let source = "\
+/// struct Point { x: f32, y: f32 }
+/// mod detail { fn helper() {} }
+/// ";
+///
+/// let published = fully_pub_core::rewrite::publish_source(source, true, &Default::default());
+/// let published = published.unwrap();
+///
+/// assert_eq!(published, "\
+/// pub struct Point { pub x: f32, pub y: f32 }
+/// pub mod detail { pub fn helper() {} }
+/// ");
+/// ```
+pub fn publish_source(source: &str, recursive: bool, config: &Config) -> Result<String> {
+    let mut args = Args {
+        recursive: Some(recursive),
+        ..Args::default()
+    };
+    args.apply(config)?;
+
+    rewrite(source, |file| {
+        for item in &mut file.items {
+            if expand_annotated(item, config)? {
+                continue;
+            }
+
+            expand(&args, item)?;
+
+            // Annotations in modules that are not explored are still expanded.
+            if let Item::Mod(ItemMod {
+                content: Some((_, items)),
+                ..
+            }) = item
+            {
+                if !recursive {
+                    expand_all(items, config)?;
+                }
+            }
+        }
+
+        remove_imports(&mut file.items);
+        Ok(())
+    })
+}
+
+/// Returns `true` if the bracketed content of an attribute is one of the macro's.
+fn is_macro_attribute(content: TokenStream2) -> bool {
+    let mut tokens = content.into_iter().peekable();
+
+    // Skips a leading `::`, as in `#[::fully_pub::fully_pub]`.
+    while let Some(TokenTree::Punct(punct)) = tokens.peek() {
+        if punct.as_char() != ':' {
+            return false;
+        }
+        tokens.next();
+    }
+
+    matches!(tokens.next(), Some(TokenTree::Ident(ident)) if ident == CRATE_NAME)
+}
+
+/// Removes all the attributes of the macro from the stream, helpers and inner
+/// attributes like `#![fully_pub::defaults(..)]` included.
+fn strip_attributes(stream: TokenStream2) -> TokenStream2 {
+    let mut trees: Vec<_> = stream.into_iter().collect();
+    let mut out = Vec::with_capacity(trees.len());
+    let mut i = 0;
+
+    while i < trees.len() {
+        if let TokenTree::Punct(punct) = &trees[i] {
+            let is_inner =
+                matches!(trees.get(i + 1), Some(TokenTree::Punct(bang)) if bang.as_char() == '!');
+            let brackets = i + 1 + usize::from(is_inner);
+            let is_macro = matches!(
+                trees.get(brackets),
+                Some(TokenTree::Group(group))
+                    if group.delimiter() == Delimiter::Bracket
+                        && is_macro_attribute(group.stream())
+            );
+
+            if punct.as_char() == '#' && (is_inner || punct.spacing() == Spacing::Alone) && is_macro
+            {
+                i = brackets + 1;
+                continue;
+            }
+        }
+
+        if let TokenTree::Group(group) = &mut trees[i] {
+            let mut stripped =
+                proc_macro2::Group::new(group.delimiter(), strip_attributes(group.stream()));
+            stripped.set_span(group.span());
+            *group = stripped;
+        }
+
+        out.push(trees[i].clone());
+        i += 1;
+    }
+
+    out.into_iter().collect()
+}
+
+/// Removes all the `fully_pub` attributes of a source file, helpers included, leaving
+/// everything at the visibility it is written with.
+///
+/// As with [`expand_source`], the imports of the macro are removed too, and the rest
+/// of the file is left untouched.
+///
+/// ```
+/// let source = "\
+/// #[fully_pub::fully_pub]
+/// struct User {
+///     name: String,
+///     #[fully_pub(exclude)]
+///     secret: String,
+/// }
+/// ";
+///
+/// let stripped = fully_pub_core::rewrite::strip_source(source).unwrap();
+///
+/// assert_eq!(stripped, "\
+/// struct User {
+///     name: String,
+///     secret: String,
+/// }
+/// ");
+/// ```
+///
+/// The inner `#![fully_pub::defaults(..)]` attributes of the modules are removed too:
+///
+/// ```
+/// let source = "\
+/// use fully_pub::fully_pub;
+///
+/// #[fully_pub(recursive)]
+/// mod app {
+///     mod storage {
+///         #![fully_pub::defaults(vis = pub(crate))]
+///         struct Store { path: String }
+///     }
+/// }
+/// ";
+///
+/// let stripped = fully_pub_core::rewrite::strip_source(source).unwrap();
+///
+/// assert_eq!(stripped, "\
+///
+/// mod app {
+///     mod storage {
+///         struct Store { path: String }
+///     }
+/// }
+/// ");
+/// ```
+pub fn strip_source(source: &str) -> Result<String> {
+    rewrite(source, |file| {
+        *file = syn::parse2(strip_attributes(file.to_token_stream()))?;
+        remove_imports(&mut file.items);
+        Ok(())
+    })
+}
@@ -0,0 +1,156 @@
+//! Annotation of the members of annotated items whose names suggest they hold
+//! sensitive data, for a review of what the macro publishes.
+
+use quote::ToTokens;
+use syn::*;
+
+use super::{apply, Edit};
+use crate::explore::matches;
+use crate::source::{expand_annotated, members};
+use crate::Config;
+
+/// The patterns of the names of the members that often hold sensitive data.
+pub const SENSITIVE_NAMES: &[&str] = &[
+    "*password*",
+    "*passwd*",
+    "*secret*",
+    "*token*",
+    "*api_key*",
+    "*private_key*",
+    "*credential*",
+    "*salt*",
+    "*ssn*",
+];
+
+/// The attribute added to the sensitive members.
+const EXCLUDE: &str = "#[fully_pub(exclude)]";
+
+/// Collects the offsets of the sensitive members the annotated items of the list
+/// publish, with the pattern their name matches, looking inside inline modules too.
+fn collect(
+    items: &[Item],
+    patterns: &[String],
+    config: &Config,
+    found: &mut Vec<(usize, String)>,
+) -> Result<()> {
+    for item in items {
+        let mut expanded = item.clone();
+
+        if !expand_annotated(&mut expanded, config)? {
+            if let Item::Mod(ItemMod {
+                content: Some((_, items)),
+                ..
+            }) = item
+            {
+                collect(items, patterns, config, found)?;
+            }
+            continue;
+        }
+
+        // Excluding the annotated item itself would leave all of it private.
+        let skipped = match item {
+            Item::Impl(_) | Item::ForeignMod(_) => 0,
+            _ => 1,
+        };
+
+        for (before, after) in members(item).iter().zip(&members(&expanded)).skip(skipped) {
+            let (was, now) = (&before.vis, &after.vis);
+            if was.to_token_stream().to_string() == now.to_token_stream().to_string() {
+                continue;
+            }
+
+            let name = before.path.rsplit("::").next().unwrap_or_default();
+            let name = name.to_lowercase();
+            let Some(pattern) = patterns
+                .iter()
+                .find(|pattern| matches(&pattern.to_lowercase(), &name))
+            else {
+                continue;
+            };
+
+            found.push((before.start.byte_range().start, pattern.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrites a source file, adding `#[fully_pub(exclude)]` with a `TODO` comment to
+/// the members that the annotated items would publish and whose names match one of
+/// the patterns, like [`SENSITIVE_NAMES`], for them to be reviewed.
+///
+/// The names are matched regardless of their case, and `*` stands for any sequence
+/// of characters. The arguments left out of the attributes are taken from `config`.
+///
+/// ```
+/// use fully_pub_core::rewrite::annotate_excludes_source;
+///
+/// let source = "\
+/// #[fully_pub::fully_pub]
+/// struct Account {
+///     login: String,
+///     password_hash: String,
+/// }
+/// ";
+///
+/// let patterns = ["*password*".to_string()];
+/// let annotated = annotate_excludes_source(source, &patterns, &Default::default()).unwrap();
+///
+/// assert_eq!(annotated, "\
+/// #[fully_pub::fully_pub]
+/// struct Account {
+///     login: String,
+///     // TODO: review, the name matches `*password*`
+///     #[fully_pub(exclude)]
+///     password_hash: String,
+/// }
+/// ");
+/// ```
+///
+/// The members sharing their line with other tokens get the attribute on the same
+/// line, with the `TODO` in a block comment:
+///
+/// ```
+/// use fully_pub_core::rewrite::annotate_excludes_source;
+///
+/// let source = "#[fully_pub::fully_pub] struct Session { user: String, token: String }";
+/// let patterns = ["*token*".to_string()];
+/// let annotated = annotate_excludes_source(source, &patterns, &Default::default()).unwrap();
+///
+/// assert_eq!(
+///     annotated,
+///     "#[fully_pub::fully_pub] struct Session { user: String, \
+///     /* TODO: review, the name matches `*token*` */ #[fully_pub(exclude)] token: String }",
+/// );
+/// ```
+pub fn annotate_excludes_source(
+    source: &str,
+    patterns: &[String],
+    config: &Config,
+) -> Result<String> {
+    let file = parse_file(source)?;
+    let mut found = Vec::new();
+    collect(&file.items, patterns, config, &mut found)?;
+    found.sort();
+
+    let edits: Vec<_> = found
+        .into_iter()
+        .map(|(at, pattern)| {
+            let line_start = source[..at].rfind('\n').map_or(0, |i| i + 1);
+            let indent = &source[line_start..at];
+            let reason = format!("TODO: review, the name matches `{pattern}`");
+
+            // Members sharing their line with other tokens get a block comment.
+            let text = match indent.trim().is_empty() {
+                true => format!("// {reason}\n{indent}{EXCLUDE}\n{indent}"),
+                false => format!("/* {reason} */ {EXCLUDE} "),
+            };
+            Edit {
+                range: at..at,
+                text,
+            }
+        })
+        .collect();
+
+    Ok(apply(source, &edits))
+}
@@ -0,0 +1,298 @@
+//! Fixes of the uses of the macro that can be written shorter: visibilities the
+//! macro gives anyway, exclusions that change nothing, and modules whose items are
+//! all annotated one by one instead of the module being recursive.
+
+use std::ops::Range;
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::parse::ParseStream;
+use syn::spanned::Spanned;
+use syn::*;
+
+use super::rewrite;
+use crate::source::{attrs_mut, expand_annotated, take_attribute};
+use crate::visit::{Decision, Member, VisibilityPolicy, Visitor};
+use crate::{lint, Config, CRATE_NAME};
+
+/// Returns `true` if the attribute is a helper attribute of the macro, like
+/// `#[fully_pub(exclude)]`.
+fn is_helper(attr: &Attribute) -> bool {
+    attr.path().is_ident(CRATE_NAME)
+        && attr
+            .parse_args_with(|input: ParseStream| input.parse::<Ident>())
+            .is_ok_and(|arg| arg == "exclude")
+}
+
+/// Returns `true` if the attribute is the macro itself, `#[fully_pub]` or
+/// `#[fully_pub::fully_pub]`.
+fn is_macro(attr: &Attribute) -> bool {
+    let segments: Vec<_> = attr.path().segments.iter().map(|s| &s.ident).collect();
+
+    match segments.as_slice() {
+        [name] => *name == CRATE_NAME,
+        [krate, name] => *krate == CRATE_NAME && *name == CRATE_NAME,
+        _ => false,
+    }
+}
+
+/// Removes the visibilities at the given locations, the redundant ones the lint found.
+struct RemoveVisibilities(Vec<Range<usize>>);
+
+impl VisibilityPolicy for RemoveVisibilities {
+    fn decide(&mut self, member: Member<'_>) -> Result<Decision> {
+        Ok(match self.0.contains(&member.vis.span().byte_range()) {
+            true => Decision::Replace(Visibility::Inherited),
+            false => Decision::Keep,
+        })
+    }
+}
+
+/// Removes the helper attribute met in the position `target`, counting how many
+/// there are.
+struct RemoveHelper {
+    target: usize,
+    seen: usize,
+}
+
+impl RemoveHelper {
+    fn attrs(&mut self, attrs: &mut Vec<Attribute>) {
+        let mut index = 0;
+
+        while index < attrs.len() {
+            if is_helper(&attrs[index]) {
+                self.seen += 1;
+                if self.seen - 1 == self.target {
+                    attrs.remove(index);
+                    continue;
+                }
+            }
+            index += 1;
+        }
+    }
+}
+
+impl VisibilityPolicy for RemoveHelper {
+    fn decide(&mut self, member: Member<'_>) -> Result<Decision> {
+        self.attrs(member.attrs);
+        Ok(Decision::Keep)
+    }
+
+    fn enter_block(&mut self, attrs: &mut Vec<Attribute>) -> Result<bool> {
+        self.attrs(attrs);
+        Ok(true)
+    }
+}
+
+/// Expands the annotated items of the list, and then the ones left in their modules,
+/// as the compiler does.
+fn expand_deep(items: &mut [Item], config: &Config) -> Result<()> {
+    for item in items {
+        expand_annotated(item, config)?;
+
+        if let Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            expand_deep(items, config)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the tokens of the item once expanded, or `None` if it can't be.
+fn expansion(item: &Item, config: &Config) -> Option<String> {
+    let mut item = item.clone();
+    expand_deep(std::slice::from_mut(&mut item), config).ok()?;
+    Some(item.into_token_stream().to_string())
+}
+
+/// Removes the attributes of the macro from the items of the module, at any depth,
+/// returning whether there were any.
+fn remove_macros(items: &mut [Item]) -> bool {
+    let mut removed = false;
+
+    for item in items {
+        if let Some(attrs) = attrs_mut(item) {
+            let len = attrs.len();
+            attrs.retain(|attr| !is_macro(attr) || is_helper(attr));
+            removed |= attrs.len() < len;
+        }
+
+        if let Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            removed |= remove_macros(items);
+        }
+    }
+
+    removed
+}
+
+/// Returns the module with `recursive` added to its attribute, and the attributes of
+/// the macro on its items removed, if its attribute does not say whether it is
+/// recursive and there are such items.
+fn with_recursive(item: &Item, config: &Config) -> Result<Option<Item>> {
+    let mut module = item.clone();
+    let Item::Mod(ItemMod {
+        attrs,
+        content: Some((_, items)),
+        ..
+    }) = &mut module
+    else {
+        return Ok(None);
+    };
+
+    let Some(mut args) = take_attribute(&mut attrs.clone())? else {
+        return Ok(None);
+    };
+    args.apply(config)?;
+    if args.recursive.is_some() {
+        return Ok(None);
+    }
+
+    let Some(attr) = attrs.iter_mut().find(|attr| is_macro(attr)) else {
+        return Ok(None);
+    };
+    let path = attr.path().clone();
+    let tokens = match &attr.meta {
+        Meta::List(list) if !list.tokens.is_empty() => {
+            let tokens = &list.tokens;
+            quote!(#tokens, recursive)
+        }
+        _ => quote!(recursive),
+    };
+    attr.meta = Meta::List(MetaList {
+        path,
+        delimiter: MacroDelimiter::Paren(Default::default()),
+        tokens: tokens as TokenStream2,
+    });
+
+    Ok(remove_macros(items).then_some(module))
+}
+
+/// Fixes the annotated item, checking its expansion stays the same after each fix.
+fn fix_item(item: &mut Item, config: &Config) -> Result<()> {
+    let Some(expected) = expansion(item, config) else {
+        return Ok(());
+    };
+    let keeps = |candidate: &Item| expansion(candidate, config).as_ref() == Some(&expected);
+
+    if let Some(recursive) = with_recursive(item, config)? {
+        if keeps(&recursive) {
+            *item = recursive;
+        }
+    }
+
+    // The exclusions that change nothing, tried one after the other.
+    let mut target = 0;
+    loop {
+        let mut candidate = item.clone();
+        let mut visitor = Visitor::new(RemoveHelper { target, seen: 0 }).recursive(true);
+        visitor.visit_item_mut(&mut candidate)?;
+        if visitor.into_policy().seen <= target {
+            break;
+        }
+
+        match keeps(&candidate) {
+            true => *item = candidate,
+            false => target += 1,
+        }
+    }
+
+    // The visibilities the macro gives anyway.
+    let mut linted = item.clone();
+    if let Some(mut args) = attrs_mut(&mut linted)
+        .map(take_attribute)
+        .transpose()?
+        .flatten()
+    {
+        args.apply(config)?;
+        let recursive = args.recursive.unwrap_or(false);
+        let spans = lint(&args, &mut linted)?;
+
+        let mut candidate = item.clone();
+        let ranges = spans.iter().map(|span| span.byte_range()).collect();
+        let mut visitor = Visitor::new(RemoveVisibilities(ranges)).recursive(recursive);
+        visitor.visit_item_mut(&mut candidate)?;
+
+        if keeps(&candidate) {
+            *item = candidate;
+        }
+    }
+
+    Ok(())
+}
+
+/// Fixes the annotated items of the list, and those of the inline modules it contains.
+fn fix_all(items: &mut [Item], config: &Config) -> Result<()> {
+    for item in items {
+        let is_annotated = |attr: &Attribute| is_macro(attr) && !is_helper(attr);
+        if attrs_mut(item).is_some_and(|attrs| attrs.iter().any(is_annotated)) {
+            fix_item(item, config)?;
+        }
+
+        if let Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            fix_all(items, config)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrites the uses of the macro in a source file that can be written shorter,
+/// without changing how the items expand with the defaults of `config`:
+///
+/// * the visibilities of members the macro gives the same visibility anyway, which
+///   the `redundant_pub` lint warns about, are removed,
+/// * the `#[fully_pub(exclude)]` attributes that change nothing are removed,
+/// * a module whose items are all annotated one by one is made `recursive` instead.
+///
+/// Each fix is only kept if the expansion of the item stays exactly the same.
+///
+/// ```
+/// let source = "\
+/// #[fully_pub::fully_pub]
+/// struct Point {
+///     pub x: f32,
+///     #[fully_pub(exclude)]
+///     y: f32,
+///     #[fully_pub(exclude)]
+///     pub z: f32,
+/// }
+///
+/// #[fully_pub::fully_pub]
+/// mod shapes {
+///     #[fully_pub::fully_pub]
+///     struct Circle { radius: f32 }
+/// }
+/// ";
+///
+/// let fixed = fully_pub_core::rewrite::fix_source(source, &Default::default()).unwrap();
+///
+/// assert_eq!(fixed, "\
+/// #[fully_pub::fully_pub]
+/// struct Point {
+///     x: f32,
+///     #[fully_pub(exclude)]
+///     y: f32,
+///     z: f32,
+/// }
+///
+/// #[fully_pub::fully_pub(recursive)]
+/// mod shapes {
+///     struct Circle { radius: f32 }
+/// }
+/// ");
+/// ```
+pub fn fix_source(source: &str, config: &Config) -> Result<String> {
+    rewrite(source, |file| fix_all(&mut file.items, config))
+}
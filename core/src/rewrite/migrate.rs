@@ -0,0 +1,444 @@
+//! Migration of items written with explicit visibilities to the macro.
+
+use quote::quote;
+use syn::punctuated;
+use syn::*;
+
+use super::rewrite;
+use crate::explore::is_marker;
+use crate::source::{expand_annotated, members};
+use crate::{Args, Config, CRATE_NAME};
+
+/// The attribute added to migrated items.
+const ATTRIBUTE: &str = "#[fully_pub::fully_pub]";
+
+/// The attribute added to migrated modules, whose items the macro only publishes
+/// when recursive.
+const RECURSIVE: &str = "#[fully_pub::fully_pub(recursive)]";
+
+/// The attribute added to the members of migrated items left as written.
+const EXCLUDE: &str = "#[fully_pub(exclude)]";
+
+/// The attribute added to the private members of migrated modules whose own members
+/// are published.
+const EXCLUDE_SELF: &str = "#[fully_pub(exclude(self))]";
+
+/// The attribute added to the members of migrated modules whose own members are left
+/// as written.
+const EXCLUDE_MEMBERS: &str = "#[fully_pub(exclude(members))]";
+
+/// What the migration does to a member.
+#[derive(Clone, Copy)]
+enum Change {
+    /// Nothing, for the fields of marker types, which the macro leaves untouched.
+    Untouched,
+    /// Removes its `pub`, if it has one, and migrates its members.
+    Publish,
+    /// Adds `#[fully_pub(exclude)]`, leaving it and its members as written.
+    Exclude,
+    /// Adds `#[fully_pub(exclude(self))]`, and migrates its members.
+    ExcludeSelf,
+    /// Removes its `pub` and adds `#[fully_pub(exclude(members))]`, leaving its
+    /// members as written.
+    ExcludeMembers,
+}
+
+/// A member the macro publishes, with the members it publishes in turn when it is
+/// recursive.
+struct Member<'a> {
+    /// The visibility of the member, `None` for the blocks, which have none.
+    vis: Option<&'a mut Visibility>,
+    attrs: &'a mut Vec<Attribute>,
+    /// Whether the member is a field of a marker type.
+    is_marker: bool,
+    members: Vec<Member<'a>>,
+    change: Change,
+}
+
+impl<'a> Member<'a> {
+    fn new(vis: Option<&'a mut Visibility>, attrs: &'a mut Vec<Attribute>) -> Self {
+        Member {
+            vis,
+            attrs,
+            is_marker: false,
+            members: Vec::new(),
+            change: Change::Publish,
+        }
+    }
+
+    /// Returns the member view of the item, if the macro publishes it.
+    fn of(item: &'a mut Item, marker_types: &[String]) -> Option<Self> {
+        let of_fields = |fields: punctuated::IterMut<'a, Field>| {
+            let fields = fields.map(|field| Member {
+                is_marker: is_marker(Some(&field.ty), marker_types),
+                ..Member::new(Some(&mut field.vis), &mut field.attrs)
+            });
+            fields.collect()
+        };
+
+        let (vis, attrs, members) = match item {
+            Item::Const(ItemConst { vis, attrs, .. })
+            | Item::Enum(ItemEnum { vis, attrs, .. })
+            | Item::Fn(ItemFn { vis, attrs, .. })
+            | Item::Static(ItemStatic { vis, attrs, .. })
+            | Item::Trait(ItemTrait { vis, attrs, .. })
+            | Item::TraitAlias(ItemTraitAlias { vis, attrs, .. })
+            | Item::Type(ItemType { vis, attrs, .. }) => (Some(vis), attrs, Vec::new()),
+            Item::Struct(ItemStruct {
+                vis, attrs, fields, ..
+            }) => (Some(vis), attrs, of_fields(fields.iter_mut())),
+            Item::Union(ItemUnion {
+                vis, attrs, fields, ..
+            }) => (Some(vis), attrs, of_fields(fields.named.iter_mut())),
+            Item::Impl(ItemImpl {
+                attrs,
+                trait_: None,
+                items,
+                ..
+            }) => {
+                let members = items.iter_mut().filter_map(|item| match item {
+                    ImplItem::Const(ImplItemConst { vis, attrs, .. })
+                    | ImplItem::Fn(ImplItemFn { vis, attrs, .. })
+                    | ImplItem::Type(ImplItemType { vis, attrs, .. }) => {
+                        Some(Member::new(Some(vis), attrs))
+                    }
+                    _ => None,
+                });
+                (None, attrs, members.collect())
+            }
+            Item::ForeignMod(ItemForeignMod { attrs, items, .. }) => {
+                let members = items.iter_mut().filter_map(|item| match item {
+                    ForeignItem::Fn(ForeignItemFn { vis, attrs, .. })
+                    | ForeignItem::Static(ForeignItemStatic { vis, attrs, .. })
+                    | ForeignItem::Type(ForeignItemType { vis, attrs, .. }) => {
+                        Some(Member::new(Some(vis), attrs))
+                    }
+                    _ => None,
+                });
+                (None, attrs, members.collect())
+            }
+            // The macro leaves the declarations of the modules in their own file as
+            // written.
+            Item::Mod(ItemMod {
+                vis,
+                attrs,
+                content: Some((_, items)),
+                ..
+            }) => {
+                let items = items.iter_mut();
+                let members = items.filter_map(|item| Member::of(item, marker_types));
+                (Some(vis), attrs, members.collect())
+            }
+            _ => return None,
+        };
+
+        Some(Member {
+            members,
+            ..Member::new(vis, attrs)
+        })
+    }
+
+    /// Returns `true` if the attributes of the member or of its members already
+    /// mention the macro.
+    fn uses_macro(&self) -> bool {
+        uses_macro(self.attrs) || self.members.iter().any(Member::uses_macro)
+    }
+
+    /// Returns how much longer the members make the source once migrated, negative
+    /// if shorter, choosing their cheapest changes.
+    fn plan_members(&mut self) -> isize {
+        self.members.iter_mut().map(Member::plan).sum()
+    }
+
+    /// Chooses the cheapest change of the member, and of its members, that the macro
+    /// expands to the visibilities as written. Returns how much longer it makes the
+    /// source, negative if shorter.
+    fn plan(&mut self) -> isize {
+        if self.is_marker {
+            self.change = Change::Untouched;
+            return 0;
+        }
+
+        let has_members = !self.members.is_empty();
+        let members = self.plan_members();
+        let len = |attribute: &str| attribute.len() as isize;
+        let public = "pub ".len() as isize;
+
+        let options: &[(Change, isize)] = match self.vis.as_deref() {
+            // Blocks have no visibility, so `exclude` leaves their members as written.
+            None => &[(Change::Publish, members), (Change::Exclude, len(EXCLUDE))],
+            Some(Visibility::Public(_)) if has_members => &[
+                (Change::Publish, members - public),
+                (Change::ExcludeMembers, len(EXCLUDE_MEMBERS) - public),
+            ],
+            Some(Visibility::Public(_)) => &[(Change::Publish, -public)],
+            Some(Visibility::Inherited) if has_members => &[
+                (Change::Exclude, len(EXCLUDE)),
+                (Change::ExcludeSelf, len(EXCLUDE_SELF) + members),
+            ],
+            // The macro can't express restricted visibilities.
+            Some(_) => &[(Change::Exclude, len(EXCLUDE))],
+        };
+
+        let (change, cost) = *options.iter().min_by_key(|(_, cost)| *cost).unwrap();
+        self.change = change;
+        cost
+    }
+
+    /// Applies the changes chosen by [`Member::plan`].
+    fn apply(self) {
+        let Member {
+            vis,
+            attrs,
+            members,
+            change,
+            ..
+        } = self;
+
+        if let (Change::Publish | Change::ExcludeMembers, Some(vis)) = (change, vis) {
+            *vis = Visibility::Inherited;
+        }
+
+        match change {
+            Change::Untouched | Change::Publish => (),
+            Change::Exclude => attrs.push(parse_quote!(#[fully_pub(exclude)])),
+            Change::ExcludeSelf => attrs.push(parse_quote!(#[fully_pub(exclude(self))])),
+            Change::ExcludeMembers => attrs.push(parse_quote!(#[fully_pub(exclude(members))])),
+        }
+
+        if let Change::Publish | Change::ExcludeSelf = change {
+            members.into_iter().for_each(Member::apply);
+        }
+    }
+}
+
+/// An item whose members the macro can publish.
+struct Container<'a> {
+    member: Member<'a>,
+    /// Whether the item is a module, whose items the macro only publishes when
+    /// recursive.
+    is_module: bool,
+}
+
+impl<'a> Container<'a> {
+    /// Returns the container view of the item, if it has members the macro publishes.
+    fn of(item: &'a mut Item, marker_types: &[String]) -> Option<Self> {
+        let is_module = match item {
+            Item::Struct(_) | Item::Union(_) | Item::ForeignMod(_) => false,
+            Item::Impl(ItemImpl { trait_: None, .. }) => false,
+            Item::Mod(ItemMod {
+                content: Some(_), ..
+            }) => true,
+            _ => return None,
+        };
+
+        Some(Container {
+            member: Member::of(item, marker_types)?,
+            is_module,
+        })
+    }
+
+    /// Migrates the item to the macro if it would be shorter, and the macro can
+    /// express its visibilities exactly. Returns whether the item was migrated.
+    fn migrate(self) -> bool {
+        let Container {
+            mut member,
+            is_module,
+        } = self;
+
+        // The macro can't keep the item itself private.
+        let itself = match member.vis.as_deref() {
+            Some(Visibility::Public(_)) => "pub ".len() as isize,
+            Some(_) => return false,
+            None => 0,
+        };
+
+        let attribute = match is_module {
+            true => RECURSIVE,
+            false => ATTRIBUTE,
+        };
+        if attribute.len() as isize + member.plan_members() - itself >= 0 {
+            return false;
+        }
+
+        // The attribute goes after the documentation of the item.
+        let index = member
+            .attrs
+            .iter()
+            .take_while(|attr| attr.path().is_ident("doc"))
+            .count();
+        let attribute = match is_module {
+            true => parse_quote!(#[fully_pub::fully_pub(recursive)]),
+            false => parse_quote!(#[fully_pub::fully_pub]),
+        };
+        member.attrs.insert(index, attribute);
+
+        member.change = Change::Publish;
+        member.apply();
+        true
+    }
+}
+
+/// Returns `true` if the attributes already mention the macro.
+fn uses_macro(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path()
+            .segments
+            .first()
+            .is_some_and(|segment| segment.ident == CRATE_NAME)
+    })
+}
+
+/// Returns the visibilities of the item and of its members, as written.
+fn visibilities(item: &Item) -> Vec<String> {
+    members(item)
+        .into_iter()
+        .map(|member| {
+            let vis = member.vis;
+            quote!(#vis).to_string()
+        })
+        .collect()
+}
+
+/// Migrates the items of the list, and those of the inline modules it contains that
+/// are not migrated as a whole.
+fn migrate_all(items: &mut [Item], config: &Config) -> Result<()> {
+    let mut args = Args::default();
+    args.apply(config)?;
+    let marker_types = args.marker_types();
+
+    for item in items {
+        let mut migrated = item.clone();
+        let is_migrated = Container::of(&mut migrated, &marker_types)
+            .is_some_and(|container| !container.member.uses_macro() && container.migrate());
+
+        // The defaults of the configuration may give other visibilities than `pub`, or
+        // exclude some members by their names.
+        if is_migrated {
+            let mut expanded = migrated.clone();
+            expand_annotated(&mut expanded, config)?;
+
+            if visibilities(&expanded) == visibilities(item) {
+                *item = migrated;
+                continue;
+            }
+        }
+
+        if let Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            migrate_all(items, config)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Rewrites the items of a source file that are written with explicit `pub`
+/// visibilities to use the macro instead, with `#[fully_pub(exclude)]` on their
+/// private members.
+///
+/// Only `struct`s, `union`s, inherent `impl` blocks, `extern` blocks and inline
+/// modules are migrated, and only when the macro expresses their visibilities
+/// exactly and the result is shorter than the explicit visibilities. The fields of
+/// marker types, which the macro leaves untouched, don't need an exclusion. The
+/// migrated items are checked to expand to the same visibilities with the defaults
+/// of `config`.
+///
+/// ```
+/// let source = "\
+/// pub struct Color<Space> {
+///     pub r: u8,
+///     pub g: u8,
+///     pub b: u8,
+///     pub a: u8,
+///     pub name: String,
+///     space: PhantomData<Space>,
+/// }
+/// ";
+///
+/// let migrated = fully_pub_core::rewrite::migrate_source(source, &Default::default()).unwrap();
+///
+/// assert_eq!(migrated, "\
+/// #[fully_pub::fully_pub]
+/// struct Color<Space> {
+///     r: u8,
+///     g: u8,
+///     b: u8,
+///     a: u8,
+///     name: String,
+///     space: PhantomData<Space>,
+/// }
+/// ");
+/// ```
+///
+/// Modules get `#[fully_pub::fully_pub(recursive)]`, and their members written
+/// with other visibilities `exclude`, `exclude(self)` or `exclude(members)`,
+/// whichever is shorter. The module of the file itself is not migrated, for
+/// attributes on `mod` declarations are unstable, only its items are:
+///
+/// ```
+/// let source = "\
+/// pub mod shapes {
+///     pub struct Circle {
+///         pub x: f64,
+///         pub y: f64,
+///         pub radius: f64,
+///     }
+///
+///     impl Circle {
+///         pub fn area(&self) -> f64 {
+///             std::f64::consts::PI * self.radius * self.radius
+///         }
+///
+///         pub fn perimeter(&self) -> f64 {
+///             2.0 * std::f64::consts::PI * self.radius
+///         }
+///
+///         pub fn contains(&self, x: f64, y: f64) -> bool {
+///             (x - self.x).hypot(y - self.y) <= self.radius
+///         }
+///     }
+///
+///     pub fn unit() -> Circle {
+///         Circle { x: 0.0, y: 0.0, radius: 1.0 }
+///     }
+/// }
+/// ";
+///
+/// let migrated = fully_pub_core::rewrite::migrate_source(source, &Default::default()).unwrap();
+///
+/// assert_eq!(migrated, "\
+/// #[fully_pub::fully_pub(recursive)]
+/// mod shapes {
+///     struct Circle {
+///         x: f64,
+///         y: f64,
+///         radius: f64,
+///     }
+///
+///     impl Circle {
+///         fn area(&self) -> f64 {
+///             std::f64::consts::PI * self.radius * self.radius
+///         }
+///
+///         fn perimeter(&self) -> f64 {
+///             2.0 * std::f64::consts::PI * self.radius
+///         }
+///
+///         fn contains(&self, x: f64, y: f64) -> bool {
+///             (x - self.x).hypot(y - self.y) <= self.radius
+///         }
+///     }
+///
+///     fn unit() -> Circle {
+///         Circle { x: 0.0, y: 0.0, radius: 1.0 }
+///     }
+/// }
+/// ");
+/// ```
+pub fn migrate_source(source: &str, config: &Config) -> Result<String> {
+    rewrite(source, |file| migrate_all(&mut file.items, config))
+}
@@ -0,0 +1,567 @@
+//! A lightweight expansion of the macro, working on the tokens of the item without
+//! parsing them into a syntax tree.
+//!
+//! Most items given to the macro are plain structs, enums and `impl` blocks, for
+//! which parsing everything down to the expressions of the function bodies is wasted
+//! work: finding where the members start and where their visibility goes is enough.
+//! The scanner recognizes these positions and splices the visibilities of the
+//! arguments in, leaving every other token as it was.
+//!
+//! Whatever the scanner does not recognize, or can't handle as the explorer does, is
+//! left to the full parser, which is always correct and reports the errors.
+
+use std::cell::Cell;
+
+use proc_macro2::{Delimiter, Group, Ident, Spacing, TokenStream as TokenStream2, TokenTree};
+use quote::quote;
+
+use crate::bounds;
+use crate::config::Level;
+use crate::explore::matches;
+use crate::splice::{attribute_tokens, relocate, visibility_len};
+use crate::{Args, CRATE_NAME};
+
+/// What the explorer does to an item, depending on its kind.
+enum Kind<'a> {
+    /// Gives the visibility to the item, whose name is given.
+    Plain(&'a Ident),
+    /// Gives the visibility to the struct or union, then to its fields, which are in
+    /// the group at the index.
+    Fields(&'a Ident, usize),
+    /// Gives the visibility to the module, then to its items if recursive, which are
+    /// in the group at the index.
+    Mod(&'a Ident, usize),
+    /// Gives the visibility to the members of the `impl` or `extern` block, which are
+    /// in the group at the index.
+    Block(usize),
+    /// Leaves the item as written, helper attributes included.
+    Untouched,
+}
+
+/// Returns `true` if the token is the identifier.
+fn is_ident(tree: Option<&TokenTree>, name: &str) -> bool {
+    matches!(tree, Some(TokenTree::Ident(ident)) if ident == name)
+}
+
+/// Returns `true` if the token is the punctuation.
+fn is_punct(tree: Option<&TokenTree>, ch: char) -> bool {
+    matches!(tree, Some(TokenTree::Punct(punct)) if punct.as_char() == ch)
+}
+
+/// Returns `true` if the token is a group with the delimiter.
+fn is_group(tree: Option<&TokenTree>, delimiter: Delimiter) -> bool {
+    matches!(tree, Some(TokenTree::Group(group)) if group.delimiter() == delimiter)
+}
+
+/// Returns the index of the first token from `start` on which `stop` holds and which
+/// is not between angle brackets, or `None` if there is none.
+///
+/// Generics are not grouped by the tokenizer, so the angle brackets are counted,
+/// without mistaking the arrow of `Fn() -> T` for a closing one.
+fn find(tokens: &[TokenTree], start: usize, stop: impl Fn(&TokenTree) -> bool) -> Option<usize> {
+    let mut depth = 0usize;
+
+    for (index, tree) in tokens.iter().enumerate().skip(start) {
+        if depth == 0 && stop(tree) {
+            return Some(index);
+        }
+
+        if let TokenTree::Punct(punct) = tree {
+            match punct.as_char() {
+                '<' => depth += 1,
+                '>' => {
+                    let prev = index.checked_sub(1).and_then(|prev| tokens.get(prev));
+                    let is_arrow = matches!(
+                        prev,
+                        Some(TokenTree::Punct(prev))
+                            if prev.as_char() == '-' && prev.spacing() == Spacing::Joint
+                    );
+                    if !is_arrow {
+                        depth = depth.checked_sub(1)?;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    None
+}
+
+/// Returns the index of the `;` ending the item, which can't be nested in anything
+/// but a group.
+fn semicolon(tokens: &[TokenTree], start: usize) -> Option<usize> {
+    (start..tokens.len()).find(|&index| is_punct(tokens.get(index), ';'))
+}
+
+/// Returns the end of the outer attributes starting at `start`.
+fn attributes_end(tokens: &[TokenTree], mut start: usize) -> usize {
+    while is_punct(tokens.get(start), '#') && is_group(tokens.get(start + 1), Delimiter::Bracket) {
+        start += 2;
+    }
+    start
+}
+
+/// Returns `true` if the attribute, given by the group following its `#`, is a helper
+/// attribute of the macro.
+///
+/// Returns `None` if it is another helper attribute, like `#[fully_pub(exclude(self))]`,
+/// which is left to the parser, as are the invalid ones it reports.
+fn is_helper(group: &TokenTree) -> Option<bool> {
+    let TokenTree::Group(group) = group else {
+        return Some(false);
+    };
+    let inner = attribute_tokens(group);
+
+    match inner.as_slice() {
+        [TokenTree::Ident(name), TokenTree::Group(args)]
+            if name == CRATE_NAME && args.delimiter() == Delimiter::Parenthesis =>
+        {
+            let args: Vec<_> = args.stream().into_iter().collect();
+            match args.as_slice() {
+                [TokenTree::Ident(arg)] if arg == "exclude" => Some(true),
+                _ => None,
+            }
+        }
+        [TokenTree::Ident(name), TokenTree::Punct(colon), ..]
+            if name == CRATE_NAME && colon.as_char() == ':' =>
+        {
+            Some(false)
+        }
+        [TokenTree::Ident(name), ..] if name == CRATE_NAME => None,
+        _ => Some(false),
+    }
+}
+
+/// Returns `true` if the inner attribute, given by the group following its `#!`, is
+/// `#![fully_pub::defaults(..)]`, whose arguments only the explorer reads.
+fn is_defaults(group: &TokenTree) -> bool {
+    let TokenTree::Group(group) = group else {
+        return false;
+    };
+    let inner = attribute_tokens(group);
+
+    matches!(
+        inner.as_slice(),
+        [TokenTree::Ident(name), TokenTree::Punct(a), TokenTree::Punct(b), TokenTree::Ident(path), ..]
+            if name == CRATE_NAME && a.as_char() == ':' && b.as_char() == ':' && path == "defaults"
+    )
+}
+
+/// Returns the end of the inner attributes starting the content of a module, or
+/// `None` if one of them is a helper attribute of the macro or its defaults.
+fn inner_attributes_end(tokens: &[TokenTree]) -> Option<usize> {
+    let mut start = 0;
+    while is_punct(tokens.get(start), '#')
+        && is_punct(tokens.get(start + 1), '!')
+        && is_group(tokens.get(start + 2), Delimiter::Bracket)
+    {
+        if is_helper(&tokens[start + 2]) != Some(false) || is_defaults(&tokens[start + 2]) {
+            return None;
+        }
+        start += 3;
+    }
+    Some(start)
+}
+
+/// Returns a group like `group`, but made of the tokens.
+fn regroup(group: &TokenTree, tokens: Vec<TokenTree>) -> TokenTree {
+    let TokenTree::Group(group) = group else {
+        unreachable!("only called on groups");
+    };
+    let mut regrouped = Group::new(group.delimiter(), tokens.into_iter().collect());
+    regrouped.set_span(group.span());
+    TokenTree::Group(regrouped)
+}
+
+/// Returns the tokens of the group.
+fn contents(group: &TokenTree) -> Vec<TokenTree> {
+    match group {
+        TokenTree::Group(group) => group.stream().into_iter().collect(),
+        _ => unreachable!("only called on groups"),
+    }
+}
+
+/// Returns the name of the identifier, without its `r#` prefix.
+fn unraw(ident: &Ident) -> String {
+    let name = ident.to_string();
+    match name.strip_prefix("r#") {
+        Some(name) => name.to_string(),
+        None => name,
+    }
+}
+
+/// Recognizes the kind of the item whose tokens start at `start`, after its
+/// visibility, and returns it with the end of the item.
+fn kind(tokens: &[TokenTree], start: usize) -> Option<(Kind<'_>, usize)> {
+    let ident = |index: usize| match tokens.get(index) {
+        Some(TokenTree::Ident(ident)) => Some(ident),
+        _ => None,
+    };
+    let is_brace = |tree: &TokenTree| is_group(Some(tree), Delimiter::Brace);
+    let is_body = |tree: &TokenTree| is_brace(tree) || is_punct(Some(tree), ';');
+
+    let mut index = start;
+    loop {
+        match ident(index)?.to_string().as_str() {
+            "unsafe" | "async" => index += 1,
+            "auto" if is_ident(tokens.get(index + 1), "trait") => index += 1,
+            "const"
+                if ident(index + 1).is_some_and(|next| {
+                    ["fn", "unsafe", "async", "extern"]
+                        .iter()
+                        .any(|kw| next == kw)
+                }) =>
+            {
+                index += 1
+            }
+            "extern" => match tokens.get(index + 1) {
+                Some(TokenTree::Ident(next)) if next == "crate" => {
+                    return Some((Kind::Untouched, semicolon(tokens, index)? + 1));
+                }
+                Some(TokenTree::Literal(_)) if is_brace(tokens.get(index + 2)?) => {
+                    return Some((Kind::Block(index + 2), index + 3));
+                }
+                Some(TokenTree::Literal(_)) => index += 2,
+                Some(tree) if is_brace(tree) => return Some((Kind::Block(index + 1), index + 2)),
+                _ => index += 1,
+            },
+            _ => break,
+        }
+    }
+
+    let keyword = ident(index)?;
+    let name = ident(index + 1);
+
+    Some(match keyword.to_string().as_str() {
+        "fn" | "trait" | "enum" => (Kind::Plain(name?), find(tokens, index + 2, is_body)? + 1),
+        "const" | "static" | "type" => {
+            // The name of a `static mut` comes after `mut`.
+            let name = match name {
+                Some(name) if name == "mut" && keyword == "static" => ident(index + 2)?,
+                name => name?,
+            };
+            (Kind::Plain(name), semicolon(tokens, index)? + 1)
+        }
+        "struct" | "union" if !is_punct(tokens.get(index + 1), '!') => {
+            let name = name?;
+            let is_fields = |tree: &TokenTree| {
+                is_body(tree)
+                    || is_group(Some(tree), Delimiter::Parenthesis)
+                    || is_ident(Some(tree), "where")
+            };
+            let mut fields = find(tokens, index + 2, is_fields)?;
+            if is_ident(tokens.get(fields), "where") {
+                fields = find(tokens, fields, is_body)?;
+            }
+
+            match &tokens[fields] {
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                    (Kind::Fields(name, fields), fields + 1)
+                }
+                // Tuple structs end with `;`, after their where clause.
+                TokenTree::Group(_) => (Kind::Fields(name, fields), semicolon(tokens, fields)? + 1),
+                _ => (Kind::Plain(name), fields + 1),
+            }
+        }
+        "mod" => match tokens.get(index + 2) {
+            Some(tree) if is_brace(tree) => (Kind::Mod(name?, index + 2), index + 3),
+            _ => (Kind::Untouched, semicolon(tokens, index)? + 1),
+        },
+        "impl" => {
+            let body = find(tokens, index + 1, is_brace)?;
+            let is_trait = (index + 1..body)
+                .any(|i| is_ident(tokens.get(i), "for") && !is_punct(tokens.get(i + 1), '<'));
+            match is_trait {
+                true => (Kind::Untouched, body + 1),
+                false => (Kind::Block(body), body + 1),
+            }
+        }
+        "use" => (Kind::Untouched, semicolon(tokens, index)? + 1),
+        _ => {
+            // A macro invocation, maybe by a path, maybe defining a `macro_rules!`.
+            let bang = (index..tokens.len()).find(|&i| {
+                !matches!(&tokens[i], TokenTree::Ident(_)) && !is_punct(tokens.get(i), ':')
+            })?;
+            if !is_punct(tokens.get(bang), '!') {
+                return None;
+            }
+            let group = bang + 1 + usize::from(ident(bang + 1).is_some());
+            match tokens.get(group)? {
+                tree if is_brace(tree) => (Kind::Untouched, group + 1),
+                TokenTree::Group(_) if is_punct(tokens.get(group + 1), ';') => {
+                    (Kind::Untouched, group + 2)
+                }
+                _ => return None,
+            }
+        }
+    })
+}
+
+/// Scans the tokens of an item, giving the visibility of the arguments to its members.
+struct Scanner<'a> {
+    /// The tokens of the visibility given to the members.
+    vis: Vec<TokenTree>,
+    recursive: bool,
+    exclude_names: &'a [String],
+    /// The names of the marker types, whose fields are left untouched.
+    marker_types: Vec<String>,
+    /// Whether a visibility or a helper attribute was changed.
+    changed: Cell<bool>,
+}
+
+impl Scanner<'_> {
+    /// Returns `true` if the name matches one of the patterns of the excluded names.
+    fn is_named(&self, ident: Option<&Ident>) -> bool {
+        !self.exclude_names.is_empty()
+            && ident.is_some_and(|ident| {
+                let name = unraw(ident);
+                self.exclude_names
+                    .iter()
+                    .any(|pattern| matches(pattern, &name))
+            })
+    }
+
+    /// Pushes the attributes to `out`, except for the `#[fully_pub(exclude)]` one,
+    /// returning whether it was found.
+    ///
+    /// Returns `None` if another helper attribute is found, or the same one twice.
+    fn attributes(&self, attrs: &[TokenTree], out: &mut Vec<TokenTree>) -> Option<bool> {
+        let mut found = false;
+
+        for attr in attrs.chunks(2) {
+            match is_helper(&attr[1])? {
+                true if found => return None,
+                true => {
+                    found = true;
+                    self.changed.set(true);
+                }
+                false => out.extend_from_slice(attr),
+            }
+        }
+
+        Some(found)
+    }
+
+    /// Pushes the visibility of a member to `out`: the one written if the member is
+    /// excluded or already has the visibility of the arguments, which is located at
+    /// `at` otherwise.
+    fn visibility(
+        &self,
+        written: &[TokenTree],
+        at: &TokenTree,
+        is_excluded: bool,
+        out: &mut Vec<TokenTree>,
+    ) {
+        let is_same = written.len() == self.vis.len()
+            && written
+                .iter()
+                .zip(&self.vis)
+                .all(|(a, b)| a.to_string() == b.to_string());
+
+        match is_excluded || is_same {
+            true => out.extend_from_slice(written),
+            false => {
+                out.extend(relocate(&self.vis, at.span()));
+                self.changed.set(true);
+            }
+        }
+    }
+
+    /// Scans the items of a module, or the members of a block if `is_block`.
+    fn items(&self, tokens: &[TokenTree], is_block: bool) -> Option<Vec<TokenTree>> {
+        let mut out = Vec::with_capacity(tokens.len());
+        let mut start = 0;
+
+        while start < tokens.len() {
+            start = self.item(tokens, start, is_block, &mut out)?;
+        }
+
+        Some(out)
+    }
+
+    /// Scans the fields of a struct or union, which are named if `is_named`.
+    fn fields(&self, tokens: &[TokenTree], is_named: bool) -> Option<Vec<TokenTree>> {
+        let mut out = Vec::with_capacity(tokens.len());
+        let mut start = 0;
+
+        while start < tokens.len() {
+            let attrs_end = attributes_end(tokens, start);
+            let vis_end = attrs_end + visibility_len(&tokens[attrs_end..]);
+
+            let name = match (is_named, tokens.get(vis_end)) {
+                (true, Some(TokenTree::Ident(name))) if is_punct(tokens.get(vis_end + 1), ':') => {
+                    Some(name)
+                }
+                (true, _) | (false, None) => return None,
+                (false, Some(_)) => None,
+            };
+            let end = find(tokens, vis_end, |tree| is_punct(Some(tree), ','))
+                .map_or(tokens.len(), |comma| comma + 1);
+
+            // Whether a type naming a marker is one is left to the parser.
+            let is_marker = tokens[vis_end..end].iter().any(|tree| {
+                matches!(tree, TokenTree::Ident(ident) if self.marker_types.contains(&unraw(ident)))
+            });
+            if is_marker {
+                return None;
+            }
+
+            let is_excluded = self.attributes(&tokens[start..attrs_end], &mut out)?;
+            let is_excluded = is_excluded || self.is_named(name);
+            self.visibility(
+                &tokens[attrs_end..vis_end],
+                &tokens[attrs_end],
+                is_excluded,
+                &mut out,
+            );
+            out.extend_from_slice(&tokens[vis_end..end]);
+
+            start = end;
+        }
+
+        Some(out)
+    }
+
+    /// Scans the item starting at `start`, pushing its tokens to `out`, and returns
+    /// its end.
+    fn item(
+        &self,
+        tokens: &[TokenTree],
+        start: usize,
+        is_block: bool,
+        out: &mut Vec<TokenTree>,
+    ) -> Option<usize> {
+        let attrs_end = attributes_end(tokens, start);
+        let vis_end = attrs_end + visibility_len(&tokens[attrs_end..]);
+        let (kind, end) = kind(tokens, vis_end)?;
+
+        // The members of blocks are all given the visibility, if they can have one.
+        if is_block && !matches!(kind, Kind::Plain(_) | Kind::Untouched) {
+            return None;
+        }
+
+        if let Kind::Untouched = kind {
+            out.extend_from_slice(&tokens[start..end]);
+            return Some(end);
+        }
+
+        let is_excluded = self.attributes(&tokens[start..attrs_end], out)?;
+        let (name, group) = match kind {
+            Kind::Plain(name) => (Some(name), None),
+            Kind::Fields(name, group) | Kind::Mod(name, group) => (Some(name), Some(group)),
+            Kind::Block(group) => (None, Some(group)),
+            Kind::Untouched => unreachable!(),
+        };
+        let is_excluded = is_excluded || self.is_named(name);
+
+        // Blocks have no visibility of their own.
+        match name {
+            Some(_) => self.visibility(
+                &tokens[attrs_end..vis_end],
+                &tokens[attrs_end],
+                is_excluded,
+                out,
+            ),
+            None => out.extend_from_slice(&tokens[attrs_end..vis_end]),
+        }
+
+        let Some(group) = group else {
+            out.extend_from_slice(&tokens[vis_end..end]);
+            return Some(end);
+        };
+        out.extend_from_slice(&tokens[vis_end..group]);
+
+        let inner = contents(&tokens[group]);
+        let scanned = match kind {
+            _ if is_excluded => None,
+            Kind::Fields(..) => {
+                let is_named = is_group(tokens.get(group), Delimiter::Brace);
+                Some(self.fields(&inner, is_named)?)
+            }
+            Kind::Mod(..) => {
+                let items_start = inner_attributes_end(&inner)?;
+                match self.recursive {
+                    true => {
+                        let mut scanned = inner[..items_start].to_vec();
+                        scanned.extend(self.items(&inner[items_start..], false)?);
+                        Some(scanned)
+                    }
+                    false => None,
+                }
+            }
+            _ => Some(self.items(&inner, true)?),
+        };
+
+        match scanned {
+            Some(scanned) => out.push(regroup(&tokens[group], scanned)),
+            None => out.push(tokens[group].clone()),
+        }
+        out.extend_from_slice(&tokens[group + 1..end]);
+
+        Some(end)
+    }
+}
+
+/// Expands the item given as tokens without parsing it, for the arguments once the
+/// configuration has been applied to them.
+///
+/// Returns `None` if the item must be expanded by parsing it instead, with
+/// [`expand`](crate::expand) and [`splice`](crate::splice): when it is not recognized,
+/// contains errors, or when the arguments ask for more than giving a visibility to
+/// the members, like an inventory, diagnostics or `exclude_impls_of`.
+///
+/// ```
+/// use fully_pub_core::{scan, Args};
+/// use proc_macro2::{Delimiter, Group, TokenTree};
+/// use quote::quote;
+///
+/// let item = "struct Point<T> { x: T, #[fully_pub(exclude)] y: T }".parse().unwrap();
+/// let scanned = scan(&Args::default(), item).unwrap();
+/// assert_eq!(scanned.to_string(), "pub struct Point < T > { pub x : T , y : T }");
+///
+/// // The helper attribute must be `#[fully_pub(exclude)]`, which the parser checks.
+/// let item = "struct Point { #[fully_pub(hide)] x: f32 }".parse().unwrap();
+/// assert!(scan(&Args::default(), item).is_none());
+///
+/// // Attributes given to `macro_rules!` as `#[$meta]` fragments are in invisible groups.
+/// let meta = Group::new(Delimiter::None, "fully_pub(exclude)".parse().unwrap());
+/// let attr = Group::new(Delimiter::Bracket, TokenTree::from(meta).into());
+/// let item = quote!(struct Point { x: f32, # #attr y: f32 });
+/// let scanned = scan(&Args::default(), item).unwrap();
+/// assert_eq!(scanned.to_string(), "pub struct Point { pub x : f32 , y : f32 }");
+/// ```
+pub fn scan(args: &Args, item: TokenStream2) -> Option<TokenStream2> {
+    if args.inventory
+        || args.non_constructible
+        || args.sync_serde_skip.is_some()
+        || args.lints.redundant_pub != Level::Allow
+        || !args.exclude_impls_of.is_empty()
+        || bounds::is_checked(args)
+    {
+        return None;
+    }
+
+    let vis = match &args.vis {
+        Some(vis) => quote!(#vis),
+        None => quote!(pub),
+    };
+    let scanner = Scanner {
+        vis: vis.into_iter().collect(),
+        recursive: args.recursive.unwrap_or(false),
+        exclude_names: args.exclude_names.as_deref().unwrap_or_default(),
+        marker_types: args.marker_types(),
+        changed: Cell::new(false),
+    };
+
+    let tokens: Vec<_> = item.into_iter().collect();
+    let mut out = Vec::with_capacity(tokens.len());
+    let end = scanner.item(&tokens, 0, false, &mut out)?;
+
+    // The tokens are given back as they came if there was nothing to change.
+    match (end == tokens.len(), scanner.changed.get()) {
+        (false, _) => None,
+        (true, true) => Some(out.into_iter().collect()),
+        (true, false) => Some(tokens.into_iter().collect()),
+    }
+}
@@ -0,0 +1,479 @@
+//! Loading of a crate's source tree, as seen after the expansion of `fully_pub`.
+
+#[cfg(feature = "files")]
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::result::Result;
+use std::{fmt, io};
+
+use proc_macro2::{Delimiter, Span, TokenTree};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::*;
+
+use crate::{expand, Args, Config, CRATE_NAME};
+
+/// An error met while loading a crate's sources.
+#[derive(Debug)]
+pub enum Error {
+    /// A file could not be read.
+    Io(PathBuf, io::Error),
+    /// A file could not be parsed, or contains an invalid use of the macro.
+    Syn(PathBuf, syn::Error),
+    /// No crate root could be found in the given directory.
+    NoCrateRoot(PathBuf),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(path, e) => write!(f, "{}: {e}", path.display()),
+            Error::Syn(path, e) => write!(f, "{}: {e}", path.display()),
+            Error::NoCrateRoot(dir) => {
+                write!(f, "no `src/lib.rs` or `src/main.rs` in {}", dir.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A module of the crate, with its items after the expansion of `fully_pub`.
+pub struct Module {
+    /// The name of the module, `crate` for the root.
+    pub name: String,
+    /// The visibility of the module, `pub` for the root.
+    pub vis: Visibility,
+    /// The file the module is defined in.
+    pub file: PathBuf,
+    /// The items of the module, excluding nested modules.
+    pub items: Vec<Item>,
+    /// The nested modules.
+    pub children: Vec<Module>,
+}
+
+impl Module {
+    /// Returns the files the module and its nested modules are defined in,
+    /// without duplicates.
+    pub fn files(&self) -> Vec<&Path> {
+        let mut files = vec![self.file.as_path()];
+
+        for child in &self.children {
+            for file in child.files() {
+                if !files.contains(&file) {
+                    files.push(file);
+                }
+            }
+        }
+
+        files
+    }
+}
+
+/// Something with a visibility: an item, a field or an associated item.
+pub struct Member {
+    /// The kind of member, like `struct` or `field`.
+    pub kind: &'static str,
+    /// The kind of member as rustdoc JSON names it, the variant of its `ItemEnum` in
+    /// snake case, like `struct_field` or `assoc_const`.
+    pub rustdoc_kind: &'static str,
+    /// The path of the member, relative to the item it was found in.
+    pub path: String,
+    /// The visibility of the member.
+    pub vis: Visibility,
+    /// The span of the name of the member, or of its type for tuple fields.
+    pub span: Span,
+    /// The span of the first token of the member after its attributes, where
+    /// attributes can be added to it.
+    pub start: Span,
+}
+
+/// Returns the span of the first token of the member, after its outer attributes.
+fn start(member: &impl ToTokens) -> Span {
+    let tokens: Vec<_> = member.to_token_stream().into_iter().collect();
+    let mut rest = &tokens[..];
+
+    while let [TokenTree::Punct(pound), TokenTree::Group(group), after @ ..] = rest {
+        if pound.as_char() != '#' || group.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        rest = after;
+    }
+
+    rest.first().map_or_else(Span::call_site, TokenTree::span)
+}
+
+/// Returns the members of the item: the item itself, its fields or associated items,
+/// and the members of the items of inline modules.
+pub fn members(item: &Item) -> Vec<Member> {
+    let mut members = Vec::new();
+    collect_members(item, "", &mut members);
+    members
+}
+
+/// Collects the members of the item, prefixing their paths with `prefix`.
+fn collect_members(item: &Item, prefix: &str, members: &mut Vec<Member>) {
+    let mut push =
+        |(kind, rustdoc_kind), name: &dyn fmt::Display, vis: &Visibility, span, start| {
+            members.push(Member {
+                kind,
+                rustdoc_kind,
+                path: format!("{prefix}{name}"),
+                vis: vis.clone(),
+                span,
+                start,
+            })
+        };
+
+    let (kind, ident, vis) = match item {
+        Item::Const(ItemConst { ident, vis, .. }) => (("const", "constant"), ident, vis),
+        Item::Enum(ItemEnum { ident, vis, .. }) => (("enum", "enum"), ident, vis),
+        Item::Fn(ItemFn { sig, vis, .. }) => (("fn", "function"), &sig.ident, vis),
+        Item::Static(ItemStatic { ident, vis, .. }) => (("static", "static"), ident, vis),
+        Item::Trait(ItemTrait { ident, vis, .. }) => (("trait", "trait"), ident, vis),
+        Item::TraitAlias(ItemTraitAlias { ident, vis, .. }) => {
+            (("trait", "trait_alias"), ident, vis)
+        }
+        Item::Type(ItemType { ident, vis, .. }) => (("type", "type_alias"), ident, vis),
+        Item::ForeignMod(ItemForeignMod { items, .. }) => {
+            for item in items {
+                let (kind, ident, vis) = match item {
+                    ForeignItem::Fn(ForeignItemFn { sig, vis, .. }) => {
+                        (("fn", "function"), &sig.ident, vis)
+                    }
+                    ForeignItem::Static(ForeignItemStatic { ident, vis, .. }) => {
+                        (("static", "static"), ident, vis)
+                    }
+                    ForeignItem::Type(ForeignItemType { ident, vis, .. }) => {
+                        (("type", "extern_type"), ident, vis)
+                    }
+                    _ => continue,
+                };
+                push(kind, ident, vis, ident.span(), start(item));
+            }
+            return;
+        }
+        Item::Impl(ItemImpl {
+            self_ty,
+            trait_: None,
+            items,
+            ..
+        }) => {
+            let self_ty = match &**self_ty {
+                Type::Path(TypePath { path, .. }) => match path.segments.last() {
+                    Some(segment) => segment.ident.to_string(),
+                    None => return,
+                },
+                _ => return,
+            };
+
+            for item in items {
+                let (kind, ident, vis) = match item {
+                    ImplItem::Const(ImplItemConst { ident, vis, .. }) => {
+                        (("const", "assoc_const"), ident, vis)
+                    }
+                    ImplItem::Fn(ImplItemFn { sig, vis, .. }) => {
+                        (("fn", "function"), &sig.ident, vis)
+                    }
+                    ImplItem::Type(ImplItemType { ident, vis, .. }) => {
+                        (("type", "assoc_type"), ident, vis)
+                    }
+                    _ => continue,
+                };
+                push(
+                    kind,
+                    &format!("{self_ty}::{ident}"),
+                    vis,
+                    ident.span(),
+                    start(item),
+                );
+            }
+            return;
+        }
+        Item::Mod(ItemMod {
+            ident,
+            vis,
+            mod_token,
+            content,
+            ..
+        }) => {
+            // The tokens of the module are not collected, for they contain all its items.
+            let start = match vis {
+                Visibility::Inherited => mod_token.span,
+                vis => vis.span(),
+            };
+            push(("mod", "module"), ident, vis, ident.span(), start);
+
+            for item in content.iter().flat_map(|(_, items)| items) {
+                collect_members(item, &format!("{prefix}{ident}::"), members);
+            }
+            return;
+        }
+        Item::Struct(ItemStruct {
+            ident, vis, fields, ..
+        }) => {
+            push(("struct", "struct"), ident, vis, ident.span(), start(item));
+            push_fields(fields.iter(), &format!("{prefix}{ident}"), members);
+            return;
+        }
+        Item::Union(ItemUnion {
+            ident, vis, fields, ..
+        }) => {
+            push(("union", "union"), ident, vis, ident.span(), start(item));
+            push_fields(fields.named.iter(), &format!("{prefix}{ident}"), members);
+            return;
+        }
+        _ => return,
+    };
+
+    push(kind, ident, vis, ident.span(), start(item));
+}
+
+/// Pushes the fields as members, their paths prefixed by the path of their type.
+fn push_fields<'a>(fields: impl Iterator<Item = &'a Field>, ty: &str, members: &mut Vec<Member>) {
+    for (i, field) in fields.enumerate() {
+        let (path, span) = match &field.ident {
+            Some(ident) => (format!("{ty}::{ident}"), ident.span()),
+            None => (format!("{ty}::{i}"), field.ty.span()),
+        };
+
+        members.push(Member {
+            kind: "field",
+            rustdoc_kind: "struct_field",
+            path,
+            vis: field.vis.clone(),
+            span,
+            start: start(field),
+        });
+    }
+}
+
+/// Returns the path of the root file of the crate in `manifest_dir`.
+pub fn crate_root(manifest_dir: &Path) -> Result<PathBuf, Error> {
+    ["src/lib.rs", "src/main.rs"]
+        .into_iter()
+        .map(|file| manifest_dir.join(file))
+        .find(|path| path.is_file())
+        .ok_or_else(|| Error::NoCrateRoot(manifest_dir.to_path_buf()))
+}
+
+/// Loads the whole module tree of the crate whose root file is `root`,
+/// following `mod` declarations and expanding every `fully_pub` attribute met
+/// with the defaults of `config` for its file.
+#[cfg(feature = "files")]
+pub fn load_crate(root: &Path, config: &Config) -> Result<Module, Error> {
+    let dir = root.parent().unwrap_or(Path::new("")).to_path_buf();
+    let items = parse_file(root, config)?;
+
+    load_module(
+        "crate".to_string(),
+        Visibility::Public(Default::default()),
+        root,
+        &dir,
+        items,
+        config,
+    )
+}
+
+/// Reads and parses a file, expanding the `fully_pub` attributes in it.
+#[cfg(feature = "files")]
+fn parse_file(path: &Path, config: &Config) -> Result<Vec<Item>, Error> {
+    let source = fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+    let mut file = syn::parse_file(&source).map_err(|e| Error::Syn(path.to_path_buf(), e))?;
+
+    expand_all(&mut file.items, &config.in_file(path))
+        .map_err(|e| Error::Syn(path.to_path_buf(), e))?;
+
+    Ok(file.items)
+}
+
+/// Removes the `#[fully_pub]` attribute from the list, returning its arguments if
+/// it was present.
+///
+/// Both `#[fully_pub]` and `#[fully_pub::fully_pub]` are recognized.
+pub fn take_attribute(attrs: &mut Vec<Attribute>) -> syn::Result<Option<Args>> {
+    let Some(index) = attrs.iter().position(|attr| is_macro_path(attr.path())) else {
+        return Ok(None);
+    };
+
+    match attrs.remove(index).meta {
+        Meta::Path(_) => Ok(Some(Args::default())),
+        Meta::List(list) => list.parse_args().map(Some),
+        meta @ Meta::NameValue(_) => bail!(meta, "expected `#[{CRATE_NAME}(...)]`"),
+    }
+}
+
+/// Returns `true` if the path names the macro.
+fn is_macro_path(path: &syn::Path) -> bool {
+    let segments: Vec<_> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+
+    matches!(
+        segments.as_slice(),
+        [name] | [_, name] if name == CRATE_NAME && segments[0] == CRATE_NAME
+    )
+}
+
+/// Returns the attributes of the item, if it can have any.
+pub fn attrs_mut(item: &mut Item) -> Option<&mut Vec<Attribute>> {
+    match item {
+        Item::Const(ItemConst { attrs, .. })
+        | Item::Enum(ItemEnum { attrs, .. })
+        | Item::ExternCrate(ItemExternCrate { attrs, .. })
+        | Item::Fn(ItemFn { attrs, .. })
+        | Item::ForeignMod(ItemForeignMod { attrs, .. })
+        | Item::Impl(ItemImpl { attrs, .. })
+        | Item::Macro(ItemMacro { attrs, .. })
+        | Item::Mod(ItemMod { attrs, .. })
+        | Item::Static(ItemStatic { attrs, .. })
+        | Item::Struct(ItemStruct { attrs, .. })
+        | Item::Trait(ItemTrait { attrs, .. })
+        | Item::TraitAlias(ItemTraitAlias { attrs, .. })
+        | Item::Type(ItemType { attrs, .. })
+        | Item::Union(ItemUnion { attrs, .. })
+        | Item::Use(ItemUse { attrs, .. }) => Some(attrs),
+        _ => None,
+    }
+}
+
+/// Expands the item if it is annotated with the macro, returning whether it was.
+///
+/// The arguments left out of the attribute are taken from `config`.
+pub fn expand_annotated(item: &mut Item, config: &Config) -> syn::Result<bool> {
+    let Some(mut args) = attrs_mut(item).map(take_attribute).transpose()?.flatten() else {
+        return Ok(false);
+    };
+
+    args.apply(config)?;
+    expand(&args, item)?;
+    Ok(true)
+}
+
+/// Expands every annotated item of the list, looking for annotations inside
+/// inline modules too, expanded or not: the compiler expands the attributes an
+/// expansion leaves in a module, like those of the items of a module annotated
+/// without `recursive`.
+///
+/// ```
+/// use quote::ToTokens;
+///
+/// let mut file: syn::File = syn::parse_quote! {
+///     #[fully_pub]
+///     mod shapes {
+///         #[fully_pub]
+///         struct Circle { radius: f64 }
+///         struct Square { side: f64 }
+///     }
+/// };
+///
+/// fully_pub_core::source::expand_all(&mut file.items, &Default::default()).unwrap();
+///
+/// assert_eq!(
+///     file.into_token_stream().to_string(),
+///     "pub mod shapes { pub struct Circle { pub radius : f64 } struct Square { side : f64 } }",
+/// );
+/// ```
+pub fn expand_all(items: &mut [Item], config: &Config) -> syn::Result<()> {
+    for item in items {
+        expand_annotated(item, config)?;
+
+        if let Item::Mod(ItemMod {
+            content: Some((_, items)),
+            ..
+        }) = item
+        {
+            expand_all(items, config)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds a module out of its expanded items, loading the nested file modules.
+///
+/// `dir` is the directory in which the files of nested modules are looked for.
+#[cfg(feature = "files")]
+fn load_module(
+    name: String,
+    vis: Visibility,
+    file: &Path,
+    dir: &Path,
+    items: Vec<Item>,
+    config: &Config,
+) -> Result<Module, Error> {
+    let mut module = Module {
+        name,
+        vis,
+        file: file.to_path_buf(),
+        items: Vec::new(),
+        children: Vec::new(),
+    };
+
+    for item in items {
+        let Item::Mod(ItemMod {
+            attrs,
+            vis,
+            ident,
+            content,
+            ..
+        }) = item
+        else {
+            module.items.push(item);
+            continue;
+        };
+
+        let path_attr = attrs.iter().find_map(|attr| match &attr.meta {
+            Meta::NameValue(MetaNameValue {
+                path,
+                value:
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(lit), ..
+                    }),
+                ..
+            }) if path.is_ident("path") => Some(lit.value()),
+            _ => None,
+        });
+
+        let child = match content {
+            Some((_, items)) => {
+                let dir = dir.join(path_attr.as_deref().unwrap_or(&ident.to_string()));
+                load_module(ident.to_string(), vis, file, &dir, items, config)?
+            }
+            None => {
+                let path = match &path_attr {
+                    Some(path) => dir.join(path),
+                    None => {
+                        let flat = dir.join(format!("{ident}.rs"));
+                        if flat.is_file() {
+                            flat
+                        } else {
+                            dir.join(ident.to_string()).join("mod.rs")
+                        }
+                    }
+                };
+
+                // Files loaded through `#[path]` behave as `mod.rs` files.
+                let child_dir = if path_attr.is_some() || is_mod_rs(&path) {
+                    path.parent().unwrap_or(Path::new("")).to_path_buf()
+                } else {
+                    path.with_extension("")
+                };
+
+                let items = parse_file(&path, config)?;
+                load_module(ident.to_string(), vis, &path, &child_dir, items, config)?
+            }
+        };
+
+        module.children.push(child);
+    }
+
+    Ok(module)
+}
+
+/// Returns `true` if the file owns its directory, like `mod.rs` or a crate root.
+#[cfg(feature = "files")]
+fn is_mod_rs(path: &Path) -> bool {
+    matches!(
+        path.file_name().and_then(|name| name.to_str()),
+        Some("mod.rs" | "lib.rs" | "main.rs")
+    )
+}
@@ -0,0 +1,462 @@
+//! Splicing of the changes made by the expansion into the tokens the macro was given.
+//!
+//! Re-emitting the parsed item would rebuild every token from the syntax tree, which
+//! loses what the tree does not record, like invisible groups left by `macro_rules!`.
+//! Instead, the tokens of the expanded item are compared with the original ones,
+//! which are kept wherever they are unchanged: only the visibilities that differ and
+//! the helper attributes that were removed are spliced.
+
+use std::mem;
+
+use proc_macro2::{Delimiter, Group, Punct, Spacing, Span, TokenStream as TokenStream2, TokenTree};
+use quote::ToTokens;
+use syn::Item;
+
+use crate::private::PRIVATE_NAME;
+use crate::CRATE_NAME;
+
+/// Returns the number of tokens of the visibility at the start of the tokens, `0` if
+/// there is none.
+pub(crate) fn visibility_len(tokens: &[TokenTree]) -> usize {
+    match tokens {
+        [TokenTree::Ident(ident), TokenTree::Group(group), ..]
+            if ident == "pub" && group.delimiter() == Delimiter::Parenthesis =>
+        {
+            // As for syn, `pub (crate::Type)` is a public field of a tuple struct.
+            let inner: Vec<_> = group.stream().into_iter().collect();
+            let is_restricted = match inner.as_slice() {
+                [TokenTree::Ident(only)] => only == "crate" || only == "self" || only == "super",
+                [TokenTree::Ident(first), ..] => first == "in",
+                _ => false,
+            };
+            if is_restricted {
+                2
+            } else {
+                1
+            }
+        }
+        [TokenTree::Ident(ident), ..] if ident == "pub" => 1,
+        // A visibility passed to `macro_rules!` as a `$vis:vis` fragment, maybe empty.
+        [TokenTree::Group(group), ..] if group.delimiter() == Delimiter::None => {
+            let inner: Vec<_> = group.stream().into_iter().collect();
+            match inner.is_empty() || visibility_len(&inner) == inner.len() {
+                true => 1,
+                false => 0,
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Returns the tokens of an attribute, given by the brackets following its `#`.
+///
+/// An attribute passed to `macro_rules!` as a `#[$meta]` fragment is seen through the
+/// invisible group around it.
+pub(crate) fn attribute_tokens(brackets: &Group) -> Vec<TokenTree> {
+    let inner: Vec<_> = brackets.stream().into_iter().collect();
+    match inner.as_slice() {
+        [TokenTree::Group(meta)] if meta.delimiter() == Delimiter::None => {
+            meta.stream().into_iter().collect()
+        }
+        _ => inner,
+    }
+}
+
+/// Returns the number of tokens of the helper attribute of the macros at the start of
+/// the tokens, like `#[fully_pub(exclude)]`, `#[fully_private(include)]` or the inner
+/// `#![fully_pub::defaults(..)]` of a module, `0` if there is none.
+fn helper_attribute_len(tokens: &[TokenTree]) -> usize {
+    match tokens {
+        [TokenTree::Punct(pound), TokenTree::Group(group), ..]
+            if pound.as_char() == '#' && group.delimiter() == Delimiter::Bracket =>
+        {
+            let mut inner = attribute_tokens(group).into_iter();
+            let is_helper = matches!(
+                (inner.next(), inner.next()),
+                (Some(TokenTree::Ident(name)), Some(TokenTree::Group(_)))
+                    if name == CRATE_NAME || name == PRIVATE_NAME
+            );
+            match is_helper {
+                true => 2,
+                false => 0,
+            }
+        }
+        [TokenTree::Punct(pound), TokenTree::Punct(bang), TokenTree::Group(group), ..]
+            if pound.as_char() == '#'
+                && bang.as_char() == '!'
+                && group.delimiter() == Delimiter::Bracket =>
+        {
+            let inner = attribute_tokens(group);
+            let is_defaults = matches!(
+                inner.as_slice(),
+                [TokenTree::Ident(krate), TokenTree::Punct(a), TokenTree::Punct(b), TokenTree::Ident(name), ..]
+                    if krate == CRATE_NAME && a.as_char() == ':' && b.as_char() == ':' && name == "defaults"
+            );
+            match is_defaults {
+                true => 3,
+                false => 0,
+            }
+        }
+        _ => 0,
+    }
+}
+
+/// Returns `true` if the tokens start with a `serde` attribute, like the
+/// `#[serde(skip_serializing)]` added by `sync_serde_skip`, maybe applied by a
+/// `cfg_attr`.
+fn is_serde_attribute(tokens: &[TokenTree]) -> bool {
+    match tokens {
+        [TokenTree::Punct(pound), TokenTree::Group(group), ..]
+            if pound.as_char() == '#' && group.delimiter() == Delimiter::Bracket =>
+        {
+            let inner = attribute_tokens(group);
+            match inner.as_slice() {
+                [TokenTree::Ident(name), TokenTree::Group(_)] if name == "serde" => true,
+                [TokenTree::Ident(name), TokenTree::Group(args)] if name == "cfg_attr" => {
+                    let args: Vec<_> = args.stream().into_iter().collect();
+                    matches!(
+                        args.as_slice(),
+                        [.., TokenTree::Ident(name), TokenTree::Group(_)] if name == "serde"
+                    )
+                }
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if the two leaf tokens are the same. The spacing of punctuation
+/// is not compared, since syn does not always emit it as written, nor the text of
+/// literals, which syn keeps as written and which can't be compared without being
+/// formatted: the identifiers and punctuation around them are enough to find the
+/// differences the expansion makes.
+fn same_leaf(original: &TokenTree, expanded: &TokenTree) -> bool {
+    match (original, expanded) {
+        (TokenTree::Ident(a), TokenTree::Ident(b)) => a == b,
+        (TokenTree::Punct(a), TokenTree::Punct(b)) => a.as_char() == b.as_char(),
+        (TokenTree::Literal(_), TokenTree::Literal(_)) => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if the two token trees are the same, groups included.
+fn same_tree(original: &TokenTree, expanded: &TokenTree) -> bool {
+    match (original, expanded) {
+        (TokenTree::Group(a), TokenTree::Group(b)) => {
+            a.delimiter() == b.delimiter()
+                && a.stream()
+                    .into_iter()
+                    .zip(b.stream())
+                    .all(|(a, b)| same_tree(&a, &b))
+                && a.stream().into_iter().count() == b.stream().into_iter().count()
+        }
+        _ => same_leaf(original, expanded),
+    }
+}
+
+/// Returns the tokens with their locations set to the one of `at`, keeping the
+/// hygiene of the call site.
+pub(crate) fn relocate(tokens: &[TokenTree], at: Span) -> impl Iterator<Item = TokenTree> + '_ {
+    let span = Span::call_site().located_at(at);
+
+    tokens.iter().cloned().map(move |tree| match tree {
+        TokenTree::Group(group) => {
+            let stream = relocate(&group.stream().into_iter().collect::<Vec<_>>(), at).collect();
+            let mut relocated = Group::new(group.delimiter(), stream);
+            relocated.set_span(span);
+            TokenTree::Group(relocated)
+        }
+        mut tree => {
+            tree.set_span(span);
+            tree
+        }
+    })
+}
+
+/// Takes the token out of the list, leaving a placeholder behind, so that it is moved
+/// instead of cloned.
+fn take(tokens: &mut [TokenTree], index: usize) -> TokenTree {
+    mem::replace(
+        &mut tokens[index],
+        TokenTree::Punct(Punct::new('#', Spacing::Alone)),
+    )
+}
+
+/// Returns the delimiter, the span and the tokens of the group, dropping it so that
+/// the tokens are not shared anymore and can be moved out of the stream.
+fn into_parts(tree: TokenTree) -> (Delimiter, Span, TokenStream2) {
+    match tree {
+        TokenTree::Group(group) => (group.delimiter(), group.span(), group.stream()),
+        _ => unreachable!("only called on groups"),
+    }
+}
+
+/// An edit of the original tokens made by splicing: tokens of a level deleted, and
+/// others inserted in their place.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Edit {
+    /// The indices of the groups containing the level, from the outermost one.
+    pub(crate) path: Vec<usize>,
+    /// The index of the first deleted token, or of the token the inserted ones are
+    /// inserted before.
+    pub(crate) index: usize,
+    pub(crate) deleted: usize,
+    /// The inserted tokens, located at the token at `index`.
+    pub(crate) inserted: String,
+}
+
+/// Splices the tokens of the expanded item into the original ones, recording the
+/// edits it makes if asked to.
+#[derive(Default)]
+struct Splicer {
+    /// The indices of the groups containing the level being spliced.
+    path: Vec<usize>,
+    edits: Option<Vec<Edit>>,
+}
+
+impl Splicer {
+    /// Records that `deleted` tokens were replaced by the `inserted` ones at `index`.
+    fn record(&mut self, index: usize, deleted: usize, inserted: &[TokenTree]) {
+        if let Some(edits) = &mut self.edits {
+            edits.push(Edit {
+                path: self.path.clone(),
+                index,
+                deleted,
+                inserted: inserted
+                    .iter()
+                    .cloned()
+                    .collect::<TokenStream2>()
+                    .to_string(),
+            });
+        }
+    }
+
+    /// Splices the tokens of a level of the expanded item into the original ones, or
+    /// returns `None` if they differ in another way than the expansion does.
+    ///
+    /// Tokens are moved out of the streams, which are owned, rather than cloned: this
+    /// is the hot path of the macro on large items.
+    fn level(&mut self, original: TokenStream2, expanded: TokenStream2) -> Option<TokenStream2> {
+        let mut original: Vec<_> = original.into_iter().collect();
+        let mut expanded: Vec<_> = expanded.into_iter().collect();
+        let (mut i, mut j) = (0, 0);
+        let mut spliced = Vec::with_capacity(expanded.len());
+
+        while i < original.len() || j < expanded.len() {
+            let (rest, new) = (&original[i..], &expanded[j..]);
+            let (old_len, new_len) = (visibility_len(rest), visibility_len(new));
+
+            if old_len > 0 || new_len > 0 {
+                let (old_vis, new_vis) = (&rest[..old_len], &new[..new_len]);
+                let is_same = old_len == new_len
+                    && old_vis
+                        .iter()
+                        .zip(new_vis)
+                        .all(|(a, b)| a.to_string() == b.to_string());
+
+                match (is_same, rest.first()) {
+                    (true, _) => spliced.extend((i..i + old_len).map(|k| take(&mut original, k))),
+                    // Created visibilities are located at the member they are given to.
+                    (false, Some(at)) => spliced.extend(relocate(new_vis, at.span())),
+                    (false, None) => spliced.extend_from_slice(new_vis),
+                }
+                if !is_same {
+                    self.record(i, old_len, new_vis);
+                }
+
+                i += old_len;
+                j += new_len;
+                continue;
+            }
+
+            let helper_len = helper_attribute_len(rest);
+            if helper_len > 0 {
+                let is_kept = new.len() >= helper_len
+                    && rest[..helper_len]
+                        .iter()
+                        .zip(new)
+                        .all(|(a, b)| a.to_string() == b.to_string());
+
+                if !is_kept {
+                    self.record(i, helper_len, &[]);
+                    i += helper_len;
+                    continue;
+                }
+            }
+
+            // The `serde` attributes `sync_serde_skip` adds to the excluded fields.
+            if is_serde_attribute(new) && !(rest.len() >= 2 && same_tree(&rest[1], &new[1])) {
+                let inserted = new[..2].to_vec();
+                match rest.first() {
+                    Some(at) => spliced.extend(relocate(&inserted, at.span())),
+                    None => spliced.extend_from_slice(&inserted),
+                }
+                self.record(i, 0, &inserted);
+                j += 2;
+                continue;
+            }
+
+            // Parsing may drop the invisible group around a fragment, like a `$ty:ty`,
+            // whose tokens are then kept as they were since there is nothing to expand
+            // in them.
+            if let (Some(TokenTree::Group(old)), Some(first)) = (rest.first(), new.first()) {
+                let is_dropped = old.delimiter() == Delimiter::None
+                    && !matches!(first, TokenTree::Group(new) if new.delimiter() == Delimiter::None);
+
+                if is_dropped {
+                    let inner: Vec<_> = old.stream().into_iter().collect();
+
+                    if new.len() >= inner.len()
+                        && inner.iter().zip(new).all(|(a, b)| same_tree(a, b))
+                    {
+                        spliced.push(take(&mut original, i));
+                        i += 1;
+                        j += inner.len();
+                        continue;
+                    }
+                }
+            }
+
+            // The tokens added after the original ones, like the private field of a
+            // `non_constructible` struct, are inserted at the end of the level.
+            if rest.is_empty() {
+                let inserted = new.to_vec();
+                self.record(i, 0, &inserted);
+                spliced.extend(inserted);
+                break;
+            }
+
+            match (rest.first()?, new.first()?) {
+                (TokenTree::Group(old), TokenTree::Group(new))
+                    if old.delimiter() == new.delimiter() =>
+                {
+                    let (delimiter, span, old) = into_parts(take(&mut original, i));
+                    let (_, _, new) = into_parts(take(&mut expanded, j));
+
+                    self.path.push(i);
+                    let inner = self.level(old, new);
+                    self.path.pop();
+
+                    let mut group = Group::new(delimiter, inner?);
+                    group.set_span(span);
+                    spliced.push(TokenTree::Group(group));
+                }
+                (old, new) if same_leaf(old, new) => spliced.push(take(&mut original, i)),
+                _ => return None,
+            }
+
+            i += 1;
+            j += 1;
+        }
+
+        Some(spliced.into_iter().collect())
+    }
+}
+
+/// Returns the tokens of the expanded item, made of the `original` tokens the item
+/// was parsed from wherever the expansion did not change them.
+///
+/// If the expansion can't be spliced into the original tokens, which may happen when
+/// parsing the item normalized them, the tokens of the expanded item are returned.
+///
+/// ```
+/// use fully_pub_core::{expand, splice, Args};
+/// use proc_macro2::{TokenStream, TokenTree};
+///
+/// let original: TokenStream = "struct Meters(f64);".parse().unwrap();
+/// let mut item = syn::parse2(original.clone()).unwrap();
+/// expand(&Args::default(), &mut item).unwrap();
+///
+/// let spliced = splice(original, &item);
+/// assert_eq!(spliced.to_string(), "pub struct Meters (pub f64) ;");
+///
+/// // `Meters` is still the token of the source.
+/// let Some(TokenTree::Ident(name)) = spliced.into_iter().nth(2) else { panic!() };
+/// assert_eq!(name.span().byte_range(), 7..13);
+/// ```
+pub fn splice(original: TokenStream2, expanded: &Item) -> TokenStream2 {
+    splice_exact(original, expanded).unwrap_or_else(|| expanded.to_token_stream())
+}
+
+/// Like [`splice`], returning `None` instead of the tokens of the expanded item when
+/// the expansion can't be spliced into the original tokens.
+pub(crate) fn splice_exact(original: TokenStream2, expanded: &Item) -> Option<TokenStream2> {
+    Splicer::default().level(original, expanded.to_token_stream())
+}
+
+/// Like [`splice`], also returning the edits made to the original tokens, unless the
+/// expansion could not be spliced into them.
+pub(crate) fn splice_edits(
+    original: TokenStream2,
+    expanded: &Item,
+) -> (TokenStream2, Option<Vec<Edit>>) {
+    let mut splicer = Splicer {
+        path: Vec::new(),
+        edits: Some(Vec::new()),
+    };
+
+    match splicer.level(original, expanded.to_token_stream()) {
+        Some(spliced) => (spliced, splicer.edits),
+        None => (expanded.to_token_stream(), None),
+    }
+}
+
+/// Applies the edits to the tokens of a level, the ones of its groups included,
+/// returning `None` if they do not fit the tokens.
+///
+/// Only the groups containing edits are rebuilt, the other tokens are kept as they
+/// are, so that applying few edits to a large item is cheap.
+fn apply_level(original: TokenStream2, edits: &[Edit], depth: usize) -> Option<TokenStream2> {
+    let mut tokens: Vec<_> = original.into_iter().collect();
+    let (here, mut nested): (Vec<_>, Vec<_>) = edits.iter().partition(|e| e.path.len() == depth);
+
+    // The edits of a group are contiguous, since they were recorded in order.
+    while let Some(first) = nested.first() {
+        let index = first.path[depth];
+        let count = nested.iter().take_while(|e| e.path[depth] == index).count();
+        let group_edits: Vec<_> = nested.drain(..count).cloned().collect();
+
+        let TokenTree::Group(group) = tokens.get(index)? else {
+            return None;
+        };
+        let mut applied = Group::new(
+            group.delimiter(),
+            apply_level(group.stream(), &group_edits, depth + 1)?,
+        );
+        applied.set_span(group.span());
+        tokens[index] = TokenTree::Group(applied);
+    }
+
+    let mut applied = Vec::with_capacity(tokens.len());
+    let mut start = 0;
+    for edit in here {
+        let end = edit.index.checked_add(edit.deleted)?;
+        if edit.index < start || end > tokens.len() {
+            return None;
+        }
+
+        let inserted: Vec<_> = edit
+            .inserted
+            .parse::<TokenStream2>()
+            .ok()?
+            .into_iter()
+            .collect();
+        applied.extend_from_slice(&tokens[start..edit.index]);
+        match tokens.get(edit.index) {
+            Some(at) => applied.extend(relocate(&inserted, at.span())),
+            None => applied.extend(inserted),
+        }
+        start = end;
+    }
+    applied.extend_from_slice(&tokens[start..]);
+
+    Some(applied.into_iter().collect())
+}
+
+/// Applies the edits returned by [`splice_edits`] to the original tokens of the item,
+/// returning the same tokens as splicing, or `None` if they are not the edits of these
+/// tokens.
+pub(crate) fn apply(original: TokenStream2, edits: &[Edit]) -> Option<TokenStream2> {
+    apply_level(original, edits, 0)
+}
@@ -0,0 +1,66 @@
+use std::collections::BTreeSet;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+/// Records the external inputs a macro reads, so that the compiler rebuilds the
+/// crate when they change.
+///
+/// There is no stable way for a procedural macro to declare its inputs, so the
+/// tokens returned by [`Tracker::tokens`] go through `include_bytes!` for files and
+/// `option_env!` for environment variables, which the compiler does track.
+///
+/// ```
+/// use fully_pub_core::Tracker;
+///
+/// let mut tracker = Tracker::default();
+/// tracker.var("FULLY_PUB_EXAMPLE");
+///
+/// // The macros are named by their paths, which items of the user can't shadow.
+/// assert!(tracker.tokens().to_string().contains(":: core :: option_env !"));
+/// ```
+#[derive(Default)]
+pub struct Tracker {
+    files: BTreeSet<PathBuf>,
+    vars: BTreeSet<String>,
+}
+
+impl Tracker {
+    /// Records that the file was read. Relative paths are resolved against the
+    /// current directory.
+    pub fn file(&mut self, path: &Path) {
+        let path = env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf());
+
+        self.files.insert(path);
+    }
+
+    /// Reads the environment variable, recording that it was read.
+    pub fn var(&mut self, name: &str) -> Option<OsString> {
+        self.vars.insert(name.to_string());
+        env::var_os(name)
+    }
+
+    /// Returns the tokens to be emitted alongside the output of the macro for the
+    /// recorded inputs to be tracked, empty if there are none.
+    pub fn tokens(&self) -> TokenStream2 {
+        // Paths that are not valid UTF-8 can't be written in a string literal.
+        let files = self.files.iter().filter_map(|path| path.to_str());
+        let vars = &self.vars;
+
+        if self.files.is_empty() && vars.is_empty() {
+            return TokenStream2::new();
+        }
+
+        quote! {
+            const _: () = {
+                #(let _ = ::core::include_bytes!(#files);)*
+                #(let _ = ::core::option_env!(#vars);)*
+            };
+        }
+    }
+}
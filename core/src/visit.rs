@@ -0,0 +1,369 @@
+//! The traversal of items the macro does, for other macros to give their own
+//! visibilities to the members of items.
+//!
+//! A [`Visitor`] walks an item down to the members the macro would mark as `pub`: the
+//! items themselves, the fields of structs and unions, the members of inherent `impl`
+//! and `extern` blocks and, if recursive, the items of nested modules. A
+//! [`VisibilityPolicy`] decides what happens to each of them.
+//!
+//! ```
+//! use fully_pub_core::visit::{Decision, Member, VisibilityPolicy, Visitor};
+//! use quote::ToTokens;
+//!
+//! /// Gives `pub(crate)` to the members, except the ones whose name starts with `_`.
+//! struct CrateOnly;
+//!
+//! impl VisibilityPolicy for CrateOnly {
+//!     fn decide(&mut self, member: Member<'_>) -> syn::Result<Decision> {
+//!         Ok(match member.name {
+//!             Some(name) if name.to_string().starts_with('_') => Decision::Skip,
+//!             _ => Decision::Replace(syn::parse_quote!(pub(crate))),
+//!         })
+//!     }
+//! }
+//!
+//! let mut item = syn::parse_quote!(struct Handle { id: u32, _raw: i32 });
+//! Visitor::new(CrateOnly).visit_item_mut(&mut item).unwrap();
+//!
+//! assert_eq!(
+//!     item.to_token_stream().to_string(),
+//!     "pub (crate) struct Handle { pub (crate) id : u32 , _raw : i32 }",
+//! );
+//! ```
+
+use std::time::{Duration, Instant};
+
+use syn::token::Pub;
+use syn::*;
+
+/// Where a member is met by the visitor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MemberKind {
+    /// The visited item, or an item of a module visited recursively.
+    Item,
+    /// A field of a struct or union.
+    Field,
+    /// A member of an inherent `impl` block.
+    ImplItem,
+    /// A member of an `extern` block.
+    ForeignItem,
+}
+
+/// A member whose visibility a [`VisibilityPolicy`] decides.
+#[non_exhaustive]
+pub struct Member<'a> {
+    pub kind: MemberKind,
+    /// The attributes of the member, which the policy may change, for instance to
+    /// remove its helper attributes.
+    pub attrs: &'a mut Vec<Attribute>,
+    /// The name of the member, `None` for the fields of tuple structs.
+    pub name: Option<&'a Ident>,
+    /// The visibility written on the member.
+    pub vis: &'a Visibility,
+    /// The type of the member, only given for the fields of structs and unions.
+    pub ty: Option<&'a Type>,
+}
+
+/// What happens to a member and to its content: the fields of a struct or union, or
+/// the items of a module when the visitor is recursive.
+#[derive(Clone)]
+pub enum Decision {
+    /// Makes the member `pub`, and visits its content.
+    Publish,
+    /// Gives the visibility to the member, and visits its content.
+    Replace(Visibility),
+    /// Gives the visibility to the member, leaving its content untouched.
+    ReplaceOnly(Visibility),
+    /// Keeps the visibility of the member, and visits its content.
+    Keep,
+    /// Leaves the member and its content untouched.
+    Skip,
+}
+
+/// Decides the visibilities the [`Visitor`] gives to the members.
+pub trait VisibilityPolicy {
+    /// Decides what happens to the member.
+    fn decide(&mut self, member: Member<'_>) -> Result<Decision>;
+
+    /// Returns whether the members of an inherent `impl` block or of an `extern`
+    /// block with these attributes are visited. Defaults to `true`.
+    fn enter_block(&mut self, attrs: &mut Vec<Attribute>) -> Result<bool> {
+        let _ = attrs;
+        Ok(true)
+    }
+
+    /// Returns whether the members of an inherent `impl` block for `self_ty` with these
+    /// attributes are visited. Defaults to [`enter_block`](Self::enter_block).
+    fn enter_impl(&mut self, attrs: &mut Vec<Attribute>, self_ty: &Type) -> Result<bool> {
+        let _ = self_ty;
+        self.enter_block(attrs)
+    }
+
+    /// Called before the items of a module are visited, with the attributes of the
+    /// module, its inner attributes included, which the policy may change. Defaults to
+    /// doing nothing.
+    fn enter_mod(&mut self, attrs: &mut Vec<Attribute>) -> Result<()> {
+        let _ = attrs;
+        Ok(())
+    }
+
+    /// Called once the items of the module given to [`enter_mod`](Self::enter_mod) are
+    /// visited. Defaults to doing nothing.
+    fn leave_mod(&mut self) {}
+
+    /// Called once the fields of the struct are visited, with the fields, which the
+    /// policy may change. Defaults to doing nothing.
+    fn leave_struct(&mut self, ident: &Ident, fields: &mut Fields) -> Result<()> {
+        let _ = (ident, fields);
+        Ok(())
+    }
+}
+
+/// Walks items down to their members, giving them the visibilities the policy
+/// decides. Trait `impl` blocks, whose members can't have a visibility, are skipped,
+/// as are `use`, `extern crate` and macro items.
+pub struct Visitor<P> {
+    policy: P,
+    recursive: bool,
+    /// The time spent visiting each module of the visited one, if measured.
+    timings: Option<Vec<(String, Duration)>>,
+}
+
+impl<P: VisibilityPolicy> Visitor<P> {
+    /// Returns a visitor following the policy, which does not visit the items of
+    /// nested modules.
+    pub fn new(policy: P) -> Self {
+        Visitor {
+            policy,
+            recursive: false,
+            timings: None,
+        }
+    }
+
+    /// Sets whether the items of nested modules are visited, as with
+    /// `#[fully_pub(recursive)]`.
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Measures the time spent visiting each module of the visited one.
+    pub(crate) fn timed(mut self) -> Self {
+        self.timings = Some(Vec::new());
+        self
+    }
+
+    /// Returns the times measured since the visitor was made [`timed`](Self::timed).
+    pub(crate) fn take_timings(&mut self) -> Vec<(String, Duration)> {
+        self.timings.take().unwrap_or_default()
+    }
+
+    /// Returns the policy, with what it recorded during the visits.
+    pub fn into_policy(self) -> P {
+        self.policy
+    }
+
+    /// Asks the policy about the member, and applies its decision to the visibility.
+    ///
+    /// Returns whether the content of the member is to be visited.
+    fn member(
+        &mut self,
+        kind: MemberKind,
+        attrs: &mut Vec<Attribute>,
+        name: Option<&Ident>,
+        vis: &mut Visibility,
+        ty: Option<&Type>,
+    ) -> Result<bool> {
+        let member = Member {
+            kind,
+            attrs,
+            name,
+            vis,
+            ty,
+        };
+
+        match self.policy.decide(member)? {
+            Decision::Publish => *vis = Visibility::Public(Pub::default()),
+            Decision::Replace(replaced) => *vis = replaced,
+            Decision::ReplaceOnly(replaced) => {
+                *vis = replaced;
+                return Ok(false);
+            }
+            Decision::Keep => (),
+            Decision::Skip => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// Visits the fields of a struct or union.
+    fn fields<'a>(&mut self, fields: impl IntoIterator<Item = &'a mut Field>) -> Result<()> {
+        for Field {
+            vis,
+            attrs,
+            ident,
+            ty,
+            ..
+        } in fields
+        {
+            self.member(MemberKind::Field, attrs, ident.as_ref(), vis, Some(ty))?;
+        }
+
+        Ok(())
+    }
+
+    /// Visits the item and its members.
+    #[allow(clippy::collapsible_match)] // `member` mutates, it can't be a match guard
+    pub fn visit_item_mut(&mut self, item: &mut Item) -> Result<()> {
+        let kind = MemberKind::Item;
+
+        match item {
+            Item::Const(ItemConst {
+                vis, attrs, ident, ..
+            })
+            | Item::Enum(ItemEnum {
+                vis, attrs, ident, ..
+            })
+            | Item::Fn(ItemFn {
+                vis,
+                attrs,
+                sig: Signature { ident, .. },
+                ..
+            })
+            | Item::Static(ItemStatic {
+                vis, attrs, ident, ..
+            })
+            | Item::Trait(ItemTrait {
+                vis, attrs, ident, ..
+            })
+            | Item::TraitAlias(ItemTraitAlias {
+                vis, attrs, ident, ..
+            })
+            | Item::Type(ItemType {
+                vis, attrs, ident, ..
+            }) => {
+                self.member(kind, attrs, Some(ident), vis, None)?;
+            }
+            Item::ExternCrate(_) | Item::Macro(_) | Item::Use(_) => (),
+            Item::ForeignMod(ItemForeignMod { attrs, items, .. }) => {
+                if self.policy.enter_block(attrs)? {
+                    let kind = MemberKind::ForeignItem;
+
+                    for item in items {
+                        match item {
+                            ForeignItem::Fn(ForeignItemFn {
+                                vis,
+                                attrs,
+                                sig: Signature { ident, .. },
+                                ..
+                            })
+                            | ForeignItem::Static(ForeignItemStatic {
+                                vis, attrs, ident, ..
+                            })
+                            | ForeignItem::Type(ForeignItemType {
+                                vis, attrs, ident, ..
+                            }) => {
+                                self.member(kind, attrs, Some(ident), vis, None)?;
+                            }
+                            ForeignItem::Macro(_) => (),
+                            _ => (),
+                        }
+                    }
+                }
+            }
+            Item::Impl(ItemImpl {
+                attrs,
+                trait_,
+                self_ty,
+                items,
+                ..
+            }) => {
+                if trait_.is_none() && self.policy.enter_impl(attrs, self_ty)? {
+                    let kind = MemberKind::ImplItem;
+
+                    for item in items {
+                        match item {
+                            ImplItem::Const(ImplItemConst {
+                                vis, attrs, ident, ..
+                            })
+                            | ImplItem::Fn(ImplItemFn {
+                                vis,
+                                attrs,
+                                sig: Signature { ident, .. },
+                                ..
+                            })
+                            | ImplItem::Type(ImplItemType {
+                                vis, attrs, ident, ..
+                            }) => {
+                                self.member(kind, attrs, Some(ident), vis, None)?;
+                            }
+                            ImplItem::Macro(_) => (),
+                            _ => (),
+                        }
+                    }
+                }
+            }
+            Item::Mod(ItemMod {
+                vis,
+                attrs,
+                ident,
+                content: Some((_, content)),
+                ..
+            }) => {
+                if self.member(kind, attrs, Some(ident), vis, None)? && self.recursive {
+                    self.policy.enter_mod(attrs)?;
+
+                    // Nested modules are measured with the module containing them.
+                    let mut timings = self.timings.take();
+
+                    for item in content {
+                        let start = timings.is_some().then(Instant::now);
+                        self.visit_item_mut(item)?;
+
+                        if let (Some(timings), Some(start), Item::Mod(ItemMod { ident, .. })) =
+                            (&mut timings, start, &*item)
+                        {
+                            timings.push((ident.to_string(), start.elapsed()));
+                        }
+                    }
+
+                    self.timings = timings;
+                    self.policy.leave_mod();
+                }
+            }
+            Item::Struct(ItemStruct {
+                vis,
+                attrs,
+                ident,
+                fields,
+                ..
+            }) => {
+                if self.member(kind, attrs, Some(ident), vis, None)? {
+                    match &mut *fields {
+                        Fields::Named(FieldsNamed { named: fields, .. })
+                        | Fields::Unnamed(FieldsUnnamed {
+                            unnamed: fields, ..
+                        }) => self.fields(fields)?,
+                        Fields::Unit => (),
+                    }
+                    self.policy.leave_struct(ident, fields)?;
+                }
+            }
+            Item::Union(ItemUnion {
+                vis,
+                attrs,
+                ident,
+                fields: FieldsNamed { named: fields, .. },
+                ..
+            }) => {
+                if self.member(kind, attrs, Some(ident), vis, None)? {
+                    self.fields(fields)?;
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
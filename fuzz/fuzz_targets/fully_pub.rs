@@ -0,0 +1,37 @@
+//! Checks that the macro never panics, whatever the tokens it is given.
+//!
+//! Run it with `cargo fuzz run fully_pub` from this directory. The input is split at
+//! its first line break: the arguments of the attribute come before it, the item
+//! after it.
+
+#![no_main]
+
+use fully_pub_core::{diagnose, expand, lint, scan, splice, Args};
+use libfuzzer_sys::fuzz_target;
+use proc_macro2::TokenStream;
+use syn::Item;
+
+fuzz_target!(|input: &str| {
+    let (attr, item) = input.split_once('\n').unwrap_or(("", input));
+    let (Ok(attr), Ok(item)) = (attr.parse::<TokenStream>(), item.parse::<TokenStream>()) else {
+        return;
+    };
+
+    let _ = fully_pub_core::fully_pub(attr.clone(), item.clone());
+    let _ = fully_pub_core::fully_private(attr.clone(), item.clone());
+
+    // The parts of the attribute other macros and tools call on their own.
+    let Ok(args) = syn::parse2::<Args>(attr) else {
+        return;
+    };
+    let _ = scan(&args, item.clone());
+
+    let Ok(mut parsed) = syn::parse2::<Item>(item.clone()) else {
+        return;
+    };
+    let _ = diagnose(&args, &parsed);
+    let _ = lint(&args, &mut parsed.clone());
+    if expand(&args, &mut parsed).is_ok() {
+        let _ = splice(item, &parsed);
+    }
+});
@@ -1,206 +1,532 @@
 #![doc = include_str!("../README.md")]
 
-use std::mem;
+use std::panic::{self, AssertUnwindSafe};
 
 use proc_macro::TokenStream;
+#[cfg(feature = "files")]
 use quote::quote;
-use syn::token::Pub;
 use syn::*;
 
-const CRATE_NAME: &str = env!("CARGO_PKG_NAME");
-
-macro_rules! bail {
-    ($span: expr, $($arg:tt)*) => {
-        return Err(syn::Error::new_spanned($span, format!($($arg)*)))
-    }
-}
-
-/// Returns `Ok(true)` if the attributes list contains a `#[fully_pub(exclude)]` attribute,
-/// then remove it from the list.
-///
-/// If the attribute is ill-formatted or present more than once, returns an `Err`.
-fn is_exclude(attrs: &mut Vec<Attribute>) -> Result<bool> {
-    let mut is_exclude = false;
-
-    for attr in mem::take(attrs) {
-        if attr.path().is_ident(CRATE_NAME) {
-            let arg = attr.parse_args::<Ident>()?;
-
-            if arg != "exclude" {
-                bail!(&arg, "unknown {CRATE_NAME} attribute `{arg}`");
-            }
-
-            if is_exclude {
-                bail!(attr, "duplicate {CRATE_NAME} attribute `exclude`");
-            }
-
-            is_exclude = true;
-        } else {
-            attrs.push(attr);
-        }
-    }
-
-    Ok(is_exclude)
-}
-
-/// Sets this visibility to public.
-fn make_pub(vis: &mut Visibility) {
-    *vis = Visibility::Public(Pub::default());
-}
-
-/// Explore the item `recursively` (or not), making it's fields
-/// public.
-fn explore_item(item: &mut Item, recursive: bool) -> Result<()> {
-    match item {
-        Item::Const(ItemConst { vis, attrs, .. })
-        | Item::Enum(ItemEnum { vis, attrs, .. })
-        | Item::Fn(ItemFn { vis, attrs, .. })
-        | Item::Static(ItemStatic { vis, attrs, .. })
-        | Item::Trait(ItemTrait { vis, attrs, .. })
-        | Item::TraitAlias(ItemTraitAlias { vis, attrs, .. })
-        | Item::Type(ItemType { vis, attrs, .. }) => {
-            if !is_exclude(attrs)? {
-                make_pub(vis);
-            }
-        }
-        Item::ExternCrate(_) | Item::Macro(_) | Item::Use(_) => (),
-        Item::ForeignMod(ItemForeignMod { attrs, items, .. }) => {
-            if !is_exclude(attrs)? {
-                for item in items {
-                    match item {
-                        ForeignItem::Fn(ForeignItemFn { vis, attrs, .. })
-                        | ForeignItem::Static(ForeignItemStatic { vis, attrs, .. })
-                        | ForeignItem::Type(ForeignItemType { vis, attrs, .. }) => {
-                            if !is_exclude(attrs)? {
-                                make_pub(vis);
-                            }
-                        }
-                        ForeignItem::Macro(_) => (),
-                        _ => (),
-                    }
-                }
-            }
-        }
-        Item::Impl(ItemImpl {
-            attrs,
-            trait_,
-            items,
-            ..
-        }) => {
-            if trait_.is_none() && !is_exclude(attrs)? {
-                for item in items {
-                    match item {
-                        ImplItem::Const(ImplItemConst { vis, attrs, .. })
-                        | ImplItem::Fn(ImplItemFn { vis, attrs, .. })
-                        | ImplItem::Type(ImplItemType { vis, attrs, .. }) => {
-                            if !is_exclude(attrs)? {
-                                make_pub(vis);
-                            }
-                        }
-                        ImplItem::Macro(_) => (),
-                        _ => (),
-                    }
-                }
-            }
-        }
-        Item::Mod(ItemMod {
-            vis,
-            attrs,
-            content: Some((_, content)),
-            ..
-        }) => {
-            if !is_exclude(attrs)? {
-                make_pub(vis);
-
-                if recursive {
-                    for item in content {
-                        explore_item(item, recursive)?;
-                    }
-                }
-            }
-        }
-        Item::Struct(ItemStruct {
-            vis, attrs, fields, ..
-        }) => {
-            if !is_exclude(attrs)? {
-                make_pub(vis);
-
-                match fields {
-                    Fields::Named(FieldsNamed { named: fields, .. })
-                    | Fields::Unnamed(FieldsUnnamed {
-                        unnamed: fields, ..
-                    }) => {
-                        for Field { vis, attrs, .. } in fields {
-                            if !is_exclude(attrs)? {
-                                make_pub(vis);
-                            }
-                        }
-                    }
-                    Fields::Unit => (),
-                }
-            }
-        }
-        Item::Union(ItemUnion {
-            vis,
-            attrs,
-            fields: FieldsNamed { named: fields, .. },
-            ..
-        }) => {
-            if !is_exclude(attrs)? {
-                make_pub(vis);
-
-                for Field { vis, attrs, .. } in fields {
-                    if !is_exclude(attrs)? {
-                        make_pub(vis);
-                    }
-                }
-            }
-        }
-        _ => (),
-    }
-
-    Ok(())
-}
-
-/// Parse arguments to attr and then explore the item recursively,
-/// making its parts public.
-fn make_fully_pub(attr: Option<Ident>, item: &mut Item) -> Result<()> {
-    let recursive = match attr {
-        Some(ident) if ident == "recursive" => true,
-        Some(ident) => bail!(ident, "invalid argument to `{CRATE_NAME}` attribute macro"),
-        None => false,
-    };
-
-    explore_item(item, recursive)
-}
-
 /// Attribute macro that can be applied to any Rust item, and marks
 /// all of its content as [`pub`](https://doc.rust-lang.org/std/keyword.pub.html).
 ///
 /// Call it with the argument `recursive` to make it recursive over the content of
 /// a nested `mod`: like so `#[fully_pub(recursive)]`.
 ///
+/// Call it with the argument `inventory` to leave the item untouched, and instead
+/// get a warning reporting how many of the members that would have been marked as `pub`
+/// already are `pub`, `pub(crate)`, otherwise restricted or private. This is useful to get a
+/// picture of an existing module before migrating it to this macro. It can be combined
+/// with `recursive`: `#[fully_pub(inventory, recursive)]`.
+///
+/// Call it with `vis = pub(crate)` (or any other visibility) to give that visibility to
+/// the members instead of `pub`, and with `exclude_names = ["raw_*", "_*"]` to leave
+/// untouched the members whose names match one of the patterns, in which `*` stands for
+/// any sequence of characters.
+///
+/// Call it with `exclude_set = "secrets"` to also leave untouched the members whose
+/// names are in a set declared with the [`exclude_set!`] macro, for names that recur
+/// across items.
+///
+/// Call it with `group = "dto"` to use the arguments of a group declared with the
+/// [`group!`] macro, for the types that share them. Arguments given alongside take
+/// precedence over the ones of the group.
+///
+/// Call it with `exclude_impls_of(Scratch, DebugOnly)` to leave untouched the inherent
+/// `impl` blocks of the types with these names, wherever they are in a recursive
+/// module, for the types whose methods must stay internal.
+///
+/// The fields whose type is a marker, `PhantomData` or `PhantomPinned` whatever their
+/// path, are left untouched: they only tell the compiler about types and lifetimes,
+/// and are no part of the interface of a type. Call it with `markers` to give them the
+/// visibility too, and with `marker_types = ["PhantomData", "Tag"]` to change the names
+/// of the marker types:
+///
+/// ```
+/// mod ids {
+///     use std::marker::PhantomData;
+///
+///     #[fully_pub::fully_pub]
+///     struct Id<T> {
+///         value: u64,
+///         kind: PhantomData<T>,
+///     }
+///
+///     #[fully_pub::fully_pub(markers)]
+///     struct Key<T>(u64, PhantomData<T>);
+/// }
+///
+/// let key = ids::Key::<u8>(1, std::marker::PhantomData);
+/// ```
+///
+/// ```compile_fail
+/// # mod ids {
+/// #     use std::marker::PhantomData;
+/// #
+/// #     #[fully_pub::fully_pub]
+/// #     struct Id<T> {
+/// #         value: u64,
+/// #         kind: PhantomData<T>,
+/// #     }
+/// # }
+/// let id = ids::Id::<u8> { value: 1, kind: std::marker::PhantomData }; // error: `kind` is private
+/// ```
+///
+/// Call it with `non_constructible` to add a private `_priv: ()` field to the structs
+/// with named fields whose fields are all given a visibility other than `pub(self)`.
+/// Their fields can still be read and written, but the structs can't be built with a
+/// struct expression out of their module, so that fields can be added to them later
+/// without breaking the crates using them. They are built with the functions of their
+/// module instead. Tuple structs are left as they are, for their constructor and
+/// their patterns to keep working:
+///
+/// ```
+/// mod config {
+///     #[fully_pub::fully_pub(non_constructible)]
+///     #[derive(Default)]
+///     struct Options {
+///         verbose: bool,
+///         jobs: usize,
+///     }
+///
+///     #[fully_pub::fully_pub(non_constructible)]
+///     struct Range(usize, usize);
+///
+///     // There is nothing to protect with `pub(self)`, which leaves the fields private.
+///     #[fully_pub::fully_pub(non_constructible, vis = pub(self))]
+///     struct Limits {
+///         max: usize,
+///     }
+///
+///     fn limits() -> Limits {
+///         Limits { max: 8 }
+///     }
+/// }
+///
+/// let mut options = config::Options::default();
+/// options.jobs = 4;
+/// let config::Range(start, end) = config::Range(0, options.jobs);
+/// ```
+///
+/// ```compile_fail
+/// # mod config {
+/// #     #[fully_pub::fully_pub(non_constructible)]
+/// #     struct Options {
+/// #         verbose: bool,
+/// #         jobs: usize,
+/// #     }
+/// # }
+/// let options = config::Options { verbose: false, jobs: 4 }; // error: `_priv` is private
+/// ```
+///
+/// The field is hidden from the documentation, and skipped by `serde` in the structs
+/// deriving `Serialize` or `Deserialize`. It is still there in the module of the
+/// struct though, whose struct expressions must then give it, and whose patterns must
+/// either name it or end with `..`:
+///
+/// ```
+/// mod config {
+///     #[fully_pub::fully_pub(non_constructible)]
+///     struct Options {
+///         verbose: bool,
+///         jobs: usize,
+///     }
+///
+///     fn options(jobs: usize) -> Options {
+///         Options { verbose: false, jobs, _priv: () }
+///     }
+///
+///     fn jobs(options: &Options) -> usize {
+///         let Options { jobs, .. } = options;
+///         *jobs
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// mod config {
+///     #[fully_pub::fully_pub(non_constructible)]
+///     struct Options {
+///         verbose: bool,
+///         jobs: usize,
+///     }
+///
+///     fn options(jobs: usize) -> Options {
+///         Options { verbose: false, jobs } // error: missing field `_priv`
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// mod config {
+///     #[fully_pub::fully_pub(non_constructible)]
+///     struct Options {
+///         verbose: bool,
+///         jobs: usize,
+///     }
+///
+///     fn jobs(options: &Options) -> usize {
+///         let Options { verbose, jobs } = options; // error: pattern does not mention `_priv`
+///         *jobs
+///     }
+/// }
+/// ```
+///
+/// Call it with `sync_serde_skip` for the excluded fields of the structs deriving
+/// `Serialize` or `Deserialize` to also get `#[serde(skip_serializing)]`, since the
+/// fields left out of the interface are seldom meant to be serialized. Use
+/// `sync_serde_skip = "skip"` or `sync_serde_skip = "skip_deserializing"` to add this
+/// attribute instead. The fields that already have a `serde` attribute skipping them
+/// are left as written:
+///
+/// ```ignore
+/// #[fully_pub(sync_serde_skip)]
+/// #[derive(Serialize)]
+/// struct Account {
+///     login: String,
+///     #[fully_pub(exclude)]
+///     password_hash: String,
+/// }
+///
+/// // Expands to:
+/// #[derive(Serialize)]
+/// pub struct Account {
+///     pub login: String,
+///     #[serde(skip_serializing)]
+///     password_hash: String,
+/// }
+/// ```
+///
+/// Call it with `exact` to make sure the item is emitted with the tokens it was
+/// written with, see [Other Attribute Macros](#other-attribute-macros).
+///
 /// Does nothing on `extern crate`, `use` and `mod` statements.
 ///
 /// You can apply the `#[fully_pub(exclude)]` attribute to any content
 /// of an item to exclude it from being marked as `pub`, if it would have been
 /// otherwise.
+/// It leaves untouched both the visibility of the member and its content, like the
+/// fields of a struct or the items of a module. Use `#[fully_pub(exclude(self))]` to
+/// keep only the visibility of the member as written, and `#[fully_pub(exclude(members))]`
+/// to leave only its content untouched. Both can be given to the annotated item itself:
+///
+/// ```
+/// mod shapes {
+///     # use fully_pub::fully_pub;
+///     #[fully_pub]
+///     #[fully_pub(exclude(self))]
+///     pub(crate) struct Circle {
+///         radius: f64,
+///     }
+///
+///     #[fully_pub]
+///     #[fully_pub(exclude(members))]
+///     struct Square {
+///         side: f64,
+///     }
+///
+///     impl Square {
+///         pub fn new(side: f64) -> Self {
+///             Square { side }
+///         }
+///     }
+/// }
+///
+/// let circle = shapes::Circle { radius: 1.0 };
+/// let square = shapes::Square::new(circle.radius);
+/// ```
+///
+/// ```compile_fail
+/// mod shapes {
+///     # use fully_pub::fully_pub;
+///     #[fully_pub]
+///     #[fully_pub(exclude(members))]
+///     struct Square {
+///         side: f64,
+///     }
+/// }
+///
+/// let square = shapes::Square { side: 1.0 }; // error: `side` is private
+/// ```
+///
+/// Blocks have no visibility: on an `impl` or `extern` block, `exclude` and
+/// `exclude(members)` both leave its members untouched, and `exclude(self)` is an
+/// error.
+///
+/// In a module explored recursively, the inner attribute `#![fully_pub::defaults(..)]`
+/// sets the arguments for the items of the module, and of the modules it contains,
+/// in place of the ones of the attribute. It takes `vis`, `exclude_names`,
+/// `exclude_impls_of`, `markers` and `marker_types`, and is removed from the
+/// expansion. The module itself keeps the visibility of its parent. Anywhere else the
+/// macro leaves it, and the compiler rejects it:
+///
+/// ```
+/// #[fully_pub::fully_pub(recursive)]
+/// mod app {
+///     mod storage {
+///         #![fully_pub::defaults(vis = pub(crate), exclude_names = ["cache"])]
+///
+///         struct Store {
+///             path: String,
+///             cache: Vec<u8>,
+///         }
+///     }
+/// }
+///
+/// let path = |store: app::storage::Store| store.path;
+/// ```
+///
+/// ```compile_fail
+/// # #[fully_pub::fully_pub(recursive)]
+/// # mod app {
+/// #     mod storage {
+/// #         #![fully_pub::defaults(vis = pub(crate), exclude_names = ["cache"])]
+/// #
+/// #         struct Store {
+/// #             path: String,
+/// #             cache: Vec<u8>,
+/// #         }
+/// #     }
+/// # }
+/// let cache = |store: app::storage::Store| store.cache; // error: `cache` is private
+/// ```
 /// 
 /// # Exact Behaviour
 /// 
 /// This macro has the following behaviour depending on the kind of items it is applied on:
 /// 
 /// * `const`, `fn`, `static`, `trait` (and `trait` aliases) and `type` are all simply made `pub`.
-/// Nested items in a `fn` are not affected.
+///   Nested items in a `fn` are not affected.
 /// * `macro_rule`, `extern crate`, `mod` statements and `use` are left as-is.
 /// * `extern` modules will see all of their items (`const`, `fn` or `static`) made `pub`.
 /// * `impl` blocks (excluding `impl Trait` blocks) get all their items
-/// (`const`, `fn` or `static`) marked as `pub`
+///   (`const`, `fn` or `static`) marked as `pub`
 /// * `mod { /* ... */ }` are marked as `pub`, but their content is left untouched, unless
-/// the `(recursive)` argument is passed to the attribute, in which case all of their items will
-/// be marked `pub` recursively.
-/// * `struct` and `union` get marked `pub` along with all their fields.
+///   the `(recursive)` argument is passed to the attribute, in which case all of their items will
+///   be marked `pub` recursively.
+/// * `struct` and `union` get marked `pub` along with all their fields, except the ones of
+///   marker types.
+///
+/// The members of an `impl` block can be annotated one by one too. The warnings of the
+/// attribute are then emitted in the body of the functions, as items can't follow an
+/// associated item, and not at all for associated constants and types:
+///
+/// ```
+/// struct Circle {
+///     radius: f64,
+/// }
+///
+/// impl Circle {
+///     #[fully_pub::fully_pub]
+///     fn area(&self) -> f64 {
+///         3.0 * self.radius * self.radius
+///     }
+///
+///     #[fully_pub::fully_pub]
+///     const UNIT: f64 = 1.0;
+/// # }
+/// #
+/// # #[cfg(feature = "report")]
+/// # impl Circle {
+///
+///     #[fully_pub::fully_pub(inventory)]
+///     fn diameter(&self) -> f64 {
+///         2.0 * self.radius
+///     }
+/// }
+/// ```
 /// 
+/// # Configuration
+///
+/// Defaults for the whole crate can be set in its `Cargo.toml`:
+///
+/// ```toml
+/// [package.metadata.fully_pub]
+/// recursive = true          # every attribute behaves as `#[fully_pub(recursive)]`
+/// vis = "pub(crate)"        # the visibility given to the members
+/// exclude_names = ["_*"]    # the members left untouched
+/// exact = true              # every attribute behaves as `#[fully_pub(exact)]`
+/// markers = true            # every attribute behaves as `#[fully_pub(markers)]`
+/// marker_types = ["Tag"]    # the types whose fields are left untouched
+///
+/// [package.metadata.fully_pub.lints]
+/// redundant_pub = "warn"    # "allow" (the default), "warn" or "deny"
+/// private_bounds = "deny"   # "allow", "warn" (the default) or "deny"
+/// ```
+///
+/// The crates of a workspace can share defaults in the `[workspace.metadata.fully_pub]`
+/// table of its root `Cargo.toml`, which takes the same keys. Each key a crate sets in
+/// its own table replaces the one of the workspace.
+///
+/// The defaults can also depend on the module an attribute is in, for crates whose
+/// layers call for different policies. Rules apply to the modules whose path matches
+/// their pattern, in which `**` stands for any number of segments and `*` for any
+/// sequence of characters in a segment. When several rules match, the later ones in
+/// `Cargo.toml` take precedence:
+///
+/// ```toml
+/// [package.metadata.fully_pub.modules."internal::**"]
+/// vis = "pub(crate)"
+///
+/// [package.metadata.fully_pub.modules."api::**"]
+/// vis = "pub"
+/// recursive = true
+/// ```
+///
+/// The module of an attribute is deduced from the file it is written in, relative to
+/// `src`: attributes in modules declared inline follow the rules of their file.
+///
+/// Arguments given to an attribute override these defaults: `#[fully_pub(recursive = false)]`
+/// is not recursive, and `exclude_names` replaces the list of the configuration. The
+/// `redundant_pub` lint reports the visibilities written on members that the macro would
+/// give the same visibility anyway. The `private_bounds` lint reports the traits of a
+/// recursive module that the macro leaves less visible than the generic members it
+/// publishes, in their bounds, which the compiler would only warn about in the expanded
+/// code.
+///
+/// The `FULLY_PUB_VIS` environment variable, when set at build time, overrides the `vis`
+/// of the configuration: `FULLY_PUB_VIS="pub" cargo build` builds a variant of the crate in
+/// which the members are `pub` despite `vis = "pub(crate)"`. Attributes with a `vis`
+/// argument are not affected.
+///
+/// Crates with large generated items can set `cache = true` for the expansions to be
+/// stored in the `fully_pub` directory of their target directory, keyed by the tokens
+/// of the items and by the arguments of their attributes: the items that did not
+/// change are then not explored again by the following builds. The items whose
+/// expansion reports an `inventory` or lints are not cached, which includes the
+/// recursive modules unless `private_bounds = "allow"`.
+///
+/// The table is validated once per build: an unknown key or a value of the wrong type is
+/// reported, with its location in `Cargo.toml`, on the first attribute expanded.
+///
+/// The defaults for the whole crate can also be written in its root, with the
+/// [`config!`] macro, for them to be reviewed with the code.
+///
+/// # Other Attribute Macros
+///
+/// The macro gives back the tokens of the item as it was given them, spans and
+/// spacing included, except for the visibilities it changes and its helper
+/// attributes. The attributes of other macros on the item or its members, like
+/// `#[wasm_bindgen]`, `#[pyo3(get)]` or `#[async_trait]`, thus see the same tokens as
+/// if the macro had not been there, whether they come before or after it.
+///
+/// Parsing normalizes the tokens of a few rare constructs, like an empty `where`
+/// clause, and these items are emitted as parsed instead. With the `exact` argument,
+/// or `exact = true` in the configuration, they are an error instead, for the crates
+/// whose other macros depend on the exact tokens.
+///
+/// The code the macro adds next to the item, which tells the compiler what the
+/// expansion read and emits its warnings, only uses `core`, named by absolute paths: it
+/// compiles in `#![no_std]` crates without `alloc`, in `#![no_implicit_prelude]`
+/// modules, and next to items that shadow the ones of the prelude.
+///
+/// ```
+/// #[no_implicit_prelude]
+/// mod units {
+///     macro_rules! option_env {
+///         ($($tokens:tt)*) => { ::core::compile_error!("not the one of the prelude") };
+///     }
+///     # macro_rules! include_bytes {
+///     #     ($($tokens:tt)*) => { ::core::compile_error!("not the one of the prelude") };
+///     # }
+///
+///     #[::fully_pub::fully_pub]
+///     struct Meters(f64);
+/// }
+///
+/// let meters = units::Meters(1.0);
+/// ```
+///
+/// # Use in `macro_rules!`
+///
+/// The attribute can be written by declarative macros, with its arguments, the
+/// attributes of the item and the `#[fully_pub(exclude)]` helper attributes given as
+/// fragments like `$vis:vis` or `#[$attr:meta]`. The rules of the configuration are
+/// the ones of the module invoking the macro, as told by the file of the tokens it
+/// gives, like the name of a struct, rather than the ones of the module defining
+/// the macro:
+///
+/// ```
+/// mod shapes {
+///     macro_rules! shape {
+///         ($(#[$attr:meta])* $name:ident {
+///             $($(#[$field_attr:meta])* $field:ident: $ty:ty),*
+///         }) => {
+///             #[fully_pub::fully_pub(exact)]
+///             $(#[$attr])*
+///             struct $name { $($(#[$field_attr])* $field: $ty),* }
+///         };
+///     }
+///
+///     shape! {
+///         #[derive(Debug, Default)]
+///         Circle { radius: f64, #[fully_pub(exclude)] area: f64 }
+///     }
+/// }
+///
+/// let circle = shapes::Circle::default();
+/// assert_eq!(circle.radius, 0.0);
+/// ```
+///
+/// # Disabling the Macro
+///
+/// When the `FULLY_PUB_DISABLE` environment variable is set at build time, the macro
+/// leaves all visibilities as written, and only removes its `#[fully_pub(exclude)]`
+/// helper attributes. Changing it makes cargo rebuild the crates using the macro.
+///
+/// # Profiling
+///
+/// When the `FULLY_PUB_TIME_BUDGET` environment variable is set at build time to a
+/// number of milliseconds, the macro measures its expansions, and warns about the ones
+/// taking longer than that with the time of each of their phases and, for recursive
+/// ones, of the modules they contain: `FULLY_PUB_TIME_BUDGET=20 cargo build` tells
+/// whether the macro is what makes a build slow.
+///
+/// # Checking
+///
+/// When the `FULLY_PUB_CHECK` environment variable is set at build time, the macro skips
+/// what only warns: the `inventory`, the profiling with `FULLY_PUB_TIME_BUDGET`, and the
+/// `lints` set to `"warn"`. The `lints` set to `"deny"` still report their errors, so
+/// `FULLY_PUB_CHECK=1 cargo check` fails whenever `cargo build` would, sooner, except
+/// for builds that turn the warnings of the macro into errors, with `-D warnings` in
+/// `RUSTFLAGS` or with `#![deny(...)]` in the crate: the check skips the warnings, so
+/// it passes where such builds fail. Without denied lints, the `light` feature and the
+/// `cache` apply to every item. The macro behaves the same under rust-analyzer, for
+/// the editors to show the items as they are typed.
+///
+/// # Lighter Expansion
+///
+/// With the `light` feature, the macro expands the items it recognizes by scanning
+/// their tokens, without parsing them: the bodies of the functions, the types of the
+/// fields and everything else the macro does not change are skipped. The items it
+/// does not recognize, the ones containing errors and the attributes asking for an
+/// `inventory`, for the diagnostics of the `lints` or for `exclude_impls_of` are still
+/// parsed, so the feature only changes how long the expansion takes.
+///
+/// ```toml
+/// [dependencies]
+/// fully_pub = { version = "0.1", features = ["light"] }
+/// ```
+///
+/// # Features
+///
+/// The macros reading files and the reports are behind features enabled by default:
+///
+/// * `files` provides the configuration, read from `Cargo.toml` and from the
+///   [`config!`], [`group!`] and [`exclude_set!`] invocations of the crate root, and
+///   [`dir!`] and the `cache` of the configuration. Without it, the attribute reads no
+///   file and only its own arguments apply.
+/// * `report` provides the `inventory` argument and the profiling with
+///   `FULLY_PUB_TIME_BUDGET`.
+///
+/// Crates only using the attribute to rewrite visibilities can disable them, for the
+/// macro to build faster:
+///
+/// ```toml
+/// [dependencies]
+/// fully_pub = { version = "0.1", default-features = false }
+/// ```
+///
 /// # Examples
 ///
 /// ```
@@ -265,13 +591,271 @@ fn make_fully_pub(attr: Option<Ident>, item: &mut Item) -> Result<()> {
 ///     }
 /// }
 /// ```
+///
+/// ```
+/// use fully_pub::fully_pub;
+///
+/// #[fully_pub(vis = pub(crate), exclude_names = ["raw_*"])]
+/// struct Handle {
+///     id: u32,     // becomes `pub(crate)`
+///     raw_fd: i32, // stays private
+/// }
+/// ```
+///
+/// ```compile_fail
+/// #[fully_pub::fully_pub(recursive, exclude_impls_of(Scratch))]
+/// mod parser {
+///     struct Parser;
+///
+///     impl Parser {
+///         fn parse(&self) {}
+///     }
+///
+///     struct Scratch;
+///
+///     impl Scratch {
+///         fn clear(&mut self) {} // stays private
+///     }
+/// }
+///
+/// parser::Scratch.clear();
+/// ```
 #[proc_macro_attribute]
 pub fn fully_pub(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let attr = parse_macro_input!(attr as Option<Ident>);
-    let mut item = parse_macro_input!(item as Item);
+    attribute("fully_pub", || {
+        fully_pub_core::fully_pub(attr.into(), item.into()).map(Into::into)
+    })
+}
+
+/// Attribute macro that does the opposite of [`fully_pub`](macro@fully_pub): it removes
+/// the visibility of an item and of all of its content, be it `pub`, `pub(crate)` or
+/// otherwise restricted.
+///
+/// It explores items as `fully_pub` does, and is recursive over the content of a
+/// nested `mod` when called with the argument `recursive`: `#[fully_private(recursive)]`.
+/// This makes it easy to turn a public module into an internal one.
+///
+/// You can apply the `#[fully_private(include)]` attribute to any content of an item
+/// to leave it, and its own content, as it is written.
+///
+/// When the `FULLY_PUB_DISABLE` environment variable is set, it leaves all
+/// visibilities as written too, and only removes its helper attributes.
+///
+/// # Examples
+///
+/// ```
+/// mod shapes {
+///     use fully_pub::fully_private;
+///
+///     pub struct Circle {
+///         radius: f64,
+///     }
+///
+///     #[fully_private]
+///     impl Circle {
+///         pub(crate) fn squared_radius(&self) -> f64 {
+///             self.radius * self.radius
+///         }
+///
+///         #[fully_private(include)]
+///         pub fn area(&self) -> f64 {
+///             std::f64::consts::PI * self.squared_radius()
+///         }
+///     }
+///
+///     pub const UNIT: Circle = Circle { radius: 1.0 };
+/// }
+///
+/// assert!(shapes::UNIT.area() > 3.0);
+/// ```
+///
+/// ```compile_fail
+/// mod shapes {
+///     #[fully_pub::fully_private]
+///     pub struct Circle {
+///         pub radius: f64,
+///     }
+/// }
+///
+/// fn radius(circle: &shapes::Circle) -> f64 {
+///     circle.radius
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn fully_private(attr: TokenStream, item: TokenStream) -> TokenStream {
+    attribute("fully_private", || {
+        fully_pub_core::fully_private(attr.into(), item.into()).map(Into::into)
+    })
+}
+
+/// Runs the implementation of the attribute `name`, turning its error into a compile
+/// error.
+///
+/// The compiler reports a panicking macro as if it had crashed, without pointing at
+/// the item: should the fuzzed implementation still panic, it is turned into an error
+/// instead.
+fn attribute(name: &str, expand: impl FnOnce() -> Result<TokenStream>) -> TokenStream {
+    panic::catch_unwind(AssertUnwindSafe(expand))
+        .unwrap_or_else(|payload| {
+            let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+                (Some(message), _) => message,
+                (_, Some(message)) => message.as_str(),
+                _ => "unknown panic",
+            };
+            Err(Error::new(
+                proc_macro::Span::call_site().into(),
+                format!("internal error of `{name}`, please report it: {message}"),
+            ))
+        })
+        .unwrap_or_else(|e| e.into_compile_error().into())
+}
+
+/// Function-like macro that declares a `pub mod` for each Rust file of a directory,
+/// so that the list of modules is kept in sync with the filesystem.
+///
+/// The path is relative to the directory containing the `Cargo.toml` of the crate,
+/// and must be the directory in which the compiler looks for the modules declared
+/// by the file invoking the macro: `src/handlers` from `src/handlers.rs` or from
+/// `src/handlers/mod.rs`. Both `.rs` files (other than `mod.rs`) and directories with
+/// a `mod.rs` file are declared, in alphabetical order.
+///
+/// Pass `exclude(...)` to leave some modules out, for example the crate root when
+/// invoking the macro from `src/lib.rs`: `fully_pub::dir!("src", exclude(lib))`.
+///
+/// The modules whose names match the `exclude_names` of the configuration of the
+/// invoking file are declared private, as the macro leaves such members untouched.
+/// Modules named after a keyword, like `type.rs`, are declared with a raw identifier:
+/// `pub mod r#type;`.
+///
+/// When the `FULLY_PUB_DISABLE` environment variable is set, the modules are declared
+/// private. Cargo does not watch the directory itself, so adding or removing a file
+/// may require to touch the invoking file for the change to be picked up.
+///
+/// # Examples
+///
+/// ```ignore
+/// // In `src/handlers.rs`, with `src/handlers/{login,logout,legacy}.rs`:
+/// fully_pub::dir!("src/handlers", exclude(legacy));
+///
+/// // Expands to:
+/// pub mod login;
+/// pub mod logout;
+/// ```
+#[cfg(feature = "files")]
+#[proc_macro]
+pub fn dir(input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(input as fully_pub_core::DirArgs);
+    let mut tracker = fully_pub_core::Tracker::default();
+
+    let modules = fully_pub_core::dir(&args, &mut tracker);
+    let tracked = tracker.tokens();
+    match modules {
+        Ok(modules) => quote! { #modules #tracked }.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Function-like macro that declares named groups of arguments, which attributes use
+/// with `#[fully_pub(group = "...")]`, so that many items can share the same arguments
+/// without repeating them.
+///
+/// Groups take the same keys as [`config!`], and must be declared among the items of
+/// the crate root, by the full path of the macro. The attributes read them from there,
+/// so the invocation itself expands to nothing. The macro can be invoked several times,
+/// but the names of the groups must be unique.
+///
+/// # Examples
+///
+/// ```ignore
+/// // In `src/lib.rs`:
+/// fully_pub::group! {
+///     dto: { vis: pub, exclude_names: ["_*"] },
+///     internal: { vis: pub(crate), recursive: true },
+/// }
+///
+/// // Anywhere in the crate:
+/// #[fully_pub(group = "dto")]
+/// struct User {
+///     name: String,
+///     _cache: Option<String>,
+/// }
+/// ```
+#[proc_macro]
+pub fn group(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as fully_pub_core::GroupArgs);
+    let file = proc_macro::Span::call_site().local_file();
+
+    match fully_pub_core::group(file.as_deref()) {
+        Ok(()) => TokenStream::new(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Function-like macro that declares named sets of member names, which attributes leave
+/// untouched with `#[fully_pub(exclude_set = "...")]`, in addition to their
+/// `exclude_names`.
+///
+/// Sets contain names, or patterns in which `*` stands for any sequence of characters
+/// written as strings. Like groups, they must be declared among the items of the crate
+/// root, by the full path of the macro, and the invocation itself expands to nothing.
+///
+/// # Examples
+///
+/// ```ignore
+/// // In `src/lib.rs`:
+/// fully_pub::exclude_set!(secrets = [password, token, "*_key"]);
+///
+/// // Anywhere in the crate:
+/// #[fully_pub(exclude_set = "secrets")]
+/// struct Credentials {
+///     login: String,
+///     password: String,
+///     api_key: String,
+/// }
+///
+/// #[fully_pub(exclude_set = "secrets")]
+/// impl Credentials {
+///     fn login(&self) -> &str { &self.login }
+///     fn token(&self) -> String { todo!() }
+/// }
+/// ```
+#[proc_macro]
+pub fn exclude_set(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as fully_pub_core::ExcludeSetArgs);
+    let file = proc_macro::Span::call_site().local_file();
+
+    match fully_pub_core::exclude_set(file.as_deref()) {
+        Ok(()) => TokenStream::new(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Function-like macro that sets the defaults of the arguments of the attributes for
+/// the whole crate, as an alternative to the `[package.metadata.fully_pub]` table of
+/// its `Cargo.toml` (see the [`fully_pub`](macro@fully_pub) attribute).
+///
+/// It must be invoked once, by its full path, among the items of the crate root:
+/// `src/lib.rs`, or `src/main.rs` for a crate without a library. The attributes read
+/// it from there, so the invocation itself expands to nothing. The keys are the same as
+/// in `Cargo.toml`, and take precedence over the ones set in it.
+///
+/// # Examples
+///
+/// ```ignore
+/// // In `src/lib.rs`:
+/// fully_pub::config! {
+///     vis: pub(crate),
+///     recursive: true,
+///     exclude_names: ["_*"],
+/// }
+/// ```
+#[proc_macro]
+pub fn config(input: TokenStream) -> TokenStream {
+    parse_macro_input!(input as fully_pub_core::ConfigArgs);
+    let file = proc_macro::Span::call_site().local_file();
 
-    match make_fully_pub(attr, &mut item) {
-        Ok(_) => quote! { #item }.into(),
+    match fully_pub_core::config(file.as_deref()) {
+        Ok(()) => TokenStream::new(),
         Err(e) => e.to_compile_error().into(),
     }
 }
@@ -0,0 +1,187 @@
+//! Snapshot testing of the expansion of the [`fully_pub`](https://docs.rs/fully_pub)
+//! attribute macro, for crates that want to check which visibilities their annotated
+//! items end up with.
+//!
+//! A snapshot is a pair of files: an input file with annotated items, and the file it
+//! is expected to expand to. The test fails with the lines that differ when the
+//! expansion changes:
+//!
+//! ```no_run
+//! // tests/expansion.rs
+//! #[test]
+//! fn models() {
+//!     fully_pub_testing::assert_fully_pub_expansion!(
+//!         "tests/expansion/models.rs",
+//!         "tests/expansion/models.expanded.rs",
+//!     );
+//! }
+//! ```
+//!
+//! Running the tests with the [`BLESS_VAR`] environment variable set writes the
+//! expected files instead, to create them or to accept a change:
+//!
+//! ```text
+//! FULLY_PUB_BLESS=1 cargo test --test expansion
+//! ```
+//!
+//! The files are expanded the way the compiler would: only the annotated items are
+//! changed, with the defaults of `[package.metadata.fully_pub]` and `fully_pub::config!`
+//! of the crate under test.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use fully_pub_core::Config;
+
+/// The environment variable which, when set to a non-empty value, makes the
+/// assertions write the expected files instead of comparing them.
+pub const BLESS_VAR: &str = "FULLY_PUB_BLESS";
+
+/// Asserts that the file `$input` expands to the file `$expected`, both relative to
+/// the manifest directory of the crate under test. See [`assert_expansion`].
+#[macro_export]
+macro_rules! assert_fully_pub_expansion {
+    ($input:expr, $expected:expr $(,)?) => {
+        $crate::assert_expansion(::std::env!("CARGO_MANIFEST_DIR"), $input, $expected)
+    };
+}
+
+/// Asserts that the Rust source file `input` expands to the content of the file
+/// `expected`, both relative to `manifest_dir`, the directory of the manifest whose
+/// configuration applies.
+///
+/// When [`BLESS_VAR`] is set, the expansion is written to `expected` instead, with
+/// `\n` line endings whatever those of `input`: the line endings of the files are not
+/// compared, for the snapshots to be the same on every platform.
+///
+/// ```
+/// # let dir = std::env::temp_dir().join("fully_pub_testing_doc");
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// std::fs::write(
+///     dir.join("Cargo.toml"),
+///     "[package]\nname = \"app\"\n\n[package.metadata.fully_pub]\nvis = \"pub(crate)\"\n",
+/// )
+/// .unwrap();
+/// std::fs::write(dir.join("input.rs"), "#[fully_pub]\nstruct Id(u64);\n").unwrap();
+/// std::fs::write(dir.join("expected.rs"), "pub(crate) struct Id(pub(crate) u64);\n").unwrap();
+///
+/// fully_pub_testing::assert_expansion(&dir, "input.rs", "expected.rs");
+/// ```
+///
+/// # Panics
+///
+/// Panics with the lines that differ if the expansion is not the expected one, and if
+/// a file can't be read or written, the configuration is invalid or the input contains
+/// an invalid use of the macro.
+///
+/// ```should_panic
+/// # let dir = std::env::temp_dir().join("fully_pub_testing_doc_panic");
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// # std::fs::write(dir.join("Cargo.toml"), "[package]\nname = \"app\"\n").unwrap();
+/// std::fs::write(dir.join("input.rs"), "#[fully_pub]\nstruct Id(u64);\n").unwrap();
+/// std::fs::write(dir.join("expected.rs"), "pub struct Id(u64);\n").unwrap();
+///
+/// // `input.rs` does not expand to `expected.rs`:
+/// //    1 - pub struct Id(u64);
+/// //    1 + pub struct Id(pub u64);
+/// fully_pub_testing::assert_expansion(&dir, "input.rs", "expected.rs");
+/// ```
+#[track_caller]
+pub fn assert_expansion(
+    manifest_dir: impl AsRef<Path>,
+    input: impl AsRef<Path>,
+    expected: impl AsRef<Path>,
+) {
+    let (manifest_dir, input, expected) =
+        (manifest_dir.as_ref(), input.as_ref(), expected.as_ref());
+
+    if let Err(message) = check(manifest_dir, input, expected) {
+        panic!("{message}");
+    }
+}
+
+/// Compares the expansion of `input` with `expected`, or writes it when blessing.
+fn check(manifest_dir: &Path, input: &Path, expected: &Path) -> Result<(), String> {
+    let (input_path, expected_path) = (manifest_dir.join(input), manifest_dir.join(expected));
+
+    // The messages name the files relative to the manifest, to be the same on every
+    // machine, and the line endings are normalized for the expansion to be as well.
+    let source = fs::read_to_string(&input_path)
+        .map_err(|e| format!("{}: {e}", input.display()))?
+        .replace("\r\n", "\n");
+    let config = Config::load(manifest_dir).map_err(|e| e.to_string())?;
+
+    let mut options = fully_pub_core::Options::default();
+    options.config = config.in_file(&input_path);
+
+    let actual = fully_pub_core::publish(&source, &options).map_err(|e| {
+        let start = e.span().start();
+        format!(
+            "{}:{}:{}: {e}",
+            input.display(),
+            start.line,
+            start.column + 1
+        )
+    })?;
+
+    if std::env::var_os(BLESS_VAR).is_some_and(|bless| !bless.is_empty()) {
+        return fs::write(&expected_path, actual)
+            .map_err(|e| format!("{}: {e}", expected.display()));
+    }
+
+    let expected_source = fs::read_to_string(&expected_path).map_err(|e| {
+        format!(
+            "{}: {e}\nhelp: set `{BLESS_VAR}=1` to write the expected expansion",
+            expected.display()
+        )
+    })?;
+
+    if expected_source.replace("\r\n", "\n") == actual {
+        return Ok(());
+    }
+
+    Err(format!(
+        "`{}` does not expand to `{}`:\n{}help: set `{BLESS_VAR}=1` to accept the new expansion",
+        input.display(),
+        expected.display(),
+        diff(&expected_source, &actual),
+    ))
+}
+
+/// Lists the lines removed from `expected` with `-` and the lines added by `actual`
+/// with `+`, each with its line number in its own file.
+fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+
+    // The length of the longest common subsequence of the lines after `i` and `j`.
+    let mut lengths = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lengths[i][j] = match expected[i] == actual[j] {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            (i, j) = (i + 1, j + 1);
+        } else if j == actual.len()
+            || (i < expected.len() && lengths[i + 1][j] >= lengths[i][j + 1])
+        {
+            let _ = writeln!(out, "{:>4} - {}", i + 1, expected[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "{:>4} + {}", j + 1, actual[j]);
+            j += 1;
+        }
+    }
+
+    out
+}